@@ -5,11 +5,20 @@ use std::ops::{Deref, DerefMut};
 use std::ptr::{self, Unique, NonNull};
 
 const CACHE_LINE_SIZE: usize = 64;
-unsafe fn allocate_cache_line(size: usize) -> *mut u8 {
+/// Allocate `size` zeroed bytes starting on a cache line boundary.
+pub(crate) unsafe fn allocate_cache_line(size: usize) -> *mut u8 {
     alloc::Global.alloc_zeroed(Layout::from_size_align(size, CACHE_LINE_SIZE).unwrap())
         .unwrap().as_ptr() as *mut u8
 }
 
+/// Free memory allocated by `allocate_cache_line`.
+pub(crate) unsafe fn free_cache_line(ptr: *mut u8, size: usize) {
+    alloc::Global.dealloc(
+        NonNull::<u8>::new_unchecked(ptr),
+        Layout::from_size_align(size, CACHE_LINE_SIZE).unwrap(),
+    );
+}
+
 pub struct CacheAligned<T: Sized> {
     ptr: Unique<T>,
 }
@@ -17,10 +26,7 @@ pub struct CacheAligned<T: Sized> {
 impl<T: Sized> Drop for CacheAligned<T> {
     fn drop(&mut self) {
         unsafe {
-            alloc::Global.dealloc(
-                NonNull::<u8>::new_unchecked(self.ptr.as_ptr() as *mut u8),
-                Layout::from_size_align(size_of::<T>(), CACHE_LINE_SIZE).unwrap(),
-            );
+            free_cache_line(self.ptr.as_ptr() as *mut u8, size_of::<T>());
         }
     }
 }
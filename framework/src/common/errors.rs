@@ -28,6 +28,14 @@ error_chain! {
             description("Bad vdev specification")
             display("Bad vdev specification: {}", vdev)
         }
+        FailedToConfigureRss(port: i32) {
+            description("Failed to configure RSS")
+            display("Failed to configure RSS for port: {}", port)
+        }
+        FlowRuleError(description: String) {
+            description("Failed to install flow rule")
+            display("Failed to install flow rule: {}", description)
+        }
         BadTxQueue(port: i32, queue: i32) {
             description("Bad TX queue")
             display("Bad TX queue {} for port {}", queue, port)
@@ -46,6 +54,36 @@ error_chain! {
             display("Metadata is too large")
         }
 
+        MetadataFieldMismatch(name: String) {
+            description("Metadata field already registered with a different type")
+            display("Metadata field {} already registered with a different type", name)
+        }
+
+        MetadataRegistrationClosed(name: String) {
+            description("Metadata field registered after operators adding metadata were built")
+            display("Metadata field {} registered after operators adding metadata were built", name)
+        }
+
+        FailedToCreateMempool(name: String) {
+            description("Failed to create mempool")
+            display("Failed to create mempool {}", name)
+        }
+
+        FailedToAttachRing(name: String) {
+            description("Failed to create or find named ring")
+            display("Failed to create or find ring {}", name)
+        }
+
+        InvalidSharedEntry(name: String) {
+            description("Shared state entry has no data or does not match its segment")
+            display("Shared state entry {} has no data or does not match its segment", name)
+        }
+
+        BadCheckpoint(description: String) {
+            description("Checkpoint does not match the state being restored")
+            display("Checkpoint does not match the state being restored: {}", description)
+        }
+
         RingAllocationFailure {
             description("Could not allocate ring")
             display("Could not allocate ring")
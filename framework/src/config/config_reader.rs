@@ -1,5 +1,6 @@
-use super::{NetbricksConfiguration, PortConfiguration};
+use super::{MempoolConfiguration, NetbricksConfiguration, PortConfiguration, RssConfiguration, RssHash};
 use common::*;
+use interface::METADATA_SLOTS;
 use std::fs::File;
 use std::io::Read;
 use toml::{self, Value};
@@ -7,12 +8,82 @@ use toml::{self, Value};
 /// Default configuration values
 pub const DEFAULT_POOL_SIZE: u32 = 2048 - 1;
 pub const DEFAULT_CACHE_SIZE: u32 = 32;
+/// Packet data in each mbuf, matches DPDK's RTE_MBUF_DEFAULT_DATAROOM.
+pub const DEFAULT_MBUF_DATA_ROOM: u16 = 2048;
+/// Matches DPDK's RTE_PKTMBUF_HEADROOM.
+pub const DEFAULT_MBUF_HEADROOM: u16 = 128;
+/// Smallest data room that fits a standard Ethernet frame.
+pub const MIN_MBUF_DATA_ROOM: u16 = 1518;
 pub const DEFAULT_SECONDARY: bool = false;
 pub const DEFAULT_PRIMARY_CORE: i32 = 0;
 pub const DEFAULT_NAME: &'static str = "zcsi";
 pub const NUM_RXD: i32 = 128;
 pub const NUM_TXD: i32 = 128;
 
+/// Read the RSS table for a port.
+fn read_rss(value: &Value) -> Result<RssConfiguration> {
+    if let Value::Table(ref rss_def) = *value {
+        let hash = match rss_def.get("hash") {
+            Some(&Value::Array(ref hashes)) => {
+                let mut hs = Vec::with_capacity(hashes.len());
+                for h in hashes {
+                    match *h {
+                        Value::String(ref name) => match RssHash::from_name(name) {
+                            Some(hash) => hs.push(hash),
+                            None => {
+                                return Err(ErrorKind::ConfigurationError(format!("Unknown RSS hash {}", name)).into())
+                            }
+                        },
+                        _ => {
+                            return Err(
+                                ErrorKind::ConfigurationError(format!("Could not parse RSS hash {:?}", h)).into(),
+                            )
+                        }
+                    }
+                }
+                Some(hs)
+            }
+            None => None,
+            v => return Err(ErrorKind::ConfigurationError(format!("Could not parse RSS hash spec {:?}", v)).into()),
+        };
+
+        let symmetric = match rss_def.get("symmetric") {
+            Some(&Value::Boolean(s)) => s,
+            None => false,
+            v => return Err(ErrorKind::ConfigurationError(format!("Could not parse symmetric spec {:?}", v)).into()),
+        };
+
+        let reta = match rss_def.get("reta") {
+            Some(&Value::Array(ref entries)) => {
+                let mut reta = Vec::with_capacity(entries.len());
+                for e in entries {
+                    match *e {
+                        Value::Integer(queue) if queue >= 0 && queue <= i64::from(u16::max_value()) => {
+                            reta.push(queue as u16)
+                        }
+                        _ => {
+                            return Err(
+                                ErrorKind::ConfigurationError(format!("Could not parse RETA entry {:?}", e)).into(),
+                            )
+                        }
+                    }
+                }
+                reta
+            }
+            None => vec![],
+            v => return Err(ErrorKind::ConfigurationError(format!("Could not parse RETA spec {:?}", v)).into()),
+        };
+
+        Ok(RssConfiguration {
+            hash: hash,
+            symmetric: symmetric,
+            reta: reta,
+        })
+    } else {
+        Err(ErrorKind::ConfigurationError(String::from("Could not understand RSS spec")).into())
+    }
+}
+
 /// Read a TOML stub and figure out the port.
 fn read_port(value: &Value) -> Result<PortConfiguration> {
     if let Value::Table(ref port_def) = *value {
@@ -59,6 +130,51 @@ fn read_port(value: &Value) -> Result<PortConfiguration> {
             v => return Err(ErrorKind::ConfigurationError(format!("Could not parse csum spec {:?}", v)).into()),
         };
 
+        let vlan_strip = match port_def.get("vlan_strip") {
+            Some(&Value::Boolean(l)) => l,
+            None => false,
+            v => return Err(ErrorKind::ConfigurationError(format!("Could not parse vlan_strip spec {:?}", v)).into()),
+        };
+
+        let ptype = match port_def.get("ptype") {
+            Some(&Value::Boolean(l)) => l,
+            None => false,
+            v => return Err(ErrorKind::ConfigurationError(format!("Could not parse ptype spec {:?}", v)).into()),
+        };
+
+        let timestamp = match port_def.get("timestamp") {
+            Some(&Value::Boolean(l)) => l,
+            None => false,
+            v => return Err(ErrorKind::ConfigurationError(format!("Could not parse timestamp spec {:?}", v)).into()),
+        };
+
+        let rss = match port_def.get("rss") {
+            Some(v) => try!(read_rss(v)),
+            None => Default::default(),
+        };
+
+        let mempool = match port_def.get("mempool") {
+            Some(&Value::String(ref pool)) => Some(pool.clone()),
+            None => None,
+            v => return Err(ErrorKind::ConfigurationError(format!("Could not parse mempool {:?}", v)).into()),
+        };
+
+        let rx_mempools = match port_def.get("rx_mempools") {
+            Some(&Value::Array(ref pools)) => {
+                let mut ps = Vec::with_capacity(pools.len());
+                for pool in pools {
+                    if let Value::String(ref pool) = *pool {
+                        ps.push(pool.clone())
+                    } else {
+                        return Err(ErrorKind::ConfigurationError(format!("Could not parse mempool {:?}", pool)).into());
+                    }
+                }
+                ps
+            }
+            None => vec![],
+            v => return Err(ErrorKind::ConfigurationError(format!("Could not parse rx_mempools {:?}", v)).into()),
+        };
+
         let symmetric_queue = port_def.contains_key("cores");
         if symmetric_queue && (port_def.contains_key("rx_cores") || port_def.contains_key("tx_cores")) {
             println!(
@@ -119,12 +235,55 @@ fn read_port(value: &Value) -> Result<PortConfiguration> {
             loopback: loopback,
             csum: csum,
             tso: tso,
+            vlan_strip: vlan_strip,
+            ptype: ptype,
+            timestamp: timestamp,
+            rss: rss,
+            mempool: mempool,
+            rx_mempools: rx_mempools,
         })
     } else {
         Err(ErrorKind::ConfigurationError(String::from("Could not understand port spec")).into())
     }
 }
 
+/// Read a named mempool, defaults are the same as for the default mempools.
+fn read_mempool(value: &Value) -> Result<MempoolConfiguration> {
+    if let Value::Table(ref pool_def) = *value {
+        let name = match pool_def.get("name") {
+            Some(&Value::String(ref name)) => name.clone(),
+            _ => return Err(ErrorKind::ConfigurationError(String::from("Could not parse name for mempool")).into()),
+        };
+
+        let size = match pool_def.get("size") {
+            Some(&Value::Integer(size)) if size > 0 => size as u32,
+            None => DEFAULT_POOL_SIZE,
+            v => return Err(ErrorKind::ConfigurationError(format!("Could not parse mempool size {:?}", v)).into()),
+        };
+
+        let cache_size = match pool_def.get("cache_size") {
+            Some(&Value::Integer(cache)) if cache >= 0 => cache as u32,
+            None => DEFAULT_CACHE_SIZE,
+            v => return Err(ErrorKind::ConfigurationError(format!("Could not parse mempool cache {:?}", v)).into()),
+        };
+
+        let socket = match pool_def.get("socket") {
+            Some(&Value::Integer(socket)) if socket >= 0 => Some(socket as i32),
+            None => None,
+            v => return Err(ErrorKind::ConfigurationError(format!("Could not parse mempool socket {:?}", v)).into()),
+        };
+
+        Ok(MempoolConfiguration {
+            name: name,
+            size: size,
+            cache_size: cache_size,
+            socket: socket,
+        })
+    } else {
+        Err(ErrorKind::ConfigurationError(String::from("Could not understand mempool spec")).into())
+    }
+}
+
 /// Read a TOML string and create a `NetbricksConfiguration` structure.
 /// `configuration` is a TOML formatted string.
 /// `filename` is used for error reporting purposes, and is otherwise meaningless.
@@ -182,6 +341,30 @@ pub fn read_configuration_from_str(configuration: &str, filename: &str) -> Resul
         }
     };
 
+    let metadata_slots = match toml.get("metadata_slots") {
+        Some(&Value::Integer(slots)) if slots > 0 && slots <= u16::max_value() as i64 => slots as u16,
+        None => METADATA_SLOTS,
+        _ => {
+            return Err(ErrorKind::ConfigurationError(String::from("Could not parse metadata slots")).into());
+        }
+    };
+
+    let mbuf_data_room = match toml.get("mbuf_data_room") {
+        Some(&Value::Integer(room)) if room > 0 && room <= u16::max_value() as i64 => room as u16,
+        None => DEFAULT_MBUF_DATA_ROOM,
+        _ => {
+            return Err(ErrorKind::ConfigurationError(String::from("Could not parse mbuf data room")).into());
+        }
+    };
+
+    let mbuf_headroom = match toml.get("mbuf_headroom") {
+        Some(&Value::Integer(room)) if room >= 0 && room <= u16::max_value() as i64 => room as u16,
+        None => DEFAULT_MBUF_HEADROOM,
+        _ => {
+            return Err(ErrorKind::ConfigurationError(String::from("Could not parse mbuf headroom")).into());
+        }
+    };
+
     // Is process a secondary process
     let secondary = match toml.get("secondary") {
         Some(&Value::Boolean(secondary)) => secondary,
@@ -240,7 +423,30 @@ pub fn read_configuration_from_str(configuration: &str, filename: &str) -> Resul
         }
     };
 
-    Ok(NetbricksConfiguration {
+    let mempools = match toml.get("mempools") {
+        Some(&Value::Array(ref pools)) => {
+            let mut pouts = Vec::with_capacity(pools.len());
+            for pool in pools {
+                pouts.push(try!(read_mempool(pool)));
+            }
+            pouts
+        }
+        None => vec![],
+        _ => return Err(ErrorKind::ConfigurationError(String::from("Mempools is not an array")).into()),
+    };
+
+    for port in &ports {
+        for pool in port.mempool.iter().chain(port.rx_mempools.iter()) {
+            if !mempools.iter().any(|p| &p.name == pool) {
+                return Err(ErrorKind::ConfigurationError(format!(
+                    "Port {} uses undefined mempool {}",
+                    port.name, pool
+                )).into());
+            }
+        }
+    }
+
+    let config = NetbricksConfiguration {
         name: name,
         primary_core: master_lcore,
         cores: cores,
@@ -248,9 +454,15 @@ pub fn read_configuration_from_str(configuration: &str, filename: &str) -> Resul
         secondary: secondary,
         pool_size: pool_size,
         cache_size: cache_size,
+        metadata_slots: metadata_slots,
+        mbuf_data_room: mbuf_data_room,
+        mbuf_headroom: mbuf_headroom,
         ports: ports,
+        mempools: mempools,
         dpdk_args: None,
-    })
+    };
+    try!(config.check_mbuf_layout());
+    Ok(config)
 }
 
 /// Read a configuration file and create a `NetbricksConfiguration` structure.
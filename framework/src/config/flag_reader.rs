@@ -1,10 +1,11 @@
 extern crate getopts;
 use self::getopts::{Matches, Options};
 use super::{read_configuration, NetbricksConfiguration, PortConfiguration};
-use common::print_error;
+use common::*;
 use std::collections::HashMap;
 use std::env;
 use std::process;
+use std::str::FromStr;
 
 /// Return a `getopts::Options` struct, preset so that it's ready to parse the
 /// configuration flags commonly used during Netbricks examples.
@@ -19,6 +20,9 @@ pub fn basic_opts() -> Options {
     opts.optopt("m", "master", "Master core", "master");
     opts.optopt("f", "configuration", "Configuration file", "path");
     opts.optmulti("", "dpdk_args", "DPDK arguments", "DPDK arguments");
+    opts.optopt("", "metadata_slots", "Number of 8-byte metadata slots per packet", "slots");
+    opts.optopt("", "mbuf_data_room", "Bytes of packet data each mbuf can hold", "bytes");
+    opts.optopt("", "mbuf_headroom", "Bytes reserved in front of packet data in allocated mbufs", "bytes");
 
     opts
 }
@@ -66,6 +70,14 @@ pub fn read_matches(matches: &Matches, opts: &Options) -> NetbricksConfiguration
         configuration
     };
 
+    let configuration = match read_mbuf_layout(matches, configuration) {
+        Ok(cfg) => cfg,
+        Err(ref e) => {
+            print_error(e);
+            process::exit(1);
+        }
+    };
+
     let configuration = if matches.opt_present("secondary") {
         NetbricksConfiguration {
             secondary: true,
@@ -116,10 +128,35 @@ pub fn read_matches(matches: &Matches, opts: &Options) -> NetbricksConfiguration
         configuration
     };
 
+    if let Err(ref e) = configuration.check_mbuf_layout() {
+        print_error(e);
+        process::exit(1);
+    }
+
     println!("Going to start with configuration {}", configuration);
     configuration
 }
 
+/// Parse the value of flag `name`, returning `default` if it was not given.
+fn parse_opt<T: FromStr>(matches: &Matches, name: &str, default: T) -> Result<T> {
+    match matches.opt_str(name) {
+        Some(value) => match value.parse() {
+            Ok(v) => Ok(v),
+            Err(_) => Err(ErrorKind::ConfigurationError(format!("Could not parse {} {}", name, value)).into()),
+        },
+        None => Ok(default),
+    }
+}
+
+fn read_mbuf_layout(matches: &Matches, configuration: NetbricksConfiguration) -> Result<NetbricksConfiguration> {
+    Ok(NetbricksConfiguration {
+        metadata_slots: try!(parse_opt(matches, "metadata_slots", configuration.metadata_slots)),
+        mbuf_data_room: try!(parse_opt(matches, "mbuf_data_room", configuration.mbuf_data_room)),
+        mbuf_headroom: try!(parse_opt(matches, "mbuf_headroom", configuration.mbuf_headroom)),
+        ..configuration
+    })
+}
+
 fn extract_cores_for_port(ports: &[String], cores: &[i32]) -> HashMap<String, Vec<i32>> {
     let mut cores_for_port = HashMap::<String, Vec<i32>>::new();
     for (port, core) in ports.iter().zip(cores.iter()) {
@@ -1,5 +1,8 @@
 pub use self::config_reader::*;
 pub use self::flag_reader::*;
+use common::*;
+use interface::{METADATA_SLOTS, RESERVED_METADATA_SLOTS};
+use std::cmp::max;
 use std::fmt;
 mod config_reader;
 mod flag_reader;
@@ -9,7 +12,9 @@ mod flag_reader;
 pub struct NetbricksConfiguration {
     /// Name, this is passed on to DPDK. If you want to run multiple DPDK apps, this needs to be unique per application.
     pub name: String,
-    /// Should this process be run as a secondary process or a primary process?
+    /// Should this process be run as a secondary process or a primary process? Secondary processes share the
+    /// primary's mempools and cannot use devices directly, instead they use `nf:<name>` ports to exchange packets with
+    /// the primary.
     pub secondary: bool,
     /// Where should the main thread (for the examples this just sits around and prints packet counts) be run.
     pub primary_core: i32,
@@ -26,6 +31,18 @@ pub struct NetbricksConfiguration {
     pub pool_size: u32,
     /// Size of the per-core mempool cache.
     pub cache_size: u32,
+    /// Number of 8-byte metadata slots stored with each packet. Some are used internally (see
+    /// `RESERVED_METADATA_SLOTS`), the rest hold freeform metadata and registered metadata fields.
+    pub metadata_slots: u16,
+    /// Bytes of packet data each mbuf can hold, not counting headroom. Ports accept jumbo frames up to this size when
+    /// it is larger than a standard Ethernet frame.
+    pub mbuf_data_room: u16,
+    /// Bytes left in front of packet data in mbufs allocated by NetBricks, available for prepending headers. Packets
+    /// received from DPDK ports always have DPDK's default headroom (128 bytes).
+    pub mbuf_headroom: u16,
+    /// Additional named mempools, which ports can use for some or all of their RX queues and threads can allocate
+    /// packets from (see `interface::dpdk::set_alloc_mempool`).
+    pub mempools: Vec<MempoolConfiguration>,
     /// Custom DPDK arguments.
     pub dpdk_args: Option<String>,
 }
@@ -37,11 +54,15 @@ impl Default for NetbricksConfiguration {
             name: String::new(),
             pool_size: DEFAULT_POOL_SIZE,
             cache_size: DEFAULT_CACHE_SIZE,
+            metadata_slots: METADATA_SLOTS,
+            mbuf_data_room: DEFAULT_MBUF_DATA_ROOM,
+            mbuf_headroom: DEFAULT_MBUF_HEADROOM,
             primary_core: 0,
             cores: Default::default(),
             strict: false,
             secondary: false,
             ports: vec![],
+            mempools: vec![],
             dpdk_args: None,
         }
     }
@@ -57,16 +78,54 @@ impl NetbricksConfiguration {
     }
 }
 
+impl NetbricksConfiguration {
+    /// Check that the metadata and mbuf sizes can be used to create mempools.
+    pub fn check_mbuf_layout(&self) -> Result<()> {
+        if self.metadata_slots <= RESERVED_METADATA_SLOTS {
+            return Err(ErrorKind::ConfigurationError(format!(
+                "At least {} metadata slots are required",
+                RESERVED_METADATA_SLOTS + 1
+            )).into());
+        }
+        if self.metadata_slots as usize * 8 > u16::max_value() as usize {
+            return Err(
+                ErrorKind::ConfigurationError(format!("Too many metadata slots {}", self.metadata_slots)).into(),
+            );
+        }
+        if self.mbuf_data_room < MIN_MBUF_DATA_ROOM {
+            return Err(ErrorKind::ConfigurationError(format!(
+                "mbuf data room must be at least {} bytes",
+                MIN_MBUF_DATA_ROOM
+            )).into());
+        }
+        let headroom = max(self.mbuf_headroom, DEFAULT_MBUF_HEADROOM) as usize;
+        if headroom + self.mbuf_data_room as usize > u16::max_value() as usize {
+            return Err(ErrorKind::ConfigurationError(String::from(
+                "mbuf headroom and data room must add up to less than 64KB",
+            )).into());
+        }
+        Ok(())
+    }
+}
+
 impl fmt::Display for NetbricksConfiguration {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         try!(write!(
             f,
-            "Configuration: name: {} mempool size: {} core cache: {} primary core: {}\n Ports:\n",
+            "Configuration: name: {} mempool size: {} core cache: {} primary core: {}\n",
             self.name, self.pool_size, self.cache_size, self.primary_core
         ));
+        try!(write!(
+            f,
+            "Metadata slots: {} mbuf data room: {} mbuf headroom: {}\n Ports:\n",
+            self.metadata_slots, self.mbuf_data_room, self.mbuf_headroom
+        ));
         for port in &self.ports {
             try!(write!(f, "\t{}\n", port))
         }
+        for mempool in &self.mempools {
+            try!(write!(f, "\t{}\n", mempool))
+        }
         try!(write!(f, "Cores:\n"));
         for core in &self.cores {
             try!(write!(f, "\t{}\n", core))
@@ -78,6 +137,39 @@ impl fmt::Display for NetbricksConfiguration {
     }
 }
 
+/// A named mempool. Mbufs in all mempools have the same layout (metadata slots, data room and headroom).
+#[derive(Clone)]
+pub struct MempoolConfiguration {
+    pub name: String,
+    /// Number of mbufs in the pool.
+    pub size: u32,
+    /// Size of the per-core mempool cache.
+    pub cache_size: u32,
+    /// NUMA node on which to allocate the pool, `None` lets DPDK choose.
+    pub socket: Option<i32>,
+}
+
+impl MempoolConfiguration {
+    pub fn new(name: &str, size: u32) -> MempoolConfiguration {
+        MempoolConfiguration {
+            name: String::from(name),
+            size: size,
+            cache_size: DEFAULT_CACHE_SIZE,
+            socket: None,
+        }
+    }
+}
+
+impl fmt::Display for MempoolConfiguration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "Mempool {} size: {} cache: {}", self.name, self.size, self.cache_size));
+        match self.socket {
+            Some(socket) => write!(f, " socket: {}", socket),
+            None => write!(f, " socket: any"),
+        }
+    }
+}
+
 /// Configuration for each port (network device) in `NetBricks`.
 pub struct PortConfiguration {
     /// Name. The exact semantics vary by backend. For DPDK, we allow things of the form:
@@ -85,6 +177,12 @@ pub struct PortConfiguration {
     ///    dpdk:<PMD Descriptor>: PMD driver with arguments
     ///    bess:<port_name>: BESS RingVport with name.
     ///    ovs:<port_id>: OVS ring with ID.
+    ///    nf:<name>: Rings shared between a primary process, which creates them, and a secondary process, which
+    ///               attaches to them. Packets are passed between processes without copying.
+    ///    ring:<name>: Named rings, created by whichever process uses them first. Packets sent to a queue are received
+    ///                 from the same queue, chaining pipelines in the same or different processes.
+    ///    af_packet:<interface>: Existing Linux interface, accessed through an AF_PACKET socket. Packets are copied.
+    ///    tap:<interface>: TAP device, created if it does not exist. Packets are copied.
     pub name: String,
     /// Core on which receive node for a given queue lives.
     pub rx_queues: Vec<i32>,
@@ -96,7 +194,23 @@ pub struct PortConfiguration {
     pub txd: i32,
     pub loopback: bool,
     pub tso: bool,
+    /// Checksum offload, for received packets the NIC reports whether checksums are valid (see
+    /// `Packet::ip_checksum`).
     pub csum: bool,
+    /// Have the NIC strip VLAN tags, the tag is then available through `Packet::vlan_tci`.
+    pub vlan_strip: bool,
+    /// Require the NIC to classify received packets (see `Packet::packet_type`), port creation fails if it cannot.
+    pub ptype: bool,
+    /// Have the NIC timestamp received packets (see `Packet::hw_rx_timestamp`), port creation fails if it cannot. Only
+    /// DPDK ports support hardware timestamps.
+    pub timestamp: bool,
+    /// How received packets are spread across RX queues.
+    pub rss: RssConfiguration,
+    /// Named mempool (see `NetbricksConfiguration::mempools`) for RX queues without an entry in `rx_mempools`. When
+    /// `None`, queues use the default mempool for the NUMA node of the core polling them.
+    pub mempool: Option<String>,
+    /// Named mempools for individual RX queues, indexed by queue.
+    pub rx_mempools: Vec<String>,
 }
 
 impl Default for PortConfiguration {
@@ -110,6 +224,12 @@ impl Default for PortConfiguration {
             loopback: false,
             tso: false,
             csum: false,
+            vlan_strip: false,
+            ptype: false,
+            timestamp: false,
+            rss: Default::default(),
+            mempool: None,
+            rx_mempools: vec![],
         }
     }
 }
@@ -129,6 +249,11 @@ impl PortConfiguration {
             ..PortConfiguration::new_with_name(name)
         }
     }
+
+    /// Name of the mempool RX queue `queue` should use, `None` for the default.
+    pub fn rx_mempool(&self, queue: usize) -> Option<&str> {
+        self.rx_mempools.get(queue).or(self.mempool.as_ref()).map(|s| &s[..])
+    }
 }
 
 impl fmt::Display for PortConfiguration {
@@ -139,7 +264,7 @@ impl fmt::Display for PortConfiguration {
         let tx_queue_str = tx_queues_str_vec.join(" ");
         write!(
             f,
-            "Port {} RXQ_Count: {} RX_Queues: [ {} ] TXQ_Count: {} TX_Queues: {} RXD: {} TXD: {} Loopback {}",
+            "Port {} RXQ_Count: {} RX_Queues: [ {} ] TXQ_Count: {} TX_Queues: {} RXD: {} TXD: {} Loopback {} RSS: {}",
             self.name,
             self.rx_queues.len(),
             rx_queue_str,
@@ -147,7 +272,62 @@ impl fmt::Display for PortConfiguration {
             tx_queue_str,
             self.rxd,
             self.txd,
-            self.loopback
+            self.loopback,
+            self.rss
         )
     }
 }
+
+/// Header fields that can be hashed by RSS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RssHash {
+    Ipv4,
+    Ipv6,
+    Tcp,
+    Udp,
+    Sctp,
+}
+
+impl RssHash {
+    /// Parse the name used for this hash type in configuration files.
+    pub fn from_name(name: &str) -> Option<RssHash> {
+        match name {
+            "ipv4" => Some(RssHash::Ipv4),
+            "ipv6" => Some(RssHash::Ipv6),
+            "tcp" => Some(RssHash::Tcp),
+            "udp" => Some(RssHash::Udp),
+            "sctp" => Some(RssHash::Sctp),
+            _ => None,
+        }
+    }
+}
+
+/// Receive side scaling (RSS) configuration for a port. The defaults leave the NIC's hashing as is.
+#[derive(Clone, Default)]
+pub struct RssConfiguration {
+    /// Header fields to hash, `None` uses the default (IP, TCP, UDP and SCTP).
+    pub hash: Option<Vec<RssHash>>,
+    /// Use a symmetric Toeplitz key, so that both directions of a flow are received on the same queue. Useful for
+    /// stateful NFs that need to see both sides of a connection on one core.
+    pub symmetric: bool,
+    /// Redirection table: hash bucket `i` is received on queue `reta[i % reta.len()]`. Empty leaves the NIC's table
+    /// (usually round robin across all queues) in place.
+    pub reta: Vec<u16>,
+}
+
+impl RssConfiguration {
+    /// True if this configuration changes anything from what the port is initialized with.
+    pub fn is_default(&self) -> bool {
+        self.hash.is_none() && !self.symmetric && self.reta.is_empty()
+    }
+}
+
+impl fmt::Display for RssConfiguration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.hash {
+            Some(ref hash) => try!(write!(f, "hash: {:?}", hash)),
+            None => try!(write!(f, "hash: default")),
+        };
+        write!(f, " symmetric: {} reta: {:?}", self.symmetric, self.reta)
+    }
+}
@@ -1,12 +1,40 @@
 use super::METADATA_SLOTS;
-use config::{NetbricksConfiguration, DEFAULT_CACHE_SIZE, DEFAULT_POOL_SIZE};
+use super::packet::set_metadata_slots;
+use common::*;
+use config::{MempoolConfiguration, NetbricksConfiguration, DEFAULT_CACHE_SIZE, DEFAULT_MBUF_DATA_ROOM,
+             DEFAULT_MBUF_HEADROOM, DEFAULT_POOL_SIZE};
 use native::libnuma;
 use native::zcsi;
 use std::cell::Cell;
 use std::ffi::CString;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SECONDARY: AtomicBool = AtomicBool::new(false);
+
+/// Mempool and mbuf layout used when initializing the system.
+struct MempoolLayout {
+    pool_size: u32,
+    cache_size: u32,
+    metadata_slots: u16,
+    data_room: u16,
+    headroom: u16,
+}
+
+impl Default for MempoolLayout {
+    fn default() -> MempoolLayout {
+        MempoolLayout {
+            pool_size: DEFAULT_POOL_SIZE,
+            cache_size: DEFAULT_CACHE_SIZE,
+            metadata_slots: METADATA_SLOTS,
+            data_room: DEFAULT_MBUF_DATA_ROOM,
+            headroom: DEFAULT_MBUF_HEADROOM,
+        }
+    }
+}
 
 /// Initialize the system, whitelisting some set of NICs and allocating mempool of given size.
-fn init_system_wl_with_mempool(name: &str, core: i32, pci: &[String], pool_size: u32, cache_size: u32) {
+fn init_system_wl_with_mempool(name: &str, core: i32, pci: &[String], layout: &MempoolLayout) {
     let name_cstr = CString::new(name).unwrap();
     let pci_cstr: Vec<_> = pci.iter().map(|p| CString::new(&p[..]).unwrap()).collect();
     let mut whitelist: Vec<_> = pci_cstr.iter().map(|p| p.as_ptr()).collect();
@@ -17,19 +45,22 @@ fn init_system_wl_with_mempool(name: &str, core: i32, pci: &[String], pool_size:
             core,
             whitelist.as_mut_ptr(),
             pci.len() as i32,
-            pool_size,
-            cache_size,
-            METADATA_SLOTS,
+            layout.pool_size,
+            layout.cache_size,
+            layout.metadata_slots,
+            layout.data_room as u32,
+            layout.headroom as u32,
         );
         if ret != 0 {
             panic!("Could not initialize the system errno {}", ret)
         }
     }
+    set_metadata_slots(layout.metadata_slots);
 }
 
 /// Initialize the system, whitelisting some set of NICs.
 pub fn init_system_wl(name: &str, core: i32, pci: &[String]) {
-    init_system_wl_with_mempool(name, core, pci, DEFAULT_POOL_SIZE, DEFAULT_CACHE_SIZE);
+    init_system_wl_with_mempool(name, core, pci, &Default::default());
     set_numa_domain();
 }
 
@@ -48,10 +79,18 @@ pub fn init_system_secondary(name: &str, core: i32) {
         if ret != 0 {
             panic!("Could not initialize secondary process errno {}", ret)
         }
+        // Use the layout chosen by the primary process.
+        set_metadata_slots(zcsi::mempool_metadata_slots());
     }
+    SECONDARY.store(true, Ordering::Release);
     set_numa_domain();
 }
 
+/// Is this a DPDK secondary process?
+pub fn is_secondary() -> bool {
+    SECONDARY.load(Ordering::Acquire)
+}
+
 /// Initialize the system based on the supplied scheduler configuration.
 pub fn init_system(config: &NetbricksConfiguration) {
     if config.name.is_empty() {
@@ -62,17 +101,65 @@ pub fn init_system(config: &NetbricksConfiguration) {
         // We do not have control over any of the other settings in this case.
         init_system_secondary(&config.name[..], config.primary_core);
     } else {
-        init_system_wl_with_mempool(
-            &config.name[..],
-            config.primary_core,
-            &[],
-            config.pool_size,
-            config.cache_size,
-        );
+        if let Err(ref e) = config.check_mbuf_layout() {
+            panic!("Bad mbuf configuration: {}", e)
+        }
+        let layout = MempoolLayout {
+            pool_size: config.pool_size,
+            cache_size: config.cache_size,
+            metadata_slots: config.metadata_slots,
+            data_room: config.mbuf_data_room,
+            headroom: config.mbuf_headroom,
+        };
+        init_system_wl_with_mempool(&config.name[..], config.primary_core, &[], &layout);
+        for mempool in &config.mempools {
+            if let Err(ref e) = create_mempool(mempool) {
+                panic!("{}", e)
+            }
+        }
     }
     set_numa_domain();
 }
 
+/// Create a named mempool, whose mbufs have the same layout as those in the default mempools. Must be called after
+/// the system is initialized.
+pub fn create_mempool(config: &MempoolConfiguration) -> Result<()> {
+    let name = CString::new(&config.name[..]).unwrap();
+    let socket = config.socket.unwrap_or(-1);
+    let pool = unsafe { zcsi::create_named_mempool(name.as_ptr(), config.size, config.cache_size, socket) };
+    if pool.is_null() {
+        Err(ErrorKind::FailedToCreateMempool(config.name.clone()).into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Find a mempool by name, including mempools created by a primary process.
+pub(crate) fn find_mempool(name: &str) -> Option<*mut zcsi::RteMempool> {
+    let name = CString::new(name).unwrap();
+    let pool = unsafe { zcsi::find_named_mempool(name.as_ptr()) };
+    if pool.is_null() {
+        None
+    } else {
+        Some(pool)
+    }
+}
+
+/// Allocate packets created by this thread (e.g., with `new_packet`, `Packet::copy` or GSO) from the named mempool, or
+/// from the default mempool for the thread's core when `name` is `None`. RX queues choose their mempool through their
+/// port's configuration instead.
+pub fn set_alloc_mempool(name: Option<&str>) -> Result<()> {
+    let pool = match name {
+        Some(name) => match find_mempool(name) {
+            Some(pool) => pool,
+            None => return Err(ErrorKind::ConfigurationError(format!("Unknown mempool {}", name)).into()),
+        },
+        None => ptr::null_mut(),
+    };
+    unsafe { zcsi::set_alloc_mempool(pool) };
+    Ok(())
+}
+
 thread_local!(static NUMA_DOMAIN: Cell<i32> = Cell::new(-1));
 
 fn set_numa_domain() {
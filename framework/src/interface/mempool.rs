@@ -0,0 +1,241 @@
+use native::zcsi::{self, MBuf, RteMempoolStats};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CStr;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Occupancy of a single mempool.
+#[derive(Clone, Debug)]
+pub struct MempoolStats {
+    pub name: String,
+    /// Total number of mbufs in the pool.
+    pub size: usize,
+    /// Mbufs that can currently be allocated, including those sitting in per-core caches.
+    pub available: usize,
+    /// Mbufs currently allocated.
+    pub in_use: usize,
+    /// NUMA node the pool was allocated on, `None` if DPDK was free to pick.
+    pub socket: Option<i32>,
+}
+
+impl MempoolStats {
+    /// Fraction of the pool that is available for allocation.
+    pub fn available_fraction(&self) -> f64 {
+        if self.size == 0 {
+            0.0
+        } else {
+            self.available as f64 / self.size as f64
+        }
+    }
+}
+
+impl fmt::Display for MempoolStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "mempool {} size {} available {} in use {}",
+            self.name, self.size, self.available, self.in_use
+        )
+    }
+}
+
+/// Occupancy of every mempool in the system, including mempools created by a primary process. Must be called after
+/// the system is initialized.
+pub fn mempool_stats() -> Vec<MempoolStats> {
+    let mut max = 8;
+    loop {
+        let mut raw: Vec<RteMempoolStats> = Vec::with_capacity(max);
+        let count = unsafe {
+            let count = zcsi::mempool_stats(raw.as_mut_ptr(), max as i32) as usize;
+            raw.set_len(if count < max { count } else { max });
+            count
+        };
+        if count <= max {
+            return raw.iter()
+                .map(|s| MempoolStats {
+                    name: unsafe { CStr::from_ptr(s.name.as_ptr()) }.to_string_lossy().into_owned(),
+                    size: s.size as usize,
+                    available: s.available as usize,
+                    in_use: s.in_use as usize,
+                    socket: if s.socket < 0 { None } else { Some(s.socket) },
+                })
+                .collect();
+        }
+        // More mempools than we had room for, retry with enough space.
+        max = count;
+    }
+}
+
+static ALLOC_FAILURES: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of times NetBricks failed to allocate mbufs because a mempool was exhausted. A steadily increasing count
+/// usually means a pipeline is leaking mbufs.
+pub fn mbuf_alloc_failures() -> usize {
+    ALLOC_FAILURES.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub(crate) fn record_alloc_failure() {
+    ALLOC_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+static TRACKING: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref OUTSTANDING: Mutex<HashMap<usize, &'static str>> = Mutex::new(HashMap::new());
+}
+
+thread_local!(static OWNER: Cell<&'static str> = Cell::new("unknown"));
+
+/// Enable or disable tracking of outstanding mbufs. While enabled every mbuf NetBricks allocates or receives is
+/// recorded along with the operator that produced it (see `mbuf_owner`), until its last reference is freed or handed
+/// to a port, so that leaks can be attributed using `outstanding_mbufs`. This takes a lock for every batch and is meant
+/// for debugging only. Disabling tracking forgets all outstanding mbufs.
+pub fn set_mbuf_tracking(enabled: bool) {
+    TRACKING.store(enabled, Ordering::Release);
+    if !enabled {
+        OUTSTANDING.lock().unwrap().clear();
+    }
+}
+
+/// Is mbuf tracking enabled?
+#[inline]
+pub fn mbuf_tracking() -> bool {
+    TRACKING.load(Ordering::Relaxed)
+}
+
+/// Restores the previous mbuf owner for this thread when dropped.
+pub struct MbufOwner {
+    previous: Option<&'static str>,
+}
+
+/// Attribute mbufs allocated or received on this thread to `owner` until the returned guard is dropped. `ReceiveBatch`
+/// and the operators that run user code (`transform`, `map`, `filter` and `add_metadata`) name themselves this way;
+/// calling this from within a closure attributes the mbufs it allocates more precisely. Does nothing unless tracking
+/// is enabled.
+pub fn mbuf_owner(owner: &'static str) -> MbufOwner {
+    MbufOwner {
+        previous: if mbuf_tracking() {
+            Some(OWNER.with(|o| o.replace(owner)))
+        } else {
+            None
+        },
+    }
+}
+
+impl Drop for MbufOwner {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous {
+            OWNER.with(|o| o.set(previous));
+        }
+    }
+}
+
+/// Number of tracked mbufs that have not been freed or sent, grouped by the operator that allocated or received them,
+/// largest first.
+pub fn outstanding_mbufs() -> Vec<(&'static str, usize)> {
+    let mut counts: HashMap<&'static str, usize> = HashMap::new();
+    for owner in OUTSTANDING.lock().unwrap().values() {
+        *counts.entry(owner).or_insert(0) += 1;
+    }
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    counts
+}
+
+/// Record that the current owner (see `mbuf_owner`) now holds `mbufs`.
+#[inline]
+pub(crate) fn track_mbufs(mbufs: &[*mut MBuf]) {
+    if mbuf_tracking() && !mbufs.is_empty() {
+        let owner = OWNER.with(|o| o.get());
+        let mut outstanding = OUTSTANDING.lock().unwrap();
+        for mbuf in mbufs {
+            outstanding.insert(*mbuf as usize, owner);
+        }
+    }
+}
+
+/// Record that a reference to each of `mbufs` is about to be freed or leave NetBricks. Mbufs with other references
+/// (see `Packet::reference`) remain outstanding until their last reference goes.
+#[inline]
+pub(crate) fn untrack_mbufs(mbufs: &[*mut MBuf]) {
+    if mbuf_tracking() && !mbufs.is_empty() {
+        let mut outstanding = OUTSTANDING.lock().unwrap();
+        for mbuf in mbufs {
+            if unsafe { (**mbuf).refcnt() } <= 1 {
+                outstanding.remove(&(*mbuf as usize));
+            }
+        }
+    }
+}
+
+/// A thread that periodically checks mempool occupancy and logs when the fraction of available mbufs in a pool drops
+/// below a low watermark (and when it recovers), along with allocation failures and, when tracking is enabled, the
+/// operators holding the most mbufs. The thread stops when the watchdog is dropped.
+pub struct MempoolWatchdog {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MempoolWatchdog {
+    /// Start checking mempools every `interval`, warning when less than `low_watermark` (a fraction between 0 and 1)
+    /// of a pool is available.
+    pub fn start(low_watermark: f64, interval: Duration) -> MempoolWatchdog {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        let thread = thread::Builder::new()
+            .name(String::from("mempool-watchdog"))
+            .spawn(move || {
+                let mut low = HashSet::new();
+                let mut failures = mbuf_alloc_failures();
+                while !stop_thread.load(Ordering::Acquire) {
+                    check_mempools(low_watermark, &mut low, &mut failures);
+                    thread::park_timeout(interval);
+                }
+            })
+            .unwrap();
+        MempoolWatchdog {
+            stop: stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for MempoolWatchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            thread.join().unwrap();
+        }
+    }
+}
+
+fn check_mempools(low_watermark: f64, low: &mut HashSet<String>, failures: &mut usize) {
+    let mut report = false;
+    for pool in mempool_stats() {
+        if pool.available_fraction() < low_watermark {
+            if low.insert(pool.name.clone()) {
+                println!("Warning: {} is below {:.0}% available", pool, low_watermark * 100.0);
+                report = true;
+            }
+        } else if low.remove(&pool.name) {
+            println!("{} recovered", pool);
+        }
+    }
+    let current = mbuf_alloc_failures();
+    if current != *failures {
+        println!("Warning: {} mbuf allocations failed", current - *failures);
+        *failures = current;
+        report = true;
+    }
+    if report && mbuf_tracking() {
+        for (owner, count) in outstanding_mbufs().into_iter().take(5) {
+            println!("\t{} mbufs held by {}", count, owner);
+        }
+    }
+}
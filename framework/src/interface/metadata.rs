@@ -0,0 +1,125 @@
+use common::*;
+use std::any::TypeId;
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A named, typed field in per-packet metadata, obtained from `register_metadata_field`. Fields are allocated from the
+/// end of the metadata area so that they never overlap each other or the pipeline's freeform metadata (written by
+/// `Packet::write_metadata` and the `add_metadata` operators). Read and write them using `Packet::read_field` and
+/// `Packet::write_field`.
+pub struct MetadataField<F: Copy + Send + 'static> {
+    offset: usize,
+    phantom: PhantomData<F>,
+}
+
+impl<F: Copy + Send + 'static> Clone for MetadataField<F> {
+    fn clone(&self) -> MetadataField<F> {
+        MetadataField {
+            offset: self.offset,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<F: Copy + Send + 'static> Copy for MetadataField<F> {}
+
+impl<F: Copy + Send + 'static> MetadataField<F> {
+    /// Offset of this field in bytes from the start of the metadata area.
+    #[inline]
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+/// Description of a registered metadata field.
+#[derive(Clone, Debug)]
+pub struct MetadataFieldInfo {
+    pub name: String,
+    pub offset: usize,
+    pub size: usize,
+    type_id: TypeId,
+}
+
+struct MetadataRegistry {
+    fields: Vec<MetadataFieldInfo>,
+    // Set once freeform metadata has been sized, after which new fields cannot be registered.
+    closed: bool,
+}
+
+lazy_static! {
+    static ref REGISTRY: Mutex<MetadataRegistry> = Mutex::new(MetadataRegistry {
+        fields: vec![],
+        closed: false,
+    });
+}
+
+// Start of the region used by registered fields, in bytes from the start of the metadata area. Freeform metadata may
+// use everything below this. Zero means no field has been registered yet.
+static FIELDS_START: AtomicUsize = AtomicUsize::new(0);
+
+/// Offset at which registered metadata fields start, given that the metadata area is `metadata_end` bytes long.
+#[inline]
+pub(crate) fn metadata_fields_start(metadata_end: usize) -> usize {
+    match FIELDS_START.load(Ordering::Acquire) {
+        0 => metadata_end,
+        start => start,
+    }
+}
+
+/// Register a metadata field called `name` holding values of type `F`. Registering the same name again with the same
+/// type returns the same field, so NFs that agree on a name can share a field, while registering it with a different
+/// type fails. Registering a field shrinks the space available to freeform metadata, so new fields cannot be registered
+/// once an operator that adds freeform metadata has been built; register fields before building pipelines.
+pub fn register_metadata_field<F: Copy + Send + 'static>(name: &str) -> Result<MetadataField<F>> {
+    let (freeform_start, metadata_end) = super::packet::metadata_bounds();
+    let mut registry = REGISTRY.lock().unwrap();
+    let type_id = TypeId::of::<F>();
+    if let Some(field) = registry.fields.iter().find(|f| f.name == name) {
+        return if field.type_id == type_id {
+            Ok(MetadataField {
+                offset: field.offset,
+                phantom: PhantomData,
+            })
+        } else {
+            Err(ErrorKind::MetadataFieldMismatch(String::from(name)).into())
+        };
+    }
+    if registry.closed {
+        return Err(ErrorKind::MetadataRegistrationClosed(String::from(name)).into());
+    }
+    let start = metadata_fields_start(metadata_end);
+    let size = size_of::<F>();
+    if size > start - freeform_start {
+        return Err(ErrorKind::MetadataTooLarge.into());
+    }
+    let offset = (start - size) & !(align_of::<F>() - 1);
+    if offset < freeform_start {
+        return Err(ErrorKind::MetadataTooLarge.into());
+    }
+    registry.fields.push(MetadataFieldInfo {
+        name: String::from(name),
+        offset: offset,
+        size: size,
+        type_id: type_id,
+    });
+    FIELDS_START.store(offset, Ordering::Release);
+    Ok(MetadataField {
+        offset: offset,
+        phantom: PhantomData,
+    })
+}
+
+/// Stop new fields from being registered, and return where registered fields start given that the metadata area is
+/// `metadata_end` bytes long.
+pub(crate) fn close_metadata_registration(metadata_end: usize) -> usize {
+    let mut registry = REGISTRY.lock().unwrap();
+    registry.closed = true;
+    metadata_fields_start(metadata_end)
+}
+
+/// All registered metadata fields, in registration order.
+pub fn metadata_fields() -> Vec<MetadataFieldInfo> {
+    REGISTRY.lock().unwrap().fields.clone()
+}
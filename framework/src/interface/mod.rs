@@ -1,8 +1,16 @@
+pub use self::mempool::{mbuf_alloc_failures, mbuf_owner, mbuf_tracking, mempool_stats, outstanding_mbufs,
+                        set_mbuf_tracking, MbufOwner, MempoolStats, MempoolWatchdog};
+pub use self::metadata::{metadata_fields, register_metadata_field, MetadataField, MetadataFieldInfo};
+pub use self::offload::{ChecksumStatus, PacketType};
 pub use self::packet::*;
 pub use self::port::*;
+pub(crate) use self::mempool::{record_alloc_failure, track_mbufs, untrack_mbufs};
 pub mod dpdk;
 mod port;
 mod packet;
+mod metadata;
+mod mempool;
+mod offload;
 use common::*;
 use native::zcsi::MBuf;
 
@@ -0,0 +1,96 @@
+// Values from rte_mbuf_ptype.h.
+const PTYPE_L2_MASK: u32 = 0x0f;
+const PTYPE_L2_ETHER_VLAN: u32 = 0x06;
+const PTYPE_L2_ETHER_QINQ: u32 = 0x07;
+const PTYPE_L3_MASK: u32 = 0xf0;
+const PTYPE_L3_IPV4: u32 = 0x10;
+const PTYPE_L3_IPV4_EXT: u32 = 0x30;
+const PTYPE_L3_IPV6: u32 = 0x40;
+const PTYPE_L3_IPV4_EXT_UNKNOWN: u32 = 0x90;
+const PTYPE_L3_IPV6_EXT: u32 = 0xc0;
+const PTYPE_L3_IPV6_EXT_UNKNOWN: u32 = 0xe0;
+const PTYPE_L4_MASK: u32 = 0xf00;
+const PTYPE_L4_TCP: u32 = 0x100;
+const PTYPE_L4_UDP: u32 = 0x200;
+const PTYPE_L4_FRAG: u32 = 0x300;
+const PTYPE_L4_SCTP: u32 = 0x400;
+const PTYPE_L4_ICMP: u32 = 0x500;
+
+/// Result of checksum verification done by the NIC on a received packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumStatus {
+    /// The NIC did not check (e.g., checksum offload is disabled or the protocol is not supported).
+    Unknown,
+    Good,
+    Bad,
+    /// The checksum field is not correct, but the NIC verified the integrity of the data some other way.
+    IntegrityVerified,
+}
+
+impl ChecksumStatus {
+    #[inline]
+    pub(crate) fn from_flags(ol_flags: u64, good: u64, bad: u64) -> ChecksumStatus {
+        match (ol_flags & good != 0, ol_flags & bad != 0) {
+            (false, false) => ChecksumStatus::Unknown,
+            (true, false) => ChecksumStatus::Good,
+            (false, true) => ChecksumStatus::Bad,
+            (true, true) => ChecksumStatus::IntegrityVerified,
+        }
+    }
+}
+
+/// Protocol classification done by the NIC on a received packet. A value of 0 means the NIC did not classify the
+/// packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PacketType(pub u32);
+
+impl PacketType {
+    #[inline]
+    pub fn is_unknown(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Carries (at least) one VLAN tag.
+    #[inline]
+    pub fn is_vlan(&self) -> bool {
+        let l2 = self.0 & PTYPE_L2_MASK;
+        l2 == PTYPE_L2_ETHER_VLAN || l2 == PTYPE_L2_ETHER_QINQ
+    }
+
+    #[inline]
+    pub fn is_ipv4(&self) -> bool {
+        let l3 = self.0 & PTYPE_L3_MASK;
+        l3 == PTYPE_L3_IPV4 || l3 == PTYPE_L3_IPV4_EXT || l3 == PTYPE_L3_IPV4_EXT_UNKNOWN
+    }
+
+    #[inline]
+    pub fn is_ipv6(&self) -> bool {
+        let l3 = self.0 & PTYPE_L3_MASK;
+        l3 == PTYPE_L3_IPV6 || l3 == PTYPE_L3_IPV6_EXT || l3 == PTYPE_L3_IPV6_EXT_UNKNOWN
+    }
+
+    #[inline]
+    pub fn is_tcp(&self) -> bool {
+        self.0 & PTYPE_L4_MASK == PTYPE_L4_TCP
+    }
+
+    #[inline]
+    pub fn is_udp(&self) -> bool {
+        self.0 & PTYPE_L4_MASK == PTYPE_L4_UDP
+    }
+
+    #[inline]
+    pub fn is_sctp(&self) -> bool {
+        self.0 & PTYPE_L4_MASK == PTYPE_L4_SCTP
+    }
+
+    #[inline]
+    pub fn is_icmp(&self) -> bool {
+        self.0 & PTYPE_L4_MASK == PTYPE_L4_ICMP
+    }
+
+    #[inline]
+    pub fn is_fragment(&self) -> bool {
+        self.0 & PTYPE_L4_MASK == PTYPE_L4_FRAG
+    }
+}
@@ -1,10 +1,16 @@
 use common::*;
-use headers::{EndOffset, NullHeader};
+use headers::{EndOffset, IpHeader, MacHeader, NullHeader, TcpHeader};
+use interface::mempool::{record_alloc_failure, track_mbufs, untrack_mbufs};
+use interface::metadata::{close_metadata_registration, metadata_fields_start, MetadataField};
+use interface::offload::*;
 use native::zcsi::*;
+use std::cmp::{max, min};
 use std::marker::PhantomData;
 use std::mem::size_of;
 use std::ptr;
 use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use utils::{checksum_add, checksum_finish};
 
 /// A packet is a safe wrapper around mbufs, that can be allocated and manipulated.
 /// We associate a header type with a packet to allow safe insertion of headers.
@@ -52,6 +58,7 @@ fn reference_mbuf(mbuf: *mut MBuf) {
     unsafe { (*mbuf).reference() };
 }
 
+/// Default number of metadata slots stored with each packet.
 pub const METADATA_SLOTS: u16 = 16;
 const HEADER_SLOT: usize = 0;
 const OFFSET_SLOT: usize = HEADER_SLOT + 1;
@@ -60,8 +67,62 @@ const STACK_OFFSET_SLOT: usize = STACK_DEPTH_SLOT + 1;
 const STACK_SIZE: usize = 0;
 #[allow(dead_code)]
 const END_OF_STACK_SLOT: usize = STACK_OFFSET_SLOT + STACK_SIZE;
-const FREEFORM_METADATA_SLOT: usize = END_OF_STACK_SLOT;
-const FREEFORM_METADATA_SIZE: usize = (METADATA_SLOTS as usize - FREEFORM_METADATA_SLOT) * 8;
+const RX_TIMESTAMP_SLOT: usize = END_OF_STACK_SLOT;
+// Value of the receive timestamp slot for packets that were not timestamped. TSC time counts from boot, so no packet
+// is received at 0.
+const NO_RX_TIMESTAMP: usize = 0;
+const FREEFORM_METADATA_SLOT: usize = RX_TIMESTAMP_SLOT + 1;
+/// Metadata slots used internally to track headers, the configured slot count must be larger than this.
+pub const RESERVED_METADATA_SLOTS: u16 = FREEFORM_METADATA_SLOT as u16;
+
+// Number of metadata slots the mempools were created with.
+static METADATA_SLOT_COUNT: AtomicUsize = AtomicUsize::new(METADATA_SLOTS as usize);
+
+/// Record the number of metadata slots in each mbuf, called when mempools are created.
+pub(crate) fn set_metadata_slots(slots: u16) {
+    METADATA_SLOT_COUNT.store(slots as usize, Ordering::Release);
+}
+
+#[inline]
+fn metadata_size() -> usize {
+    METADATA_SLOT_COUNT.load(Ordering::Relaxed) * 8
+}
+
+/// Start of freeform metadata and end of the metadata area, in bytes.
+#[inline]
+pub(crate) fn metadata_bounds() -> (usize, usize) {
+    (FREEFORM_METADATA_SLOT * 8, metadata_size())
+}
+
+/// Space available to freeform metadata, i.e., what is not used by registered metadata fields.
+#[inline]
+fn freeform_metadata_size() -> usize {
+    metadata_fields_start(metadata_size()) - FREEFORM_METADATA_SLOT * 8
+}
+
+/// Check that metadata of type `M2` fits in the freeform metadata area, called when building operators that add
+/// metadata. This also closes metadata field registration, so the area cannot shrink afterwards.
+pub(crate) fn reserve_freeform_metadata<M2: Sized>() -> Result<()> {
+    let start = close_metadata_registration(metadata_size());
+    if size_of::<M2>() >= start - FREEFORM_METADATA_SLOT * 8 {
+        Err(ErrorKind::MetadataTooLarge.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Record that the packet in `mbuf` was received at `ns` (TSC time in nanoseconds), see `Packet::rx_timestamp`.
+#[inline]
+pub(crate) fn set_rx_timestamp(mbuf: *mut MBuf, ns: u64) {
+    MBuf::write_metadata_slot(mbuf, RX_TIMESTAMP_SLOT, ns as usize);
+}
+
+/// Record that the packet in `mbuf` has no receive time. Metadata is not reset when mbufs are reused, so this must be
+/// called for every packet that enters a pipeline without being timestamped.
+#[inline]
+pub(crate) fn clear_rx_timestamp(mbuf: *mut MBuf) {
+    MBuf::write_metadata_slot(mbuf, RX_TIMESTAMP_SLOT, NO_RX_TIMESTAMP);
+}
 
 #[inline]
 pub unsafe fn packet_from_mbuf<T: EndOffset>(mbuf: *mut MBuf, offset: usize) -> Packet<T, EmptyMetadata> {
@@ -88,8 +149,11 @@ pub fn new_packet() -> Option<Packet<NullHeader, EmptyMetadata>> {
         // This sets refcnt = 1
         let mbuf = mbuf_alloc();
         if mbuf.is_null() {
+            record_alloc_failure();
             None
         } else {
+            track_mbufs(&[mbuf]);
+            clear_rx_timestamp(mbuf);
             Some(packet_from_mbuf_no_increment(mbuf, 0))
         }
     }
@@ -102,6 +166,12 @@ pub fn new_packet_array(count: usize) -> Vec<Packet<NullHeader, EmptyMetadata>>
         let alloc_ret = mbuf_alloc_bulk(array.as_mut_ptr(), 0, count as i32);
         if alloc_ret == 0 {
             array.set_len(count);
+            track_mbufs(&array);
+            for mbuf in &array {
+                clear_rx_timestamp(*mbuf);
+            }
+        } else {
+            record_alloc_failure();
         }
         array
             .iter()
@@ -130,6 +200,12 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         self.offset
     }
 
+    #[inline]
+    #[cfg(not(feature = "packet_offset"))]
+    fn move_header(&mut self, by: isize) {
+        self.header = unsafe { self.header_u8().offset(by) as *mut T };
+    }
+
     // ----------------- Using packet offsets -------------------------------------------------------------
     #[inline]
     #[cfg(feature = "packet_offset")]
@@ -149,6 +225,14 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         self.read_offset()
     }
 
+    #[inline]
+    #[cfg(feature = "packet_offset")]
+    fn move_header(&mut self, by: isize) {
+        let header = unsafe { self.header_u8().offset(by) };
+        let offset = self.offset();
+        self.update_ptrs(header, offset);
+    }
+
     // -----------------Common code ------------------------------------------------------------------------
     #[inline]
     fn read_stack_depth(&self) -> usize {
@@ -202,6 +286,7 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
     #[inline]
     pub fn free_packet(self) {
         if !self.mbuf.is_null() {
+            untrack_mbufs(&[self.mbuf]);
             unsafe { mbuf_free(self.mbuf) };
         }
     }
@@ -259,6 +344,21 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         self.data_len() - self.offset() - self.payload_offset()
     }
 
+    /// Make sure the first `len` bytes of payload are in the first segment, so a header of that size can be accessed
+    /// in place. Only the missing bytes are moved out of later segments. Fails if the packet is too short or the first
+    /// segment does not have enough room.
+    #[inline]
+    pub fn make_header_contiguous(&mut self, len: usize) -> Result<()> {
+        let end = self.offset() + self.payload_offset() + len;
+        if end > self.pkt_len() {
+            Err(ErrorKind::BadOffset(end).into())
+        } else if unsafe { (*self.mbuf).pull_up(end) } {
+            Ok(())
+        } else {
+            Err(ErrorKind::FailedAllocation.into())
+        }
+    }
+
     #[inline]
     pub fn get_header(&self) -> &T {
         unsafe { &(*(self.header())) }
@@ -269,9 +369,11 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         unsafe { &mut (*(self.header())) }
     }
 
+    /// Read freeform metadata. Operators that add metadata check its size when they are built, but packets can also be
+    /// given any metadata type with `reinterpret_metadata`, so this panics if `M` does not fit.
     #[inline]
     pub fn read_metadata(&self) -> &M {
-        assert!(size_of::<M>() < FREEFORM_METADATA_SIZE);
+        assert!(size_of::<M>() < freeform_metadata_size());
         unsafe {
             let ptr = MBuf::metadata_as::<M>(self.mbuf, FREEFORM_METADATA_SLOT);
             &(*(ptr))
@@ -280,7 +382,7 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
 
     #[inline]
     pub fn write_metadata<M2: Sized + Send>(&mut self, metadata: &M2) -> Result<()> {
-        if size_of::<M2>() >= FREEFORM_METADATA_SIZE {
+        if size_of::<M2>() >= freeform_metadata_size() {
             Err(ErrorKind::MetadataTooLarge.into())
         } else {
             unsafe {
@@ -291,6 +393,19 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         }
     }
 
+    /// Read a metadata field registered with `register_metadata_field`. Fields are not cleared when packets are
+    /// received, so this returns garbage unless the field was written earlier in the pipeline.
+    #[inline]
+    pub fn read_field<F: Copy + Send + 'static>(&self, field: &MetadataField<F>) -> F {
+        unsafe { *(MBuf::metadata_as::<u8>(self.mbuf, 0).offset(field.offset() as isize) as *const F) }
+    }
+
+    /// Write a metadata field registered with `register_metadata_field`.
+    #[inline]
+    pub fn write_field<F: Copy + Send + 'static>(&mut self, field: &MetadataField<F>, value: F) {
+        unsafe { *(MBuf::mut_metadata_as::<u8>(self.mbuf, 0).offset(field.offset() as isize) as *mut F) = value }
+    }
+
     #[inline]
     pub fn reinterpret_metadata<M2: Sized + Send>(mut self) -> Packet<T, M2> {
         let hdr = self.header();
@@ -304,7 +419,7 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         unsafe {
             let len = self.data_len();
             let size = header.offset();
-            let added = (*self.mbuf).add_data_end(size);
+            let added = (*self.mbuf).add_data_first_segment(size);
 
             let hdr = header as *const T2;
             let offset = self.offset() + self.payload_offset();
@@ -339,27 +454,32 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         }
     }
 
-    /// Add data to the head of the payload.
+    /// Add `size` bytes to the head of the payload, e.g., to insert a header. The headers in front of the payload are
+    /// moved into the mbuf's headroom (see `NetbricksConfiguration::mbuf_headroom`), this fails if the headroom is too
+    /// small. The added bytes are not initialized.
     #[inline]
     pub fn add_to_payload_head(&mut self, size: usize) -> Result<()> {
         unsafe {
-            let added = (*self.mbuf).add_data_end(size);
-            if added >= size {
-                let src = self.payload();
-                let dst = src.offset(size as isize);
-                ptr::copy_nonoverlapping(src, dst, size);
-                Ok(())
-            } else {
-                Err(ErrorKind::FailedAllocation.into())
+            let headers = self.offset() + self.payload_offset();
+            if size > 0 && (*self.mbuf).add_data_beginning(size) < size {
+                return Err(ErrorKind::FailedAllocation.into());
             }
+            let dst = self.data_base();
+            ptr::copy(dst.offset(size as isize), dst, headers);
+            self.move_header(-(size as isize));
+            Ok(())
         }
     }
 
     #[inline]
     pub fn remove_from_payload_tail(&mut self, size: usize) -> Result<()> {
         unsafe {
-            (*self.mbuf).remove_data_end(size);
-            Ok(())
+            let removed = (*self.mbuf).remove_data_end(size);
+            if removed >= size {
+                Ok(())
+            } else {
+                Err(ErrorKind::BadOffset(size).into())
+            }
         }
     }
 
@@ -390,8 +510,10 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
 
     #[inline]
     pub fn parse_header<T2: EndOffset<PreviousHeader = T>>(mut self) -> Packet<T2, M> {
+        if let Err(e) = self.make_header_contiguous(T2::size()) {
+            panic!("Cannot parse header: {}", e)
+        }
         unsafe {
-            assert!{self.payload_size() >= T2::size()}
             let hdr = self.payload() as *mut T2;
             let offset = self.offset() + self.payload_offset();
             create_packet(self.get_mbuf_ref(), hdr, offset)
@@ -400,8 +522,10 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
 
     #[inline]
     pub fn parse_header_and_record<T2: EndOffset<PreviousHeader = T>>(mut self) -> Packet<T2, M> {
+        if let Err(e) = self.make_header_contiguous(T2::size()) {
+            panic!("Cannot parse header: {}", e)
+        }
         unsafe {
-            assert!{self.payload_size() >= T2::size()}
             let hdr = self.payload() as *mut T2;
             let payload_offset = self.payload_offset();
             let offset = self.offset() + payload_offset;
@@ -471,23 +595,29 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         }
     }
 
+    /// Grow the packet by `increase_by` bytes at the end of its last segment. Returns the number of bytes added, which
+    /// is 0 if the last segment does not have enough tailroom.
     #[inline]
     pub fn increase_payload_size(&mut self, increase_by: usize) -> usize {
         unsafe { (*self.mbuf).add_data_end(increase_by) }
     }
 
+    /// Shrink the packet by `trim_by` bytes from its end, freeing segments that are emptied. Returns the number of
+    /// bytes removed, which is 0 if the packet is too short.
     #[inline]
     pub fn trim_payload_size(&mut self, trim_by: usize) -> usize {
         unsafe { (*self.mbuf).remove_data_end(trim_by) }
     }
 
+    /// Copy `other`'s payload over this packet's payload, growing this packet if needed. Payloads of chained packets
+    /// are copied in full. Returns the number of bytes copied.
     #[inline]
     pub fn copy_payload(&mut self, other: &Self) -> usize {
-        let copy_len = other.payload_size();
-        let dst = self.payload();
-        let src = other.payload();
+        let start = self.offset() + self.payload_offset();
+        let other_start = other.offset() + other.payload_offset();
+        let copy_len = other.pkt_len() - other_start;
 
-        let payload_size = self.payload_size();
+        let payload_size = self.pkt_len() - start;
 
         let should_copy = if payload_size < copy_len {
             let increment = copy_len - payload_size;
@@ -496,10 +626,9 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
             copy_len
         };
 
-        unsafe {
-            ptr::copy_nonoverlapping(src, dst, should_copy);
-            should_copy
-        }
+        self.walk_segments(start, should_copy, |dst, at, chunk| unsafe {
+            other.read_bytes(other_start + at, slice::from_raw_parts_mut(dst, chunk));
+        })
     }
 
     #[inline]
@@ -507,6 +636,220 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         unsafe { (*self.mbuf).refcnt() }
     }
 
+    /// The RSS hash computed by the NIC, if any. This can be used instead of recomputing `flow_hash` in software, but
+    /// note that it depends on the port's RSS configuration.
+    #[inline]
+    pub fn rss_hash(&self) -> Option<u32> {
+        unsafe {
+            if (*self.mbuf).ol_flags() & PKT_RX_RSS_HASH != 0 {
+                Some((*self.mbuf).rss())
+            } else {
+                None
+            }
+        }
+    }
+
+    /// The VLAN TCI of a tag stripped by the NIC (see `PortConfiguration::vlan_strip`), if any.
+    #[inline]
+    pub fn vlan_tci(&self) -> Option<u16> {
+        unsafe {
+            if (*self.mbuf).ol_flags() & PKT_RX_VLAN_STRIPPED != 0 {
+                Some((*self.mbuf).vlan_tci())
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Protocols found in this packet by the NIC.
+    #[inline]
+    pub fn packet_type(&self) -> PacketType {
+        unsafe { PacketType((*self.mbuf).packet_type()) }
+    }
+
+    /// Result of IP header checksum verification by the NIC.
+    #[inline]
+    pub fn ip_checksum(&self) -> ChecksumStatus {
+        let flags = unsafe { (*self.mbuf).ol_flags() };
+        ChecksumStatus::from_flags(flags, PKT_RX_IP_CKSUM_GOOD, PKT_RX_IP_CKSUM_BAD)
+    }
+
+    /// Result of L4 (TCP, UDP, SCTP) checksum verification by the NIC.
+    #[inline]
+    pub fn l4_checksum(&self) -> ChecksumStatus {
+        let flags = unsafe { (*self.mbuf).ol_flags() };
+        ChecksumStatus::from_flags(flags, PKT_RX_L4_CKSUM_GOOD, PKT_RX_L4_CKSUM_BAD)
+    }
+
+    /// The ID assigned to this packet by a hardware flow rule with a `Mark` action, if any.
+    #[inline]
+    pub fn flow_mark(&self) -> Option<u32> {
+        unsafe { (*self.mbuf).flow_mark() }
+    }
+
+    /// TSC time in nanoseconds at which a timestamping `ReceiveBatch` received this packet (see
+    /// `utils::tsc_ns_to_unix_ns` to convert it to wall clock time). `None` if the packet was not timestamped.
+    #[inline]
+    pub fn rx_timestamp(&self) -> Option<u64> {
+        match MBuf::read_metadata_slot(self.mbuf, RX_TIMESTAMP_SLOT) {
+            NO_RX_TIMESTAMP => None,
+            ns => Some(ns as u64),
+        }
+    }
+
+    /// Receive timestamp set by the NIC, for ports configured with `timestamp`. This is in the NIC's clock units
+    /// (e.g., ticks of a free running counter), which depend on the device and are not nanoseconds.
+    #[inline]
+    pub fn hw_rx_timestamp(&self) -> Option<u64> {
+        unsafe { (*self.mbuf).hw_rx_timestamp() }
+    }
+
+    /// Create another handle to this packet, incrementing the mbuf's reference count. Both handles share packet data
+    /// and metadata, so changes made through one are visible through the other.
+    #[inline]
+    pub fn reference(&self) -> Packet<T, M> {
+        reference_mbuf(self.mbuf);
+        create_packet(self.mbuf, self.header(), self.offset())
+    }
+
+    /// Allocate a new mbuf and copy this packet's data and metadata into it. Unlike `reference` the returned packet can
+    /// be modified independently. Returns `None` if no mbuf could be allocated.
+    pub fn copy(&self) -> Option<Packet<T, M>> {
+        unsafe {
+            let mbuf = mbuf_alloc();
+            if mbuf.is_null() {
+                record_alloc_failure();
+                return None;
+            }
+            // Chained packets are linearized into the copy.
+            let len = self.pkt_len();
+            if (*mbuf).add_data_end(len) != len {
+                mbuf_free(mbuf);
+                return None;
+            }
+            track_mbufs(&[mbuf]);
+            self.read_bytes(0, slice::from_raw_parts_mut((*mbuf).data_address(0), len));
+            self.copy_metadata_to(mbuf);
+            let header = (*mbuf).data_address(self.offset()) as *mut T;
+            Some(create_packet(mbuf, header, self.offset()))
+        }
+    }
+
+    /// Length of the entire packet, across all segments. Payload accessors (e.g., `get_payload`) only cover the first
+    /// segment, use `read_bytes`, `write_bytes` or `linearize` to access the rest of a chained packet.
+    #[inline]
+    pub fn pkt_len(&self) -> usize {
+        unsafe { (*self.mbuf).pkt_len() }
+    }
+
+    /// Length of data in the first segment.
+    #[inline]
+    pub fn segment_len(&self) -> usize {
+        self.data_len()
+    }
+
+    /// Does this packet span more than one mbuf segment?
+    #[inline]
+    pub fn is_segmented(&self) -> bool {
+        unsafe { !(*self.mbuf).next_segment().is_null() }
+    }
+
+    /// Iterate over the data held in each segment of this packet.
+    #[inline]
+    pub fn segments(&self) -> Segments {
+        Segments {
+            segment: self.mbuf,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Call `f` with (pointer, position in request, length) for each contiguous piece of the `len` bytes starting at
+    /// `offset`. Returns the number of bytes covered.
+    #[inline]
+    fn walk_segments<F: FnMut(*mut u8, usize, usize)>(&self, offset: usize, len: usize, mut f: F) -> usize {
+        let mut segment = self.mbuf;
+        let mut offset = offset;
+        let mut done = 0;
+        unsafe {
+            while !segment.is_null() && done < len {
+                let segment_len = (*segment).data_len();
+                if offset >= segment_len {
+                    offset -= segment_len;
+                } else {
+                    let chunk = min(segment_len - offset, len - done);
+                    f((*segment).data_address(offset), done, chunk);
+                    done += chunk;
+                    offset = 0;
+                }
+                segment = (*segment).next_segment();
+            }
+        }
+        done
+    }
+
+    /// Copy packet data starting at `offset` (from the start of the packet, not the current header) into `buf`,
+    /// following segment boundaries. Returns the number of bytes copied.
+    pub fn read_bytes(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let len = buf.len();
+        let dst = buf.as_mut_ptr();
+        self.walk_segments(offset, len, |src, at, chunk| unsafe {
+            ptr::copy_nonoverlapping(src, dst.offset(at as isize), chunk)
+        })
+    }
+
+    /// Copy `data` into the packet starting at `offset` (from the start of the packet), following segment
+    /// boundaries. The packet is not grown, writing past its end is an error.
+    pub fn write_bytes(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        if offset + data.len() > self.pkt_len() {
+            Err(ErrorKind::BadOffset(offset + data.len()).into())
+        } else {
+            let src = data.as_ptr();
+            self.walk_segments(offset, data.len(), |dst, at, chunk| unsafe {
+                ptr::copy_nonoverlapping(src.offset(at as isize), dst, chunk)
+            });
+            Ok(())
+        }
+    }
+
+    /// Copy data from all segments into the first one and free the rest, after which all payload accessors cover the
+    /// entire packet. Header pointers remain valid since the first segment's data does not move. Fails if the first
+    /// segment does not have enough room.
+    pub fn linearize(&mut self) -> Result<()> {
+        if unsafe { (*self.mbuf).linearize() } {
+            Ok(())
+        } else {
+            Err(ErrorKind::FailedAllocation.into())
+        }
+    }
+
+    /// Append `other` (and all of its segments) to the end of this packet. `other`'s headers and metadata are ignored.
+    pub fn append_segment<T2: EndOffset, M2: Sized + Send>(&mut self, other: Packet<T2, M2>) -> Result<()> {
+        unsafe {
+            let tail = other.get_mbuf();
+            untrack_mbufs(&[tail]);
+            if (*self.mbuf).chain(tail) {
+                Ok(())
+            } else {
+                mbuf_free(tail);
+                Err(ErrorKind::FailedAllocation.into())
+            }
+        }
+    }
+
+    /// Copy this packet's metadata into `mbuf`, which must contain the same headers at the same offsets.
+    unsafe fn copy_metadata_to(&self, mbuf: *mut MBuf) {
+        ptr::copy_nonoverlapping(
+            MBuf::metadata_as::<u8>(self.mbuf, 0),
+            MBuf::mut_metadata_as::<u8>(mbuf, 0),
+            metadata_size(),
+        );
+        // The saved header is an absolute pointer into the original mbuf, fix it up so it can still be restored.
+        if !self.read_header::<T>().is_null() {
+            let saved = (*mbuf).data_address(self.read_offset());
+            MBuf::write_metadata_slot(mbuf, HEADER_SLOT, saved as usize);
+        }
+    }
+
     /// Get the mbuf reference by this packet.
     ///
     /// # Safety
@@ -524,3 +867,139 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         mbuf
     }
 }
+
+const TCP_PROTOCOL: u8 = 6;
+
+/// Segmentation offload for TCP packets. These assume the packet was parsed as Ethernet, IPv4, TCP from the start of
+/// the packet.
+impl<M: Sized + Send> Packet<TcpHeader, M> {
+    #[inline]
+    fn l2_len(&self) -> usize {
+        unsafe { (*(self.data_base() as *const MacHeader)).offset() }
+    }
+
+    /// Ask the NIC to split this packet into segments carrying at most `mss` bytes of TCP payload each. The port must
+    /// have been configured with `tso`, otherwise use `gso_segment` (or the `gso` operator).
+    pub fn request_tso(&mut self, mss: u16) {
+        assert!(mss > 0, "MSS must be positive");
+        let l2_len = self.l2_len();
+        let l3_len = self.offset() - l2_len;
+        let l4_len = self.payload_offset();
+        unsafe { mbuf_set_tso(self.mbuf, l2_len as u16, l3_len as u16, l4_len as u16, mss) }
+    }
+
+    /// Length of TCP payload carried by this packet, across all segments.
+    #[inline]
+    pub fn tcp_payload_len(&self) -> usize {
+        self.pkt_len() - self.offset() - self.payload_offset()
+    }
+
+    /// Split this packet in software into new packets carrying at most `mss` bytes of TCP payload each, with sequence
+    /// numbers, IP IDs, lengths and checksums fixed up. FIN and PSH are only kept on the last segment and CWR on the
+    /// first. Metadata is copied to every segment. This packet is left untouched.
+    pub fn gso_segment(&self, mss: usize) -> Result<Vec<Packet<TcpHeader, M>>> {
+        assert!(mss > 0, "MSS must be positive");
+        let l2_len = self.l2_len();
+        let tcp_offset = self.offset();
+        let l3_len = tcp_offset - l2_len;
+        let l4_len = self.payload_offset();
+        let hdr_len = tcp_offset + l4_len;
+        let payload_len = self.tcp_payload_len();
+        let count = max(1, (payload_len + mss - 1) / mss);
+        let seq = self.get_header().seq_num();
+
+        let mut segments = Vec::with_capacity(count);
+        for i in 0..count {
+            let start = i * mss;
+            let len = min(mss, payload_len - start);
+            unsafe {
+                let mbuf = mbuf_alloc();
+                if mbuf.is_null() || (*mbuf).add_data_end(hdr_len + len) != hdr_len + len {
+                    if mbuf.is_null() {
+                        record_alloc_failure();
+                    } else {
+                        mbuf_free(mbuf);
+                    }
+                    for segment in segments {
+                        Packet::free_packet(segment);
+                    }
+                    return Err(ErrorKind::FailedAllocation.into());
+                }
+                let data = (*mbuf).data_address(0);
+                self.read_bytes(0, slice::from_raw_parts_mut(data, hdr_len));
+                self.read_bytes(
+                    hdr_len + start,
+                    slice::from_raw_parts_mut(data.offset(hdr_len as isize), len),
+                );
+                self.copy_metadata_to(mbuf);
+                track_mbufs(&[mbuf]);
+
+                let ip = data.offset(l2_len as isize) as *mut IpHeader;
+                let ip_id = (*ip).id();
+                (*ip).set_length((l3_len + l4_len + len) as u16);
+                (*ip).set_id(ip_id.wrapping_add(i as u16));
+                (*ip).set_csum(0);
+                let ip_csum = checksum_finish(checksum_add(slice::from_raw_parts(ip as *const u8, l3_len), 0));
+                (*ip).set_csum(ip_csum);
+
+                let tcp = data.offset(tcp_offset as isize) as *mut TcpHeader;
+                (*tcp).set_seq_num(seq.wrapping_add(start as u32));
+                // Flags are copied from the original packet.
+                if i != count - 1 {
+                    (*tcp).unset_fin_flag();
+                    (*tcp).unset_psh_flag();
+                }
+                if i != 0 {
+                    (*tcp).unset_cwr_flag();
+                }
+                (*tcp).set_checksum(0);
+                let tcp_len = l4_len + len;
+                let src = (*ip).src();
+                let dst = (*ip).dst();
+                let pseudo_header = [
+                    (src >> 24) as u8,
+                    (src >> 16) as u8,
+                    (src >> 8) as u8,
+                    src as u8,
+                    (dst >> 24) as u8,
+                    (dst >> 16) as u8,
+                    (dst >> 8) as u8,
+                    dst as u8,
+                    0,
+                    TCP_PROTOCOL,
+                    (tcp_len >> 8) as u8,
+                    tcp_len as u8,
+                ];
+                let sum = checksum_add(&pseudo_header, 0);
+                let sum = checksum_add(slice::from_raw_parts(tcp as *const u8, tcp_len), sum);
+                (*tcp).set_checksum(checksum_finish(sum));
+
+                segments.push(create_packet(mbuf, tcp, tcp_offset));
+            }
+        }
+        Ok(segments)
+    }
+}
+
+/// Iterator over the data held in each segment of a packet, see `Packet::segments`.
+pub struct Segments<'a> {
+    segment: *mut MBuf,
+    _phantom: PhantomData<&'a MBuf>,
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = &'a [u8];
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.segment.is_null() {
+            None
+        } else {
+            unsafe {
+                let segment = &*self.segment;
+                self.segment = segment.next_segment();
+                Some(slice::from_raw_parts(segment.data_address(0), segment.data_len()))
+            }
+        }
+    }
+}
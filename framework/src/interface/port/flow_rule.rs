@@ -0,0 +1,173 @@
+use super::PmdPort;
+use common::*;
+use headers::MacAddress;
+use native::zcsi::*;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::sync::Arc;
+
+/// Packet fields matched by a hardware flow rule. Fields that are `None` match any value. IPv4 addresses are given as
+/// (address, prefix length) and, like ports, are in host byte order.
+#[derive(Default)]
+pub struct FlowMatch {
+    pub src_mac: Option<MacAddress>,
+    pub dst_mac: Option<MacAddress>,
+    pub ether_type: Option<u16>,
+    pub src_ip: Option<(u32, u8)>,
+    pub dst_ip: Option<(u32, u8)>,
+    /// IP protocol, must be TCP (6) or UDP (17) when matching on ports.
+    pub proto: Option<u8>,
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+}
+
+/// What the NIC does with packets matching a flow rule.
+#[derive(Clone, Copy, Debug)]
+pub enum FlowAction {
+    /// Receive matching packets on the given RX queue.
+    Queue(u16),
+    /// Drop matching packets in hardware.
+    Drop,
+    /// Tag matching packets with an ID, available through `Packet::flow_mark`.
+    Mark(u32),
+    /// Count matching packets, read using `FlowRule::count`.
+    Count,
+}
+
+#[inline]
+fn prefix_mask(prefix: u8) -> u32 {
+    if prefix == 0 {
+        0
+    } else if prefix >= 32 {
+        !0
+    } else {
+        !0 << (32 - prefix)
+    }
+}
+
+impl FlowMatch {
+    fn to_native(&self) -> NbFlowMatch {
+        let mut native = NbFlowMatch::default();
+        if let Some(ref mac) = self.src_mac {
+            native.fields |= NB_FLOW_ETH_SRC;
+            native.eth_src = mac.addr;
+        }
+        if let Some(ref mac) = self.dst_mac {
+            native.fields |= NB_FLOW_ETH_DST;
+            native.eth_dst = mac.addr;
+        }
+        if let Some(ether_type) = self.ether_type {
+            native.fields |= NB_FLOW_ETHER_TYPE;
+            native.ether_type = ether_type;
+        }
+        if let Some((ip, prefix)) = self.src_ip {
+            native.fields |= NB_FLOW_IPV4_SRC;
+            native.ipv4_src = ip;
+            native.ipv4_src_mask = prefix_mask(prefix);
+        }
+        if let Some((ip, prefix)) = self.dst_ip {
+            native.fields |= NB_FLOW_IPV4_DST;
+            native.ipv4_dst = ip;
+            native.ipv4_dst_mask = prefix_mask(prefix);
+        }
+        if let Some(proto) = self.proto {
+            native.fields |= NB_FLOW_IP_PROTO;
+            native.ip_proto = proto;
+        }
+        if let Some(port) = self.src_port {
+            native.fields |= NB_FLOW_SRC_PORT;
+            native.src_port = port;
+        }
+        if let Some(port) = self.dst_port {
+            native.fields |= NB_FLOW_DST_PORT;
+            native.dst_port = port;
+        }
+        native
+    }
+}
+
+/// A flow rule installed on a port. Rules stay installed until `remove` is called or the port is closed.
+pub struct FlowRule {
+    port: Arc<PmdPort>,
+    flow: *mut RteFlow,
+}
+
+// The rte_flow handle is only ever passed back to DPDK.
+unsafe impl Send for FlowRule {}
+
+const FLOW_ERROR_LEN: usize = 256;
+
+impl FlowRule {
+    pub(crate) fn new(port: &Arc<PmdPort>, flow_match: &FlowMatch, actions: &[FlowAction]) -> Result<FlowRule> {
+        let mut native_actions = NbFlowActions {
+            queue: -1,
+            ..Default::default()
+        };
+        for action in actions {
+            match *action {
+                FlowAction::Queue(queue) => {
+                    if queue as i32 >= port.rxqs() {
+                        return Err(ErrorKind::BadRxQueue(port.name(), queue as i32).into());
+                    }
+                    if native_actions.queue >= 0 || native_actions.drop != 0 {
+                        return Err(ErrorKind::FlowRuleError(String::from("Flow can only go to one queue")).into());
+                    }
+                    native_actions.queue = queue as i32;
+                }
+                FlowAction::Drop => {
+                    if native_actions.queue >= 0 {
+                        return Err(ErrorKind::FlowRuleError(String::from("Cannot both drop and queue a flow")).into());
+                    }
+                    native_actions.drop = 1;
+                }
+                FlowAction::Mark(id) => {
+                    native_actions.mark = 1;
+                    native_actions.mark_id = id;
+                }
+                FlowAction::Count => native_actions.count = 1,
+            }
+        }
+        let native_match = flow_match.to_native();
+        let mut err = [0 as c_char; FLOW_ERROR_LEN];
+        let flow = unsafe {
+            create_flow_rule(
+                port.name(),
+                &native_match,
+                &native_actions,
+                err.as_mut_ptr(),
+                FLOW_ERROR_LEN as i32,
+            )
+        };
+        if flow.is_null() {
+            let message = unsafe { CStr::from_ptr(err.as_ptr()) };
+            Err(ErrorKind::FlowRuleError(message.to_string_lossy().into_owned()).into())
+        } else {
+            Ok(FlowRule {
+                port: port.clone(),
+                flow: flow,
+            })
+        }
+    }
+
+    /// Number of packets and bytes that matched this rule, requires the rule to have a `Count` action.
+    pub fn count(&self) -> Result<(u64, u64)> {
+        let mut hits = 0;
+        let mut bytes = 0;
+        let ret = unsafe { query_flow_rule_count(self.port.name(), self.flow, &mut hits, &mut bytes) };
+        if ret == 0 {
+            Ok((hits, bytes))
+        } else {
+            Err(ErrorKind::FlowRuleError(format!("Could not query flow counters ({})", ret)).into())
+        }
+    }
+
+    /// Remove this rule from the NIC.
+    pub fn remove(self) -> Result<()> {
+        let ret = unsafe { destroy_flow_rule(self.port.name(), self.flow) };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(ErrorKind::FlowRuleError(format!("Could not remove flow rule ({})", ret)).into())
+        }
+    }
+}
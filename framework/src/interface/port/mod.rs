@@ -1,3 +1,4 @@
+pub use self::flow_rule::*;
 pub use self::phy_port::*;
 pub use self::virt_port::*;
 use allocators::*;
@@ -5,6 +6,7 @@ use common::*;
 use interface::{PacketRx, PacketTx};
 use native::zcsi::MBuf;
 use std::sync::atomic::AtomicUsize;
+mod flow_rule;
 mod phy_port;
 mod virt_port;
 
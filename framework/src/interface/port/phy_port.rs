@@ -1,16 +1,22 @@
-use super::PortStats;
+use super::{FlowAction, FlowMatch, FlowRule, PortStats};
 use super::super::{PacketRx, PacketTx};
 use allocators::*;
 use common::*;
-use config::{PortConfiguration, NUM_RXD, NUM_TXD};
+use config::{PortConfiguration, RssConfiguration, RssHash, NUM_RXD, NUM_TXD};
 use headers::MacAddress;
+use interface::dpdk::{find_mempool, is_secondary};
 use native::zcsi::*;
 use regex::Regex;
-use std::cmp::min;
+use std::cmp::{max, min};
 use std::ffi::CString;
 use std::fmt;
+use std::ptr;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
+use utils::round_to_power_of_2;
+
+// Longest ring name DPDK accepts (RTE_RING_NAMESIZE - 1).
+const MAX_RING_NAME_LEN: usize = 29;
 
 /// A DPDK based PMD port. Send and receive should not be called directly on this structure but on the port queue
 /// structure instead.
@@ -22,8 +28,31 @@ pub struct PmdPort {
     txqs: i32,
     stats_rx: Vec<Arc<CacheAligned<PortStats>>>,
     stats_tx: Vec<Arc<CacheAligned<PortStats>>>,
+    backend: PortBackend,
+}
+
+/// Where a port's packets come from and go to.
+enum PortBackend {
+    /// A DPDK PMD, identified by the port ID.
+    Pmd,
+    /// Named rings, with an RX and TX ring for each queue.
+    Rings(Vec<(*mut RteRing, *mut RteRing)>),
+    /// A Linux interface, accessed through AF_PACKET or a TAP device.
+    Kernel(*mut KernelPort),
+}
+
+/// The part of `PortBackend` needed by a single queue.
+#[derive(Clone, Copy)]
+enum QueueBackend {
+    Pmd,
+    Ring(*mut RteRing, *mut RteRing),
+    Kernel(*mut KernelPort),
 }
 
+// Rings are shared memory and can be used from any thread, kernel ports are only used by the queue's RX and TX cores.
+unsafe impl Send for PmdPort {}
+unsafe impl Sync for PmdPort {}
+
 /// A port queue represents a single queue for a physical port, and should be used to send and receive data.
 #[derive(Clone)]
 pub struct PortQueue {
@@ -35,8 +64,12 @@ pub struct PortQueue {
     port_id: i32,
     txq: i32,
     rxq: i32,
+    backend: QueueBackend,
 }
 
+unsafe impl Send for PortQueue {}
+unsafe impl Sync for PortQueue {}
+
 impl Drop for PmdPort {
     fn drop(&mut self) {
         if self.connected && self.should_close {
@@ -44,6 +77,9 @@ impl Drop for PmdPort {
                 free_pmd_port(self.port);
             }
         }
+        if let PortBackend::Kernel(kernel) = self.backend {
+            unsafe { kernel_port_close(kernel) }
+        }
     }
 }
 
@@ -66,8 +102,14 @@ impl PortQueue {
     #[inline]
     fn send_queue(&self, queue: i32, pkts: *mut *mut MBuf, to_send: i32) -> Result<u32> {
         unsafe {
-            let sent = send_pkts(self.port_id, queue, pkts, to_send);
-            let update = self.stats_tx.stats.load(Ordering::Relaxed) + sent as usize;
+            let mut dropped = 0;
+            let sent = match self.backend {
+                QueueBackend::Pmd => send_pkts(self.port_id, queue, pkts, to_send),
+                QueueBackend::Ring(_, tx) => ring_enqueue_pkts(tx, pkts, to_send),
+                QueueBackend::Kernel(kernel) => kernel_port_send(kernel, pkts, to_send, &mut dropped),
+            };
+            // Packets the kernel port dropped are consumed but not counted as sent.
+            let update = self.stats_tx.stats.load(Ordering::Relaxed) + (sent - dropped) as usize;
             self.stats_tx.stats.store(update, Ordering::Relaxed);
             Ok(sent as u32)
         }
@@ -76,7 +118,18 @@ impl PortQueue {
     #[inline]
     fn recv_queue(&self, queue: i32, pkts: *mut *mut MBuf, to_recv: i32) -> Result<u32> {
         unsafe {
-            let recv = recv_pkts(self.port_id, queue, pkts, to_recv);
+            let recv = match self.backend {
+                QueueBackend::Pmd => recv_pkts(self.port_id, queue, pkts, to_recv),
+                QueueBackend::Ring(rx, _) => {
+                    let recv = ring_dequeue_pkts(rx, pkts, to_recv);
+                    // NIC timestamps belong to the pipeline or process that received these packets first.
+                    for i in 0..recv {
+                        (**pkts.offset(i as isize)).clear_hw_rx_timestamp();
+                    }
+                    recv
+                }
+                QueueBackend::Kernel(kernel) => kernel_port_recv(kernel, pkts, to_recv),
+            };
             let update = self.stats_rx.stats.load(Ordering::Relaxed) + recv as usize;
             self.stats_rx.stats.store(update, Ordering::Relaxed);
             Ok(recv as u32)
@@ -124,6 +177,19 @@ fn i32_from_bool(x: bool) -> i32 {
     }
 }
 
+// Must match the NB_RSS_* flags in native/pmd.c.
+fn rss_types(hash: &[RssHash]) -> i32 {
+    hash.iter().fold(0, |types, h| {
+        types | match *h {
+            RssHash::Ipv4 => 0x1,
+            RssHash::Ipv6 => 0x2,
+            RssHash::Tcp => 0x4,
+            RssHash::Udp => 0x8,
+            RssHash::Sctp => 0x10,
+        }
+    })
+}
+
 impl PmdPort {
     /// Determine the number of ports in a system.
     pub fn num_pmd_ports() -> i32 {
@@ -147,9 +213,9 @@ impl PmdPort {
     }
 
     pub fn new_queue_pair(port: &Arc<PmdPort>, rxq: i32, txq: i32) -> Result<CacheAligned<PortQueue>> {
-        if rxq > port.rxqs || rxq < 0 {
+        if rxq >= port.rxqs || rxq < 0 {
             Err(ErrorKind::BadRxQueue(port.port, rxq).into())
-        } else if txq > port.txqs || txq < 0 {
+        } else if txq >= port.txqs || txq < 0 {
             Err(ErrorKind::BadTxQueue(port.port, txq).into())
         } else {
             Ok(CacheAligned::allocate(PortQueue {
@@ -159,6 +225,11 @@ impl PmdPort {
                 rxq: rxq,
                 stats_rx: port.stats_rx[rxq as usize].clone(),
                 stats_tx: port.stats_tx[txq as usize].clone(),
+                backend: match port.backend {
+                    PortBackend::Pmd => QueueBackend::Pmd,
+                    PortBackend::Rings(ref rings) => QueueBackend::Ring(rings[rxq as usize].0, rings[txq as usize].1),
+                    PortBackend::Kernel(kernel) => QueueBackend::Kernel(kernel),
+                },
             }))
         }
     }
@@ -169,13 +240,134 @@ impl PmdPort {
         self.port
     }
 
+    /// Number of packets dropped on receipt and transmission by a port backed by a Linux interface, because they were
+    /// too large or no mbufs were available. `None` for other ports.
+    pub fn kernel_drops(&self) -> Option<(u64, u64)> {
+        if let PortBackend::Kernel(kernel) = self.backend {
+            let mut rx = 0;
+            let mut tx = 0;
+            unsafe { kernel_port_drops(kernel, &mut rx, &mut tx) };
+            Some((rx, tx))
+        } else {
+            None
+        }
+    }
+
     /// Get stats for an RX/TX queue pair.
     pub fn stats(&self, queue: i32) -> (usize, usize) {
-        let idx = queue as usize;
-        (
-            self.stats_rx[idx].stats.load(Ordering::Relaxed),
-            self.stats_tx[idx].stats.load(Ordering::Relaxed),
-        )
+        (self.rx_stats(queue), self.tx_stats(queue))
+    }
+
+    /// Packets received on RX queue `queue`.
+    pub fn rx_stats(&self, queue: i32) -> usize {
+        self.stats_rx[queue as usize].stats.load(Ordering::Relaxed)
+    }
+
+    /// Packets sent on TX queue `queue`.
+    pub fn tx_stats(&self, queue: i32) -> usize {
+        self.stats_tx[queue as usize].stats.load(Ordering::Relaxed)
+    }
+
+    /// Change the header fields used to compute the RSS hash (an empty slice selects the default fields). When
+    /// `symmetric` is set a symmetric key is installed so both directions of a flow are received on the same queue,
+    /// otherwise the key the driver configured the port with is restored. Fails if the port cannot hash on any of the
+    /// requested fields. This can be called while the port is running.
+    pub fn set_rss_hash(&self, hash: &[RssHash], symmetric: bool) -> Result<()> {
+        if !self.connected {
+            return Err(ErrorKind::FailedToConfigureRss(self.port).into());
+        }
+        let ret = unsafe { update_rss_hash(self.port, rss_types(hash), i32_from_bool(symmetric)) };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(ErrorKind::FailedToConfigureRss(self.port).into())
+        }
+    }
+
+    /// Number of entries in the port's RSS redirection table.
+    pub fn rss_reta_size(&self) -> Result<usize> {
+        let size = if self.connected {
+            unsafe { rss_reta_size(self.port) }
+        } else {
+            -1
+        };
+        if size > 0 {
+            Ok(size as usize)
+        } else {
+            Err(ErrorKind::FailedToConfigureRss(self.port).into())
+        }
+    }
+
+    /// Replace the RSS redirection table, hash bucket `i` is received on RX queue `reta[i % reta.len()]`. This can be
+    /// called while the port is running, e.g., to move flows away from an overloaded core.
+    pub fn set_rss_reta(&self, reta: &[u16]) -> Result<()> {
+        if let Some(&queue) = reta.iter().find(|&&q| q as i32 >= self.rxqs) {
+            return Err(ErrorKind::BadRxQueue(self.port, queue as i32).into());
+        }
+        if !self.connected || reta.is_empty() {
+            return Err(ErrorKind::FailedToConfigureRss(self.port).into());
+        }
+        let ret = unsafe { update_rss_reta(self.port, reta.as_ptr(), reta.len() as i32) };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(ErrorKind::FailedToConfigureRss(self.port).into())
+        }
+    }
+
+    /// Enable or disable VLAN tag stripping, stripped tags are available through `Packet::vlan_tci`.
+    pub fn set_vlan_strip(&self, on: bool) -> Result<()> {
+        let ret = if self.connected {
+            unsafe { set_vlan_strip(self.port, i32_from_bool(on)) }
+        } else {
+            -1
+        };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(ErrorKind::FailedToInitializePort(self.port).into())
+        }
+    }
+
+    /// Does the NIC classify received packets by L3 and L4 protocol (see `Packet::packet_type`)?
+    pub fn supports_packet_type(&self) -> bool {
+        self.connected && unsafe { supported_ptypes(self.port) } > 0
+    }
+
+    fn configure_rss(&self, rss: &RssConfiguration) -> Result<()> {
+        if rss.hash.is_some() || rss.symmetric {
+            let hash: &[RssHash] = match rss.hash {
+                Some(ref hash) => &hash[..],
+                None => &[],
+            };
+            try!(self.set_rss_hash(hash, rss.symmetric));
+        }
+        if !rss.reta.is_empty() {
+            try!(self.set_rss_reta(&rss.reta[..]));
+        }
+        Ok(())
+    }
+
+    /// NUMA node the port is attached to, or `None` if unknown (e.g., for virtual devices).
+    pub fn socket_id(&self) -> Option<i32> {
+        if !self.connected {
+            return None;
+        }
+        match unsafe { port_socket_id(self.port) } {
+            socket if socket >= 0 => Some(socket),
+            _ => None,
+        }
+    }
+
+    /// Install a hardware flow rule on `port` applying `actions` to packets matching `flow_match`, e.g., to steer a
+    /// flow to a particular queue or drop it before it reaches software. At most one of `Queue` and `Drop` may be
+    /// given.
+    pub fn add_flow_rule(port: &Arc<PmdPort>, flow_match: &FlowMatch, actions: &[FlowAction]) -> Result<FlowRule> {
+        if port.connected {
+            FlowRule::new(port, flow_match, actions)
+        } else {
+            Err(ErrorKind::FlowRuleError(String::from("Port is not connected")).into())
+        }
     }
 
     /// Create a PMD port with a given number of RX and TXQs.
@@ -190,6 +382,8 @@ impl PmdPort {
         loopback: bool,
         tso: bool,
         csumoffload: bool,
+        timestamp: bool,
+        rx_pools: &[*mut RteMempool],
     ) -> Result<Arc<PmdPort>> {
         let loopbackv = i32_from_bool(loopback);
         let tsov = i32_from_bool(tso);
@@ -199,7 +393,9 @@ impl PmdPort {
         let actual_rxqs = min(max_rxqs, rxqs);
         let actual_txqs = min(max_txqs, txqs);
 
-        if ((actual_txqs as usize) <= tx_cores.len()) && ((actual_rxqs as usize) <= rx_cores.len()) {
+        if ((actual_txqs as usize) <= tx_cores.len()) && ((actual_rxqs as usize) <= rx_cores.len()) &&
+            (rx_pools.is_empty() || (actual_rxqs as usize) <= rx_pools.len())
+        {
             let ret = unsafe {
                 init_pmd_port(
                     port,
@@ -212,6 +408,12 @@ impl PmdPort {
                     loopbackv,
                     tsov,
                     csumoffloadv,
+                    i32_from_bool(timestamp),
+                    if rx_pools.is_empty() {
+                        ptr::null()
+                    } else {
+                        rx_pools.as_ptr()
+                    },
                 )
             };
             if ret == 0 {
@@ -223,6 +425,7 @@ impl PmdPort {
                     should_close: true,
                     stats_rx: (0..rxqs).map(|_| Arc::new(PortStats::new())).collect(),
                     stats_tx: (0..txqs).map(|_| Arc::new(PortStats::new())).collect(),
+                    backend: PortBackend::Pmd,
                 }))
             } else {
                 Err(ErrorKind::FailedToInitializePort(port).into())
@@ -250,6 +453,7 @@ impl PmdPort {
                 should_close: false,
                 stats_rx: vec![Arc::new(PortStats::new())],
                 stats_tx: vec![Arc::new(PortStats::new())],
+                backend: PortBackend::Pmd,
             }))
         } else {
             Err(ErrorKind::FailedToInitializePort(port).into())
@@ -270,6 +474,7 @@ impl PmdPort {
                         should_close: false,
                         stats_rx: vec![Arc::new(PortStats::new())],
                         stats_tx: vec![Arc::new(PortStats::new())],
+                        backend: PortBackend::Pmd,
                     }))
                 } else {
                     Err(ErrorKind::FailedToInitializePort(port).into())
@@ -290,7 +495,16 @@ impl PmdPort {
         loopback: bool,
         tso: bool,
         csumoffload: bool,
+        timestamp: bool,
+        rx_pools: &[*mut RteMempool],
     ) -> Result<Arc<PmdPort>> {
+        if is_secondary() {
+            // Devices belong to the primary process, which should share them through `nf` ports.
+            return Err(ErrorKind::ConfigurationError(format!(
+                "Device {} can only be used by the primary process",
+                spec
+            )).into());
+        }
         let cannonical_spec = PmdPort::cannonicalize_pci(spec);
         let port = unsafe { attach_pmd_device((cannonical_spec[..]).as_ptr()) };
         if port >= 0 {
@@ -306,12 +520,105 @@ impl PmdPort {
                 loopback,
                 tso,
                 csumoffload,
+                timestamp,
+                rx_pools,
             ).chain_err(|| ErrorKind::BadDev(String::from(spec)))
         } else {
             Err(ErrorKind::BadDev(String::from(spec)).into())
         }
     }
 
+    /// Create a port that exchanges packets with another NetBricks process through a pair of named rings per queue.
+    /// The primary process creates rings `<name>_rx<queue>` (which the secondary receives from) and `<name>_tx<queue>`
+    /// (which the secondary sends to), and a secondary using a port with the same name attaches to them. Rings hold at
+    /// least `ring_size` packets. Since rings live in the primary's shared memory a secondary can be restarted and
+    /// reattach without the primary noticing; mbufs held by a secondary that exits are lost.
+    fn new_nf_port(name: &str, queues: i32, ring_size: i32) -> Result<Arc<PmdPort>> {
+        let primary = !is_secondary();
+        let count = PmdPort::ring_count(ring_size);
+        let mut rings = Vec::with_capacity(queues as usize);
+        for queue in 0..queues {
+            let to_nf = try!(PmdPort::named_ring(&format!("{}_rx{}", name, queue), count, primary));
+            let from_nf = try!(PmdPort::named_ring(&format!("{}_tx{}", name, queue), count, primary));
+            rings.push(if primary { (from_nf, to_nf) } else { (to_nf, from_nf) });
+        }
+        Ok(Arc::new(PmdPort {
+            connected: false,
+            port: -1,
+            rxqs: queues,
+            txqs: queues,
+            should_close: false,
+            stats_rx: (0..queues).map(|_| Arc::new(PortStats::new())).collect(),
+            stats_tx: (0..queues).map(|_| Arc::new(PortStats::new())).collect(),
+            backend: PortBackend::Rings(rings),
+        }))
+    }
+
+    /// Create a port backed by a named ring per queue (`<name>_<queue>`), creating the rings if they do not already
+    /// exist. Packets sent on a queue are received from the same queue of every port with this name, so a pipeline
+    /// sending to `ring:<name>` can be chained to one receiving from it, in this or another process sharing the same
+    /// mempools. Any number of pipelines can send to or receive from a queue. Rings hold at least `ring_size` packets.
+    fn new_ring_port(name: &str, queues: i32, ring_size: i32) -> Result<Arc<PmdPort>> {
+        let count = PmdPort::ring_count(ring_size);
+        let mut rings = Vec::with_capacity(queues as usize);
+        for queue in 0..queues {
+            let ring = try!(PmdPort::named_ring(&format!("{}_{}", name, queue), count, true));
+            rings.push((ring, ring));
+        }
+        Ok(Arc::new(PmdPort {
+            connected: false,
+            port: -1,
+            rxqs: queues,
+            txqs: queues,
+            should_close: false,
+            stats_rx: (0..queues).map(|_| Arc::new(PortStats::new())).collect(),
+            stats_tx: (0..queues).map(|_| Arc::new(PortStats::new())).collect(),
+            backend: PortBackend::Rings(rings),
+        }))
+    }
+
+    /// Create a single queue port that copies packets to and from a Linux interface, either by binding an AF_PACKET
+    /// socket to an existing interface or by creating a TAP device (when `tap` is set). AF_PACKET rings hold
+    /// `ring_size` packets in each direction. Packets larger than 16KB do not fit in an AF_PACKET ring and are
+    /// dropped (see `kernel_drops`); packets larger than an mbuf are received into chained mbufs.
+    fn new_kernel_port(name: &str, tap: bool, ring_size: i32) -> Result<Arc<PmdPort>> {
+        let ifname = CString::new(name).unwrap();
+        let kernel = unsafe { kernel_port_open(ifname.as_ptr(), i32_from_bool(tap), max(ring_size, 1) as u32) };
+        if kernel.is_null() {
+            return Err(ErrorKind::BadDev(String::from(name)).into());
+        }
+        Ok(Arc::new(PmdPort {
+            connected: false,
+            port: -1,
+            rxqs: 1,
+            txqs: 1,
+            should_close: false,
+            stats_rx: vec![Arc::new(PortStats::new())],
+            stats_tx: vec![Arc::new(PortStats::new())],
+            backend: PortBackend::Kernel(kernel),
+        }))
+    }
+
+    /// Size of a ring that holds at least `ring_size` packets. DPDK rings must be a power of 2 in size and hold one
+    /// packet less than their size, so this is the next power of 2 above `ring_size`.
+    fn ring_count(ring_size: i32) -> u32 {
+        round_to_power_of_2(max(ring_size, 1) as usize + 1) as u32
+    }
+
+    /// Find a named ring, creating it if `create` is set and it does not exist.
+    fn named_ring(name: &str, count: u32, create: bool) -> Result<*mut RteRing> {
+        if name.len() > MAX_RING_NAME_LEN {
+            return Err(ErrorKind::ConfigurationError(format!("Ring name {} is too long", name)).into());
+        }
+        let name_cstr = CString::new(name).unwrap();
+        let ring = unsafe { nb_ring(name_cstr.as_ptr(), count, -1, i32_from_bool(create)) };
+        if ring.is_null() {
+            Err(ErrorKind::FailedToAttachRing(String::from(name)).into())
+        } else {
+            Ok(ring)
+        }
+    }
+
     fn null_port() -> Result<Arc<PmdPort>> {
         Ok(Arc::new(PmdPort {
             connected: false,
@@ -321,12 +628,35 @@ impl PmdPort {
             should_close: false,
             stats_rx: vec![Arc::new(PortStats::new())],
             stats_tx: vec![Arc::new(PortStats::new())],
+            backend: PortBackend::Pmd,
         }))
     }
 
+    /// Look up the mempools named in a port's configuration, returning an empty vector if all queues use the
+    /// default mempools.
+    fn rx_mempools(port_config: &PortConfiguration) -> Result<Vec<*mut RteMempool>> {
+        if port_config.mempool.is_none() && port_config.rx_mempools.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut pools = Vec::with_capacity(port_config.rx_queues.len());
+        for queue in 0..port_config.rx_queues.len() {
+            match port_config.rx_mempool(queue) {
+                Some(name) => match find_mempool(name) {
+                    Some(pool) => pools.push(pool),
+                    None => {
+                        return Err(ErrorKind::ConfigurationError(format!("Unknown mempool {}", name)).into());
+                    }
+                },
+                None => pools.push(ptr::null_mut()),
+            }
+        }
+        Ok(pools)
+    }
+
     /// Create a new port from a `PortConfiguration`.
     pub fn new_port_from_configuration(port_config: &PortConfiguration) -> Result<Arc<PmdPort>> {
-        PmdPort::new_port_with_queues_descriptors_offloads(
+        let rx_pools = try!(PmdPort::rx_mempools(port_config));
+        let port = try!(PmdPort::new_port_with_mempools(
             &port_config.name[..],
             port_config.rx_queues.len() as i32,
             port_config.tx_queues.len() as i32,
@@ -337,14 +667,31 @@ impl PmdPort {
             port_config.loopback,
             port_config.tso,
             port_config.csum,
-        )
+            port_config.timestamp,
+            &rx_pools[..],
+        ));
+        if !port_config.rss.is_default() {
+            try!(port.configure_rss(&port_config.rss));
+        }
+        if port_config.vlan_strip {
+            try!(port.set_vlan_strip(true));
+        }
+        if port_config.ptype && !port.supports_packet_type() {
+            return Err(ErrorKind::ConfigurationError(format!(
+                "Port {} cannot classify packet types",
+                port_config.name
+            )).into());
+        }
+        Ok(port)
     }
 
     /// Create a new port.
     ///
     /// Description
-    /// -   `name`: The name for a port. NetBricks currently supports Bess native vports, OVS shared memory ports and
-    ///     `dpdk` PMDs. DPDK PMDs can be used to input pcap (e.g., `dpdk:eth_pcap0,rx_pcap=<pcap_name>`), etc.
+    /// -   `name`: The name for a port. NetBricks currently supports Bess native vports, OVS shared memory ports, rings
+    ///     shared with other NetBricks processes (`nf:<name>`) or pipelines (`ring:<name>`), Linux interfaces
+    ///     (`af_packet:<interface>` or `tap:<interface>`) and `dpdk` PMDs. DPDK PMDs can be used to input pcap (e.g.,
+    ///     `dpdk:eth_pcap0,rx_pcap=<pcap_name>`), etc.
     /// -   `rxqs`, `txqs`: Number of RX and TX queues.
     /// -   `tx_cores`, `rx_cores`: Core affinity of where the queues will be used.
     /// -   `nrxd`, `ntxd`: RX and TX descriptors.
@@ -359,11 +706,47 @@ impl PmdPort {
         loopback: bool,
         tso: bool,
         csumoffload: bool,
+    ) -> Result<Arc<PmdPort>> {
+        PmdPort::new_port_with_mempools(
+            name,
+            rxqs,
+            txqs,
+            rx_cores,
+            tx_cores,
+            nrxd,
+            ntxd,
+            loopback,
+            tso,
+            csumoffload,
+            false,
+            &[],
+        )
+    }
+
+    /// Create a new port whose RX queues use the given mempools, with one entry per queue (null for the default
+    /// mempool). Only DPDK ports support custom mempools and hardware timestamps.
+    fn new_port_with_mempools(
+        name: &str,
+        rxqs: i32,
+        txqs: i32,
+        rx_cores: &[i32],
+        tx_cores: &[i32],
+        nrxd: i32,
+        ntxd: i32,
+        loopback: bool,
+        tso: bool,
+        csumoffload: bool,
+        timestamp: bool,
+        rx_pools: &[*mut RteMempool],
     ) -> Result<Arc<PmdPort>> {
         let parts: Vec<_> = name.splitn(2, ':').collect();
         match parts[0] {
             "bess" => PmdPort::new_bess_port(parts[1], rx_cores[0]),
             "ovs" => PmdPort::new_ovs_port(parts[1], rx_cores[0]),
+            "nf" => PmdPort::new_nf_port(parts[1], max(rxqs, txqs), max(nrxd, ntxd)),
+            "ring" => PmdPort::new_ring_port(parts[1], max(rxqs, txqs), max(nrxd, ntxd)),
+            "af_packet" => PmdPort::new_kernel_port(parts[1], false, max(nrxd, ntxd)),
+            "tap" => PmdPort::new_kernel_port(parts[1], true, max(nrxd, ntxd)),
             "dpdk" => PmdPort::new_dpdk_port(
                 parts[1],
                 rxqs,
@@ -375,6 +758,8 @@ impl PmdPort {
                 loopback,
                 tso,
                 csumoffload,
+                timestamp,
+                rx_pools,
             ),
             "null" => PmdPort::null_port(),
             _ => PmdPort::new_dpdk_port(
@@ -388,6 +773,8 @@ impl PmdPort {
                 loopback,
                 tso,
                 csumoffload,
+                timestamp,
+                rx_pools,
             ),
         }
     }
@@ -437,6 +824,9 @@ impl PmdPort {
     #[inline]
     pub fn mac_address(&self) -> MacAddress {
         let mut address = MacAddress { addr: [0; 6] };
+        if !self.connected {
+            return address;
+        }
         unsafe {
             rte_eth_macaddr_get(self.port, &mut address as *mut MacAddress);
             address
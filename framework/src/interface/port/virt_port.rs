@@ -1,5 +1,5 @@
 use super::PortStats;
-use super::super::{PacketRx, PacketTx};
+use super::super::{record_alloc_failure, PacketRx, PacketTx};
 use allocators::*;
 use common::*;
 use native::zcsi::*;
@@ -44,7 +44,13 @@ impl PacketRx for VirtualQueue {
     fn recv(&self, pkts: &mut [*mut MBuf]) -> Result<u32> {
         let len = pkts.len() as i32;
         let status = unsafe { mbuf_alloc_bulk(pkts.as_mut_ptr(), 60, len) };
-        let alloced = if status == 0 { len } else { 0 };
+        let alloced = if status == 0 {
+            len
+        } else {
+            // Most likely mbufs are being leaked, make this visible to `MempoolWatchdog`.
+            record_alloc_failure();
+            0
+        };
         let update = self.stats_rx.stats.load(Ordering::Relaxed) + alloced as usize;
         self.stats_rx.stats.store(update, Ordering::Relaxed);
         Ok(alloced as u32)
@@ -26,6 +26,9 @@ extern "C" {
 
     pub fn numa_node_size(node: i32, freep: *mut u64) -> u64;
 
+    /// NUMA node a CPU belongs to, -1 on error.
+    pub fn numa_node_of_cpu(cpu: i32) -> i32;
+
     pub fn numa_preferred() -> i32;
     pub fn numa_set_preferred(node: i32);
     pub fn numa_get_interleave_node() -> i32;
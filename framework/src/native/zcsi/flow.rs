@@ -0,0 +1,40 @@
+// Must match the NB_FLOW_* flags in native/include/flow.h.
+pub const NB_FLOW_ETH_SRC: u32 = 0x1;
+pub const NB_FLOW_ETH_DST: u32 = 0x2;
+pub const NB_FLOW_ETHER_TYPE: u32 = 0x4;
+pub const NB_FLOW_IPV4_SRC: u32 = 0x8;
+pub const NB_FLOW_IPV4_DST: u32 = 0x10;
+pub const NB_FLOW_IP_PROTO: u32 = 0x20;
+pub const NB_FLOW_SRC_PORT: u32 = 0x40;
+pub const NB_FLOW_DST_PORT: u32 = 0x80;
+
+/// Flat description of a flow rule's pattern, see `struct nb_flow_match`. Fields are in host byte order.
+#[repr(C)]
+#[derive(Default)]
+pub struct NbFlowMatch {
+    pub fields: u32,
+    pub eth_src: [u8; 6],
+    pub eth_dst: [u8; 6],
+    pub ether_type: u16,
+    pub ipv4_src: u32,
+    pub ipv4_src_mask: u32,
+    pub ipv4_dst: u32,
+    pub ipv4_dst_mask: u32,
+    pub ip_proto: u8,
+    pub src_port: u16,
+    pub dst_port: u16,
+}
+
+/// See `struct nb_flow_actions`.
+#[repr(C)]
+#[derive(Default)]
+pub struct NbFlowActions {
+    pub queue: i32,
+    pub drop: i32,
+    pub mark: i32,
+    pub mark_id: u32,
+    pub count: i32,
+}
+
+/// Opaque handle to a `struct rte_flow`.
+pub enum RteFlow {}
@@ -1,6 +1,21 @@
 use super::super::super::native_include as ldpdk;
+use super::mbuf_free;
+use std::cmp::min;
+use std::ptr;
 pub type MBuf = ldpdk::rte_mbuf;
 
+// Offload flags, from rte_mbuf.h.
+pub(crate) const PKT_RX_RSS_HASH: u64 = 1 << 1;
+pub(crate) const PKT_RX_L4_CKSUM_BAD: u64 = 1 << 3;
+pub(crate) const PKT_RX_IP_CKSUM_BAD: u64 = 1 << 4;
+pub(crate) const PKT_RX_VLAN_STRIPPED: u64 = 1 << 6;
+pub(crate) const PKT_RX_IP_CKSUM_GOOD: u64 = 1 << 7;
+pub(crate) const PKT_RX_L4_CKSUM_GOOD: u64 = 1 << 8;
+// The mbuf carries an ID set by a flow rule's mark action in hash.fdir.hi.
+pub(crate) const PKT_RX_FDIR_ID: u64 = 1 << 13;
+// The timestamp field holds a timestamp from the NIC.
+pub(crate) const PKT_RX_TIMESTAMP: u64 = 1 << 17;
+
 // FIXME: Remove this once we start using these functions correctly
 #[allow(dead_code)]
 impl MBuf {
@@ -42,7 +57,7 @@ impl MBuf {
         self.buf_len as usize
     }
 
-    /// Returns the length of data in this mbuf segment.
+    /// Returns the length of data in this mbuf segment. For chained packets this is less than `pkt_len`.
     #[inline]
     pub fn data_len(&self) -> usize {
         self.data_len as usize
@@ -54,13 +69,106 @@ impl MBuf {
         self.pkt_len as usize
     }
 
+    /// Number of segments in this packet, only meaningful on the first segment.
+    #[inline]
+    pub fn nb_segs(&self) -> usize {
+        self.nb_segs as usize
+    }
+
+    /// The next segment of this packet, or null if this is the last segment.
+    #[inline]
+    pub fn next_segment(&self) -> *mut MBuf {
+        self.next
+    }
+
+    /// Returns the last segment of the packet starting at `mbuf`.
+    #[inline]
+    pub unsafe fn last_segment(mbuf: *mut MBuf) -> *mut MBuf {
+        let mut seg = mbuf;
+        while !(*seg).next.is_null() {
+            seg = (*seg).next;
+        }
+        seg
+    }
+
+    /// Append the packet starting at `tail` to this packet. Fails (returning false) if the resulting packet would have
+    /// too many segments.
+    #[inline]
+    pub unsafe fn chain(&mut self, tail: *mut MBuf) -> bool {
+        let segs = self.nb_segs() + (*tail).nb_segs();
+        if segs > u16::max_value() as usize {
+            false
+        } else {
+            (*MBuf::last_segment(self)).next = tail;
+            self.nb_segs = segs as u16;
+            self.pkt_len += (*tail).pkt_len;
+            true
+        }
+    }
+
+    /// Copy all data held in later segments into this one and free them. Fails (returning false) if this segment does
+    /// not have enough tailroom.
+    #[inline]
+    pub unsafe fn linearize(&mut self) -> bool {
+        let rest = self.pkt_len() - self.data_len();
+        if self.next.is_null() {
+            true
+        } else if rest > self.pkt_tailroom() {
+            false
+        } else {
+            let mut dst = self.data_address(self.data_len());
+            let mut seg = self.next;
+            while !seg.is_null() {
+                let len = (*seg).data_len();
+                ptr::copy_nonoverlapping((*seg).data_address(0), dst, len);
+                dst = dst.offset(len as isize);
+                seg = (*seg).next;
+            }
+            let chain = self.next;
+            self.next = ptr::null_mut();
+            self.nb_segs = 1;
+            self.data_len += rest as u16;
+            // Frees every segment in the chain.
+            mbuf_free(chain);
+            true
+        }
+    }
+
+    /// Move data from later segments into this one until it holds at least `len` bytes, freeing segments that are
+    /// emptied. Fails (returning false) if the packet is shorter than `len` or this segment does not have enough
+    /// tailroom.
+    #[inline]
+    pub unsafe fn pull_up(&mut self, len: usize) -> bool {
+        if len <= self.data_len() {
+            true
+        } else if len > self.pkt_len() || len - self.data_len() > self.pkt_tailroom() {
+            false
+        } else {
+            while self.data_len() < len {
+                let seg = self.next;
+                let chunk = min(len - self.data_len(), (*seg).data_len());
+                ptr::copy_nonoverlapping((*seg).data_address(0), self.data_address(self.data_len()), chunk);
+                self.data_len += chunk as u16;
+                (*seg).data_off += chunk as u16;
+                (*seg).data_len -= chunk as u16;
+                if (*seg).data_len == 0 {
+                    self.next = (*seg).next;
+                    self.nb_segs -= 1;
+                    (*seg).next = ptr::null_mut();
+                    mbuf_free(seg);
+                }
+            }
+            true
+        }
+    }
+
     #[inline]
     fn pkt_headroom(&self) -> usize {
         self.data_off as usize
     }
 
     #[inline]
-    fn pkt_tailroom(&self) -> usize {
+    pub fn pkt_tailroom(&self) -> usize {
         self.buf_len() - self.data_off as usize - self.data_len()
     }
 
@@ -78,10 +186,26 @@ impl MBuf {
         }
     }
 
-    /// Add data to the end of a packet buffer. This might fail (i.e., return 0) when no more tailroom is left. We do
-    /// not currently deal with packet with multiple segments.
+    /// Add data to the end of the last segment of a packet. This might fail (i.e., return 0) when no more tailroom is
+    /// left in that segment.
     #[inline]
     pub fn add_data_end(&mut self, len: usize) -> usize {
+        unsafe {
+            let last = MBuf::last_segment(self);
+            if len > (*last).pkt_tailroom() {
+                0
+            } else {
+                (*last).data_len += len as u16;
+                self.pkt_len += len as u32;
+                len
+            }
+        }
+    }
+
+    /// Grow the first segment of a packet by `len` bytes, used to make room for headers. This might fail (i.e., return
+    /// 0) when no more tailroom is left in the first segment.
+    #[inline]
+    pub fn add_data_first_segment(&mut self, len: usize) -> usize {
         if len > self.pkt_tailroom() {
             0
         } else {
@@ -103,12 +227,30 @@ impl MBuf {
         }
     }
 
+    /// Remove data from the end of a packet, freeing any segments that are left empty (the first segment is always
+    /// kept). This might fail (i.e., return 0) when the packet is shorter than `len`.
     #[inline]
     pub fn remove_data_end(&mut self, len: usize) -> usize {
-        if len > self.data_len() {
+        if len > self.pkt_len() {
             0
         } else {
-            self.data_len -= len as u16;
+            unsafe {
+                let mut keep = self.pkt_len() - len;
+                let mut segs = 1;
+                let mut seg: *mut MBuf = self;
+                while keep > (*seg).data_len() {
+                    keep -= (*seg).data_len();
+                    seg = (*seg).next;
+                    segs += 1;
+                }
+                (*seg).data_len = keep as u16;
+                let rest = (*seg).next;
+                if !rest.is_null() {
+                    (*seg).next = ptr::null_mut();
+                    self.nb_segs = segs;
+                    mbuf_free(rest);
+                }
+            }
             self.pkt_len -= len as u32;
             len
         }
@@ -119,6 +261,56 @@ impl MBuf {
         unsafe { self.__bindgen_anon_1.refcnt }
     }
 
+    /// Offload flags.
+    #[inline]
+    pub fn ol_flags(&self) -> u64 {
+        self.ol_flags
+    }
+
+    /// Packet type as classified by the NIC (RTE_PTYPE_*).
+    #[inline]
+    pub fn packet_type(&self) -> u32 {
+        unsafe { self.__bindgen_anon_2.packet_type }
+    }
+
+    /// RSS hash, only valid if the NIC set PKT_RX_RSS_HASH.
+    #[inline]
+    pub fn rss(&self) -> u32 {
+        unsafe { self.hash.rss }
+    }
+
+    /// VLAN TCI, only valid if the NIC stripped a tag.
+    #[inline]
+    pub fn vlan_tci(&self) -> u16 {
+        self.vlan_tci
+    }
+
+    /// ID set by a hardware flow rule's mark action, if any.
+    #[inline]
+    pub fn flow_mark(&self) -> Option<u32> {
+        if self.ol_flags & PKT_RX_FDIR_ID != 0 {
+            Some(unsafe { self.hash.fdir.hi })
+        } else {
+            None
+        }
+    }
+
+    /// Receive timestamp set by the NIC, in the device's clock units.
+    #[inline]
+    pub fn hw_rx_timestamp(&self) -> Option<u64> {
+        if self.ol_flags & PKT_RX_TIMESTAMP != 0 {
+            Some(self.timestamp)
+        } else {
+            None
+        }
+    }
+
+    /// Forget the NIC's receive timestamp, for packets received from a queue other than the one that set it.
+    #[inline]
+    pub fn clear_hw_rx_timestamp(&mut self) {
+        self.ol_flags &= !PKT_RX_TIMESTAMP;
+    }
+
     #[inline]
     pub fn reference(&mut self) {
         unsafe {
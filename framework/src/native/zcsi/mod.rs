@@ -1,5 +1,7 @@
 #[cfg_attr(feature = "dev", allow(module_inception))]
 mod zcsi;
 mod mbuf;
+mod flow;
+pub use self::flow::*;
 pub use self::mbuf::*;
 pub use self::zcsi::*;
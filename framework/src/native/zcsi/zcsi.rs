@@ -1,6 +1,26 @@
-use super::MBuf;
+use super::{MBuf, NbFlowActions, NbFlowMatch, RteFlow};
 use headers::MacAddress;
 use std::os::raw::c_char;
+
+/// Opaque DPDK mempool.
+pub enum RteMempool {}
+
+/// Opaque DPDK ring.
+pub enum RteRing {}
+
+/// Opaque handle for a Linux interface accessed through AF_PACKET or TAP.
+pub enum KernelPort {}
+
+/// Occupancy of a mempool, as filled in by `mempool_stats`.
+#[repr(C)]
+pub struct RteMempoolStats {
+    pub name: [c_char; 32],
+    pub size: u32,
+    pub available: u32,
+    pub in_use: u32,
+    pub socket: i32,
+}
+
 #[link(name = "zcsi")]
 extern "C" {
     pub fn init_system_whitelisted(
@@ -12,8 +32,11 @@ extern "C" {
         pool_size: u32,
         cache_size: u32,
         slots: u16,
+        data_size: u32,
+        headroom: u32,
     ) -> i32;
     pub fn init_thread(tid: i32, core: i32) -> i32;
+    pub fn mempool_metadata_slots() -> u16;
     pub fn init_secondary(name: *const c_char, nlen: i32, core: i32, vdevs: *mut *const c_char, vdev_count: i32)
         -> i32;
     pub fn init_pmd_port(
@@ -27,7 +50,14 @@ extern "C" {
         loopback: i32,
         tso: i32,
         csumoffload: i32,
+        timestamp: i32,
+        rx_pools: *const *mut RteMempool,
     ) -> i32;
+    pub fn port_socket_id(port: i32) -> i32;
+    pub fn create_named_mempool(name: *const c_char, size: u32, cache_size: u32, socket: i32) -> *mut RteMempool;
+    pub fn find_named_mempool(name: *const c_char) -> *mut RteMempool;
+    pub fn set_alloc_mempool(pool: *mut RteMempool);
+    pub fn mempool_stats(stats: *mut RteMempoolStats, max: i32) -> i32;
     pub fn free_pmd_port(port: i32) -> i32;
     pub fn recv_pkts(port: i32, qid: i32, pkts: *mut *mut MBuf, len: i32) -> i32;
     pub fn send_pkts(port: i32, qid: i32, pkts: *mut *mut MBuf, len: i32) -> i32;
@@ -35,15 +65,39 @@ extern "C" {
     pub fn rte_eth_macaddr_get(port: i32, address: *mut MacAddress);
     pub fn init_bess_eth_ring(ifname: *const c_char, core: i32) -> i32;
     pub fn init_ovs_eth_ring(iface: i32, core: i32) -> i32;
+    pub fn nb_ring(name: *const c_char, count: u32, socket: i32, create: i32) -> *mut RteRing;
+    pub fn ring_enqueue_pkts(ring: *mut RteRing, pkts: *mut *mut MBuf, len: i32) -> i32;
+    pub fn ring_dequeue_pkts(ring: *mut RteRing, pkts: *mut *mut MBuf, len: i32) -> i32;
+    pub fn kernel_port_open(ifname: *const c_char, tap: i32, frames: u32) -> *mut KernelPort;
+    pub fn kernel_port_close(port: *mut KernelPort);
+    pub fn kernel_port_recv(port: *mut KernelPort, pkts: *mut *mut MBuf, len: i32) -> i32;
+    pub fn kernel_port_send(port: *mut KernelPort, pkts: *mut *mut MBuf, len: i32, dropped: *mut i32) -> i32;
+    pub fn kernel_port_drops(port: *mut KernelPort, rx: *mut u64, tx: *mut u64);
     pub fn find_port_with_pci_address(pciaddr: *const c_char) -> i32;
     pub fn attach_pmd_device(dev: *const c_char) -> i32;
     // FIXME: Generic PMD info
     pub fn max_rxqs(port: i32) -> i32;
     pub fn max_txqs(port: i32) -> i32;
+    pub fn update_rss_hash(port: i32, rss_types: i32, symmetric: i32) -> i32;
+    pub fn rss_reta_size(port: i32) -> i32;
+    pub fn update_rss_reta(port: i32, reta: *const u16, len: i32) -> i32;
+    pub fn set_vlan_strip(port: i32, on: i32) -> i32;
+    pub fn supported_ptypes(port: i32) -> i32;
+    pub fn create_flow_rule(
+        port: i32,
+        flow_match: *const NbFlowMatch,
+        actions: *const NbFlowActions,
+        err: *mut c_char,
+        errlen: i32,
+    ) -> *mut RteFlow;
+    pub fn destroy_flow_rule(port: i32, flow: *mut RteFlow) -> i32;
+    pub fn query_flow_rule_count(port: i32, flow: *mut RteFlow, hits: *mut u64, bytes: *mut u64) -> i32;
     pub fn mbuf_alloc() -> *mut MBuf;
     pub fn mbuf_free(buf: *mut MBuf);
     pub fn mbuf_alloc_bulk(array: *mut *mut MBuf, len: u16, cnt: i32) -> i32;
     pub fn mbuf_free_bulk(array: *mut *mut MBuf, cnt: i32) -> i32;
     pub fn crc_hash_native(to_hash: *const u8, size: u32, iv: u32) -> u32;
     pub fn ipv4_cksum(payload: *const u8) -> u16;
+    pub fn mbuf_set_tso(mbuf: *mut MBuf, l2_len: u16, l3_len: u16, l4_len: u16, mss: u16);
+    pub fn tsc_frequency() -> u64;
 }
@@ -3,7 +3,7 @@ use super::act::Act;
 use super::iterator::*;
 use super::packet_batch::PacketBatch;
 use common::*;
-use interface::Packet;
+use interface::{mbuf_owner, reserve_freeform_metadata, Packet};
 use interface::PacketTx;
 use std::marker::PhantomData;
 
@@ -26,6 +26,9 @@ where
     V: Batch + BatchIterator + Act,
 {
     pub fn new(parent: V, generator: MetadataFn<V::Header, V::Metadata, M>) -> AddMetadataBatch<M, V> {
+        if let Err(e) = reserve_freeform_metadata::<M>() {
+            panic!("Cannot add metadata: {}", e)
+        }
         AddMetadataBatch {
             parent: parent,
             generator: generator,
@@ -73,6 +76,7 @@ where
         if !self.applied {
             self.parent.act();
             {
+                let _owner = mbuf_owner("add_metadata");
                 let iter = PayloadEnumerator::<V::Header, V::Metadata>::new(&mut self.parent);
                 while let Some(ParsedDescriptor { mut packet, .. }) = iter.next(&mut self.parent) {
                     let metadata = (self.generator)(&packet);
@@ -0,0 +1,69 @@
+use super::ReceiveBatch;
+use super::filter_batch::FilterFn;
+use allocators::CacheAligned;
+use headers::EndOffset;
+use interface::Packet;
+use queues::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Hands packets to the host kernel stack (e.g., ARP, routing protocols or management traffic addressed to the box).
+/// Packets are queued for the core servicing the exception path (see `NetBricksContext::add_exception_path`), which
+/// writes them to a TAP interface. Clone this to punt packets from more than one pipeline.
+#[derive(Clone)]
+pub struct ExceptionPath {
+    producer: MpscProducer,
+    dropped: Arc<CacheAligned<AtomicUsize>>,
+}
+
+impl ExceptionPath {
+    /// Queue a packet for the kernel, the packet is dropped (and freed) if the queue is full.
+    #[inline]
+    pub fn enqueue<T: EndOffset, M: Sized + Send>(&self, packet: Packet<T, M>) -> bool {
+        if self.producer.enqueue_one_or_free(packet) {
+            true
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+
+    /// Number of packets dropped because the exception path could not keep up.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Create an `ExceptionPath` queueing up to `size` packets, along with the batch used to receive them.
+pub fn new_exception_path_with_size(size: usize) -> (ExceptionPath, ReceiveBatch<MpscConsumer>) {
+    let (producer, consumer) = new_mpsc_queue_pair_with_size(size);
+    (
+        ExceptionPath {
+            producer: producer,
+            dropped: Arc::new(CacheAligned::allocate(AtomicUsize::new(0))),
+        },
+        consumer,
+    )
+}
+
+const DEFAULT_EXCEPTION_QUEUE_SIZE: usize = 1024;
+
+pub fn new_exception_path() -> (ExceptionPath, ReceiveBatch<MpscConsumer>) {
+    new_exception_path_with_size(DEFAULT_EXCEPTION_QUEUE_SIZE)
+}
+
+/// Filter that hands packets selected by `select` to `exception`, and keeps the rest.
+pub(crate) fn punt_selected<T: EndOffset, M: Sized + Send>(
+    exception: ExceptionPath,
+    mut select: FilterFn<T, M>,
+) -> FilterFn<T, M> {
+    box move |pkt: &Packet<T, M>| {
+        if select(pkt) {
+            // The batch frees its reference when the packet is filtered out.
+            exception.enqueue(pkt.reference());
+            false
+        } else {
+            true
+        }
+    }
+}
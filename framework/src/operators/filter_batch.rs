@@ -4,7 +4,7 @@ use super::iterator::*;
 use super::packet_batch::PacketBatch;
 use common::*;
 use headers::EndOffset;
-use interface::Packet;
+use interface::{mbuf_owner, Packet};
 use interface::PacketTx;
 
 pub type FilterFn<T, M> = Box<FnMut(&Packet<T, M>) -> bool + Send>;
@@ -48,6 +48,7 @@ where
     fn act(&mut self) {
         self.parent.act();
         // Filter during the act
+        let _owner = mbuf_owner("filter");
         let iter = PayloadEnumerator::<T, V::Metadata>::new(&mut self.parent);
         while let Some(ParsedDescriptor {
             mut packet,
@@ -0,0 +1,121 @@
+use super::Batch;
+use super::ReceiveBatch;
+use super::RestoreHeader;
+use super::act::Act;
+use super::iterator::*;
+use common::*;
+use headers::TcpHeader;
+use interface::PacketRx;
+use native::zcsi::MBuf;
+use std::cell::RefCell;
+use std::cmp::min;
+
+/// Packets after software segmentation, restored to their TCP header.
+pub type GsoBatch<V> = RestoreHeader<TcpHeader, <V as BatchIterator>::Metadata, ReceiveBatch<GsoQueue<V>>>;
+
+struct GsoState<V>
+where
+    V: Batch + BatchIterator<Header = TcpHeader> + Act + 'static,
+{
+    parent: V,
+    mss: usize,
+    pending: Vec<*mut MBuf>,
+    failed: usize,
+}
+
+impl<V> GsoState<V>
+where
+    V: Batch + BatchIterator<Header = TcpHeader> + Act + 'static,
+{
+    /// Receive a batch from the parent, splitting packets whose payload exceeds the MSS.
+    fn pull(&mut self) {
+        self.parent.act();
+        {
+            let iter = PayloadEnumerator::<TcpHeader, V::Metadata>::new(&mut self.parent);
+            while let Some(ParsedDescriptor { mut packet, .. }) = iter.next(&mut self.parent) {
+                if packet.tcp_payload_len() <= self.mss {
+                    packet.save_header_and_offset();
+                    self.pending.push(unsafe { packet.get_mbuf() });
+                    continue;
+                }
+                match packet.gso_segment(self.mss) {
+                    Ok(segments) => for mut segment in segments {
+                        segment.save_header_and_offset();
+                        self.pending.push(unsafe { segment.get_mbuf() });
+                    },
+                    Err(_) => self.failed += 1,
+                }
+                packet.free_packet();
+            }
+        }
+        self.parent.get_packet_batch().clear_packets();
+        self.parent.done();
+    }
+}
+
+/// Receive end of the software segmentation operator. The parent is polled whenever all segments produced from its
+/// previous batch have been consumed.
+pub struct GsoQueue<V>
+where
+    V: Batch + BatchIterator<Header = TcpHeader> + Act + 'static,
+{
+    state: RefCell<GsoState<V>>,
+}
+
+// *mut MBuf is not send by default.
+unsafe impl<V> Send for GsoQueue<V>
+where
+    V: Batch + BatchIterator<Header = TcpHeader> + Act + 'static,
+{
+}
+
+impl<V> GsoQueue<V>
+where
+    V: Batch + BatchIterator<Header = TcpHeader> + Act + 'static,
+{
+    /// Number of packets dropped because buffers for their segments could not be allocated.
+    pub fn failed(&self) -> usize {
+        self.state.borrow().failed
+    }
+}
+
+impl<V> PacketRx for GsoQueue<V>
+where
+    V: Batch + BatchIterator<Header = TcpHeader> + Act + 'static,
+{
+    #[inline]
+    fn recv(&self, mbufs: &mut [*mut MBuf]) -> Result<u32> {
+        let mut state = self.state.borrow_mut();
+        if state.pending.is_empty() {
+            state.pull();
+        }
+        let dequeue = min(mbufs.len(), state.pending.len());
+        for (dst, src) in mbufs.iter_mut().zip(state.pending.drain(..dequeue)) {
+            *dst = src;
+        }
+        Ok(dequeue as u32)
+    }
+}
+
+/// Split TCP packets from `parent` carrying more than `mss` bytes of payload in software. Useful when the outgoing
+/// port does not support TSO.
+pub fn new_gso<V>(parent: V, mss: usize) -> GsoBatch<V>
+where
+    V: Batch + BatchIterator<Header = TcpHeader> + Act + 'static,
+{
+    assert!(mss > 0, "MSS must be positive");
+    let capacity = parent.capacity() as usize;
+    let dependencies = parent.get_task_dependencies();
+    let mut batch = ReceiveBatch::new(GsoQueue {
+        state: RefCell::new(GsoState {
+            parent: parent,
+            mss: mss,
+            pending: Vec::with_capacity(capacity),
+            failed: 0,
+        }),
+    });
+    for task in &dependencies {
+        batch.get_packet_batch().add_parent_task(*task);
+    }
+    RestoreHeader::new(batch)
+}
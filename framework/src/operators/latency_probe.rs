@@ -0,0 +1,169 @@
+use super::map_batch::MapFn;
+use super::transform_batch::TransformFn;
+use byteorder::{ByteOrder, LittleEndian};
+use headers::EndOffset;
+use interface::Packet;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use utils::{rdtsc_unsafe, tsc_to_ns, LatencyHistogram};
+
+/// Marks a payload as carrying a latency probe.
+pub const PROBE_MAGIC: u32 = 0x4e42_4c50;
+/// Bytes used by a probe: magic (u32), sequence number (u32) and TSC (u64), all little endian.
+pub const PROBE_LEN: usize = 16;
+// Set in `ProbeStats::next` once a probe has been seen, the low 32 bits hold the next expected sequence number.
+const SEEN: usize = 1 << 32;
+
+/// Sequence numbers for the probes stamped at one point of a chain. Share one `ProbePoint` between all `stamp_latency`
+/// operators stamping the same stream (e.g., one per queue), so probes carry a single sequence that `measure_latency`
+/// can detect gaps in.
+pub struct ProbePoint {
+    seq: AtomicUsize,
+}
+
+impl ProbePoint {
+    pub fn new() -> ProbePoint {
+        ProbePoint {
+            seq: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    fn next_seq(&self) -> u32 {
+        self.seq.fetch_add(1, Ordering::Relaxed) as u32
+    }
+}
+
+impl Default for ProbePoint {
+    fn default() -> ProbePoint {
+        ProbePoint::new()
+    }
+}
+
+/// Latency and loss observed by `measure_latency` operators. Can be shared between operators measuring the same
+/// stream (e.g., one per queue) and with a thread that reports on it while packets are being measured.
+pub struct ProbeStats {
+    latency: LatencyHistogram,
+    next: AtomicUsize,
+    gaps: AtomicUsize,
+    reordered: AtomicUsize,
+    unstamped: AtomicUsize,
+}
+
+impl ProbeStats {
+    pub fn new() -> ProbeStats {
+        ProbeStats {
+            latency: LatencyHistogram::new(),
+            next: AtomicUsize::new(0),
+            gaps: AtomicUsize::new(0),
+            reordered: AtomicUsize::new(0),
+            unstamped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Histogram of measured latencies in nanoseconds.
+    pub fn latency(&self) -> &LatencyHistogram {
+        &self.latency
+    }
+
+    /// Probes that never arrived, inferred from gaps in sequence numbers that were not filled by reordered probes.
+    pub fn lost(&self) -> usize {
+        self.gaps.load(Ordering::Relaxed).saturating_sub(self.reordered())
+    }
+
+    /// Probes that arrived after a probe with a higher sequence number.
+    pub fn reordered(&self) -> usize {
+        self.reordered.load(Ordering::Relaxed)
+    }
+
+    /// Packets that did not carry a probe.
+    pub fn unstamped(&self) -> usize {
+        self.unstamped.load(Ordering::Relaxed)
+    }
+
+    /// Clear all statistics, e.g., between measurement intervals.
+    pub fn reset(&self) {
+        self.latency.reset();
+        self.next.store(0, Ordering::Relaxed);
+        self.gaps.store(0, Ordering::Relaxed);
+        self.reordered.store(0, Ordering::Relaxed);
+        self.unstamped.store(0, Ordering::Relaxed);
+    }
+}
+
+impl ProbeStats {
+    /// Account for a probe with sequence number `seq`, counting the probes skipped since the highest sequence number
+    /// seen so far as gaps, or `seq` as reordered if it is lower.
+    fn record_seq(&self, seq: u32) {
+        let mut current = self.next.load(Ordering::Relaxed);
+        loop {
+            let expected = current as u32;
+            if current & SEEN != 0 && (seq.wrapping_sub(expected) as i32) < 0 {
+                // This probe was counted in a gap when a later one was seen.
+                self.reordered.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            let next = SEEN | seq.wrapping_add(1) as usize;
+            match self.next.compare_exchange_weak(current, next, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => {
+                    if current & SEEN != 0 {
+                        self.gaps.fetch_add(seq.wrapping_sub(expected) as usize, Ordering::Relaxed);
+                    }
+                    return;
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+impl Default for ProbeStats {
+    fn default() -> ProbeStats {
+        ProbeStats::new()
+    }
+}
+
+impl fmt::Display for ProbeStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} lost {} reordered {} unstamped {}",
+            self.latency,
+            self.lost(),
+            self.reordered(),
+            self.unstamped()
+        )
+    }
+}
+
+/// Write a probe with `point`'s next sequence number and the current TSC at `offset` bytes into each packet's payload.
+/// Packets too short to hold a probe are left untouched.
+pub(crate) fn stamp_probe<T: EndOffset, M: Sized + Send>(offset: usize, point: Arc<ProbePoint>) -> TransformFn<T, M> {
+    box move |pkt: &mut Packet<T, M>| {
+        let payload = pkt.get_mut_payload();
+        if payload.len() >= offset + PROBE_LEN {
+            let probe = &mut payload[offset..offset + PROBE_LEN];
+            LittleEndian::write_u32(&mut probe[..4], PROBE_MAGIC);
+            LittleEndian::write_u32(&mut probe[4..8], point.next_seq());
+            LittleEndian::write_u64(&mut probe[8..], rdtsc_unsafe());
+        }
+    }
+}
+
+/// Read probes written by `stamp_probe` and record the time elapsed since they were stamped in `stats`.
+pub(crate) fn measure_probe<T: EndOffset, M: Sized + Send>(offset: usize, stats: Arc<ProbeStats>) -> MapFn<T, M> {
+    box move |pkt: &Packet<T, M>| {
+        let now = rdtsc_unsafe();
+        let payload = pkt.get_payload();
+        if payload.len() < offset + PROBE_LEN || LittleEndian::read_u32(&payload[offset..offset + 4]) != PROBE_MAGIC {
+            stats.unstamped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let probe = &payload[offset..offset + PROBE_LEN];
+        let seq = LittleEndian::read_u32(&probe[4..8]);
+        let stamped = LittleEndian::read_u64(&probe[8..]);
+        stats.latency.record(tsc_to_ns(now.saturating_sub(stamped)));
+        stats.record_seq(seq);
+    }
+}
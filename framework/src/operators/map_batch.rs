@@ -4,7 +4,7 @@ use super::iterator::*;
 use super::packet_batch::PacketBatch;
 use common::*;
 use headers::EndOffset;
-use interface::Packet;
+use interface::{mbuf_owner, Packet};
 use interface::PacketTx;
 use std::marker::PhantomData;
 
@@ -53,6 +53,7 @@ where
         if !self.applied {
             self.parent.act();
             {
+                let _owner = mbuf_owner("map");
                 let iter = PayloadEnumerator::<T, V::Metadata>::new(&mut self.parent);
                 while let Some(ParsedDescriptor { packet, .. }) = iter.next(&mut self.parent) {
                     (self.transformer)(&packet);
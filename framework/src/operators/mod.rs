@@ -5,23 +5,32 @@ pub use self::add_metadata_mut::MutableAddMetadataBatch;
 use self::add_metadata_mut::MutableMetadataFn;
 pub use self::composition_batch::CompositionBatch;
 pub use self::deparsed_batch::DeparsedBatch;
+pub use self::exception_path::{new_exception_path, new_exception_path_with_size, ExceptionPath};
+use self::exception_path::punt_selected;
 pub use self::filter_batch::FilterBatch;
 use self::filter_batch::FilterFn;
 pub use self::group_by::*;
+pub use self::gso_batch::*;
+use self::latency_probe::{measure_probe, stamp_probe};
+pub use self::latency_probe::{ProbePoint, ProbeStats, PROBE_LEN, PROBE_MAGIC};
 use self::iterator::BatchIterator;
 pub use self::map_batch::MapBatch;
 use self::map_batch::MapFn;
 pub use self::merge_batch::MergeBatch;
 pub use self::parsed_batch::ParsedBatch;
+pub use self::partition::*;
 pub use self::receive_batch::ReceiveBatch;
+pub use self::replicate::*;
 pub use self::reset_parse::ResetParsingBatch;
 pub use self::restore_header::*;
 pub use self::send_batch::SendBatch;
+pub use self::steer_batch::*;
 pub use self::transform_batch::TransformBatch;
 use self::transform_batch::TransformFn;
 use headers::*;
 use interface::*;
 use scheduler::Scheduler;
+use std::sync::Arc;
 
 #[macro_use]
 mod macros;
@@ -29,16 +38,22 @@ mod macros;
 mod act;
 mod composition_batch;
 mod deparsed_batch;
+mod exception_path;
 mod filter_batch;
 mod group_by;
+mod gso_batch;
 mod iterator;
+mod latency_probe;
 mod map_batch;
 mod merge_batch;
 mod packet_batch;
 mod parsed_batch;
+mod partition;
 mod receive_batch;
+mod replicate;
 mod reset_parse;
 mod send_batch;
+mod steer_batch;
 mod transform_batch;
 mod restore_header;
 mod add_metadata;
@@ -129,6 +144,20 @@ pub trait Batch: BatchIterator + Act + Send {
         FilterBatch::<Self::Header, Self>::new(self, filter_f)
     }
 
+    /// Hand packets for which `select` returns true to the host kernel stack through `exception`, removing them from
+    /// the batch. Replies from the kernel are received by the exception path's pipeline (see
+    /// `NetBricksContext::add_exception_path`).
+    fn exception(
+        self,
+        exception: ExceptionPath,
+        select: FilterFn<Self::Header, Self::Metadata>,
+    ) -> FilterBatch<Self::Header, Self>
+    where
+        Self: Sized,
+    {
+        FilterBatch::<Self::Header, Self>::new(self, punt_selected(exception, select))
+    }
+
     /// Reset the packet pointer to 0. This is identical to composition except for using static dispatch.
     fn reset(self) -> ResetParsingBatch<Self>
     where
@@ -157,4 +186,76 @@ pub trait Batch: BatchIterator + Act + Send {
     {
         GroupBy::<Self::Header, Self>::new(self, groups, group_f, sched)
     }
+
+    /// Send every packet in this batch to `replicas` downstream batches (obtained through `get_replica`). When `copy`
+    /// is false replicas share the underlying mbuf and must not be modified, otherwise every replica except the first
+    /// receives its own copy of the packet.
+    fn replicate<S: Scheduler + Sized>(
+        self,
+        replicas: usize,
+        copy: bool,
+        sched: &mut S,
+    ) -> Replicate<Self::Header, Self>
+    where
+        Self: Sized,
+    {
+        Replicate::<Self::Header, Self>::new(self, replicas, copy, sched)
+    }
+
+    /// Split this batch in two: packets for which `predicate` returns true go to the first batch, the rest to the
+    /// second. Unlike `group_by` this does not add a scheduler task or use MPSC queues, the parent is polled whenever
+    /// the side being executed has no packets left. Both batches should be scheduled on the same core, so that they do
+    /// not contend for the lock protecting the packets waiting on each side.
+    fn partition(
+        self,
+        predicate: PartitionFn<Self::Header, Self::Metadata>,
+    ) -> (PartitionBatch<Self::Header, Self>, PartitionBatch<Self::Header, Self>)
+    where
+        Self: Sized + 'static,
+    {
+        new_partition(self, predicate)
+    }
+
+    /// Steer packets in this batch to the targets of `steering` (usually pipelines on other cores, see
+    /// `NetBricksContext::add_steered_pipeline`). `steer_f` picks the target for each packet, its result is taken modulo
+    /// the number of targets. The returned task must be added to a scheduler.
+    fn steer(
+        self,
+        steering: Steering,
+        steer_f: SteerFn<Self::Header, Self::Metadata>,
+    ) -> SteerBatch<Self::Header, Self>
+    where
+        Self: Sized + 'static,
+    {
+        SteerBatch::<Self::Header, Self>::new(self, steering, steer_f)
+    }
+
+    /// Split TCP packets carrying more than `mss` bytes of payload into multiple packets in software (generic
+    /// segmentation offload). Use `Packet::request_tso` instead when the outgoing port supports TSO.
+    fn gso(self, mss: usize) -> GsoBatch<Self>
+    where
+        Self: BatchIterator<Header = TcpHeader> + Sized + 'static,
+    {
+        new_gso(self, mss)
+    }
+
+    /// Stamp each packet with a latency probe (a sequence number from `point` and the current TSC) written `offset`
+    /// bytes into its payload, for measurement by `measure_latency` further down the chain. Packets whose payload
+    /// cannot hold `PROBE_LEN` bytes at `offset` are not stamped.
+    fn stamp_latency(self, offset: usize, point: Arc<ProbePoint>) -> TransformBatch<Self::Header, Self>
+    where
+        Self: Sized,
+    {
+        TransformBatch::<Self::Header, Self>::new(self, stamp_probe(offset, point))
+    }
+
+    /// Measure the latency of packets stamped by `stamp_latency`, recording it and any lost or reordered probes in
+    /// `stats`. Placing the stamp on egress and the measurement on ingress of the same host gives round-trip times;
+    /// one-way times require both ends to share a TSC (e.g., NFs on the same machine).
+    fn measure_latency(self, offset: usize, stats: Arc<ProbeStats>) -> MapBatch<Self::Header, Self>
+    where
+        Self: Sized,
+    {
+        MapBatch::<Self::Header, Self>::new(self, measure_probe(offset, stats))
+    }
 }
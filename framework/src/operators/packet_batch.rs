@@ -84,6 +84,22 @@ impl PacketBatch {
         }
     }
 
+    /// Record `ns` as the receive time of every packet in this batch.
+    #[inline]
+    pub fn set_rx_timestamps(&mut self, ns: u64) {
+        for mbuf in &self.array {
+            set_rx_timestamp(*mbuf, ns);
+        }
+    }
+
+    /// Record that no packet in this batch has a receive time.
+    #[inline]
+    pub fn clear_rx_timestamps(&mut self) {
+        for mbuf in &self.array {
+            clear_rx_timestamp(*mbuf);
+        }
+    }
+
     // Assumes we have already deallocated batch.
     #[inline]
     unsafe fn recv_internal<Rx: PacketRx>(&mut self, port: &Rx) -> Result<u32> {
@@ -93,6 +109,7 @@ impl PacketBatch {
             e @ Err(_) => e,
             Ok(recv) => {
                 self.add_to_batch(recv as usize);
+                track_mbufs(&self.array[..recv as usize]);
                 Ok(recv)
             }
         }
@@ -142,6 +159,7 @@ impl PacketBatch {
                 } else {
                     // Now free the dropped packets
                     let len = self.scratch.len();
+                    untrack_mbufs(&self.scratch);
                     // No need to offset here since self.scratch is tight.
                     let array_ptr = self.scratch.as_mut_ptr();
                     let ret = mbuf_free_bulk(array_ptr, len as i32);
@@ -191,8 +209,10 @@ impl PacketBatch {
                 let ret = mbuf_alloc_bulk(self.array.as_mut_ptr(), len, cnt);
                 if ret == 0 {
                     self.array.set_len(cnt as usize);
+                    track_mbufs(&self.array);
                     Ok(())
                 } else {
+                    record_alloc_failure();
                     Err(ErrorKind::FailedAllocation.into())
                 }
             }
@@ -206,6 +226,7 @@ impl PacketBatch {
                 Ok(())
             } else {
                 let len = self.array.len() as i32;
+                untrack_mbufs(&self.array);
                 let ret = {
                     let parray = self.packet_ptr().as_mut_ptr();
                     mbuf_free_bulk(parray, len)
@@ -258,6 +279,7 @@ impl Act for PacketBatch {
             unsafe {
                 // let available = self.available() as i32;
                 try!(port.send(self.packet_ptr()).and_then(|sent| {
+                    untrack_mbufs(&self.array[..sent as usize]);
                     self.consume_batch_partial(sent as usize);
                     total_sent += sent;
                     Ok(sent)
@@ -0,0 +1,159 @@
+use super::Batch;
+use super::ReceiveBatch;
+use super::RestoreHeader;
+use super::act::Act;
+use super::iterator::*;
+use common::*;
+use headers::EndOffset;
+use interface::{Packet, PacketRx};
+use native::zcsi::MBuf;
+use std::cmp::min;
+use std::sync::{Arc, Mutex};
+
+pub type PartitionFn<T, M> = Box<FnMut(&Packet<T, M>) -> bool + Send>;
+
+/// One side of a partition, packets are restored to the header type they had when the partition was made.
+pub type PartitionBatch<T, V> = RestoreHeader<T, <V as BatchIterator>::Metadata, ReceiveBatch<PartitionQueue<T, V>>>;
+
+struct PartitionState<T, V>
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+    parent: V,
+    predicate: PartitionFn<T, V::Metadata>,
+    /// Packets for which the predicate returned true.
+    matched: Vec<*mut MBuf>,
+    /// Packets for which the predicate returned false.
+    unmatched: Vec<*mut MBuf>,
+    limit: usize,
+    dropped: usize,
+}
+
+// *mut MBuf is not send by default, the mbufs are owned by the partition and only accessed with the lock held.
+unsafe impl<T, V> Send for PartitionState<T, V>
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+}
+
+impl<T, V> PartitionState<T, V>
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+    /// Receive a batch from the parent and split it between the two sides.
+    fn pull(&mut self) {
+        self.parent.act();
+        {
+            let iter = PayloadEnumerator::<T, V::Metadata>::new(&mut self.parent);
+            while let Some(ParsedDescriptor { mut packet, .. }) = iter.next(&mut self.parent) {
+                let matched = (self.predicate)(&packet);
+                packet.save_header_and_offset();
+                let side = if matched {
+                    &mut self.matched
+                } else {
+                    &mut self.unmatched
+                };
+                if side.len() < self.limit {
+                    side.push(unsafe { packet.get_mbuf() });
+                } else {
+                    // The other side has not kept up, drop rather than buffer without bound.
+                    packet.free_packet();
+                    self.dropped += 1;
+                }
+            }
+        }
+        self.parent.get_packet_batch().clear_packets();
+        self.parent.done();
+    }
+
+    fn dequeue(&mut self, matched: bool, mbufs: &mut [*mut MBuf]) -> usize {
+        let side = if matched {
+            &mut self.matched
+        } else {
+            &mut self.unmatched
+        };
+        let dequeue = min(mbufs.len(), side.len());
+        for (dst, src) in mbufs.iter_mut().zip(side.drain(..dequeue)) {
+            *dst = src;
+        }
+        dequeue
+    }
+}
+
+/// The receive end of one side of a partition. Packets are only pulled from the parent once the side being polled has
+/// run dry, so a parent batch is consumed exactly once without going through a queue. Both sides share state through a
+/// mutex, which is uncontended when they are scheduled on the same core.
+pub struct PartitionQueue<T, V>
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+    state: Arc<Mutex<PartitionState<T, V>>>,
+    matched: bool,
+}
+
+impl<T, V> PartitionQueue<T, V>
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+    /// Number of packets dropped because one of the sides was not being drained.
+    pub fn dropped(&self) -> usize {
+        self.state.lock().unwrap().dropped
+    }
+}
+
+impl<T, V> PacketRx for PartitionQueue<T, V>
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+    #[inline]
+    fn recv(&self, mbufs: &mut [*mut MBuf]) -> Result<u32> {
+        let mut state = self.state.lock().unwrap();
+        let received = state.dequeue(self.matched, mbufs);
+        if received > 0 {
+            Ok(received as u32)
+        } else {
+            state.pull();
+            Ok(state.dequeue(self.matched, mbufs) as u32)
+        }
+    }
+}
+
+/// Split `parent` into two batches: the first receives packets for which `predicate` returns true, the second the rest.
+/// Both batches should be scheduled on the same core, so that they do not contend for the shared state.
+pub fn new_partition<T, V>(
+    parent: V,
+    predicate: PartitionFn<T, V::Metadata>,
+) -> (PartitionBatch<T, V>, PartitionBatch<T, V>)
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+    let capacity = parent.capacity() as usize;
+    let dependencies = parent.get_task_dependencies();
+    let state = Arc::new(Mutex::new(PartitionState {
+        parent: parent,
+        predicate: predicate,
+        // Allow each side to fall one batch behind before we start dropping.
+        matched: Vec::with_capacity(2 * capacity),
+        unmatched: Vec::with_capacity(2 * capacity),
+        limit: 2 * capacity,
+        dropped: 0,
+    }));
+    let side = |matched: bool| {
+        let mut side = ReceiveBatch::new(PartitionQueue {
+            state: state.clone(),
+            matched: matched,
+        });
+        for task in &dependencies {
+            side.get_packet_batch().add_parent_task(*task);
+        }
+        RestoreHeader::new(side)
+    };
+    (side(true), side(false))
+}
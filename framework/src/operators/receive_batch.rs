@@ -4,11 +4,13 @@ use super::iterator::*;
 use super::packet_batch::PacketBatch;
 use common::*;
 use headers::NullHeader;
-use interface::{PacketRx, PacketTx};
+use interface::{mbuf_owner, PacketRx, PacketTx};
+use utils::tsc_now_ns;
 
 pub struct ReceiveBatch<T: PacketRx> {
     parent: PacketBatch,
     queue: T,
+    timestamp: bool,
     pub received: u64,
 }
 
@@ -17,6 +19,7 @@ impl<T: PacketRx> ReceiveBatch<T> {
         ReceiveBatch {
             parent: parent,
             queue: queue,
+            timestamp: false,
             received: 0,
         }
     }
@@ -25,9 +28,19 @@ impl<T: PacketRx> ReceiveBatch<T> {
         ReceiveBatch {
             parent: PacketBatch::new(32),
             queue: queue,
+            timestamp: false,
             received: 0,
         }
     }
+
+    /// Receive from `queue`, recording the TSC time at which each batch was received as the receive time of its packets
+    /// (see `Packet::rx_timestamp`). Hardware timestamps, if any, are kept separately (see `Packet::hw_rx_timestamp`).
+    pub fn new_with_timestamps(queue: T) -> ReceiveBatch<T> {
+        ReceiveBatch {
+            timestamp: true,
+            ..ReceiveBatch::new(queue)
+        }
+    }
 }
 
 impl<T: PacketRx> Batch for ReceiveBatch<T> {}
@@ -51,6 +64,7 @@ impl<T: PacketRx> Act for ReceiveBatch<T> {
     #[inline]
     fn act(&mut self) {
         self.parent.act();
+        let _owner = mbuf_owner("receive");
         self.parent
             .recv(&self.queue)
             .and_then(|x| {
@@ -58,6 +72,11 @@ impl<T: PacketRx> Act for ReceiveBatch<T> {
                 Ok(x)
             })
             .expect("Receive failure");
+        if self.timestamp {
+            self.parent.set_rx_timestamps(tsc_now_ns());
+        } else {
+            self.parent.clear_rx_timestamps();
+        }
     }
 
     #[inline]
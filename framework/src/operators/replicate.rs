@@ -0,0 +1,129 @@
+use super::Batch;
+use super::ReceiveBatch;
+use super::RestoreHeader;
+use super::act::Act;
+use super::iterator::*;
+use headers::EndOffset;
+use interface::Packet;
+use queues::*;
+use scheduler::{Executable, Scheduler};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// Sends every packet received from the parent batch to each of `replicas` downstream batches, useful for port
+/// mirroring, multicast fan-out or feeding a monitoring pipeline alongside forwarding.
+pub struct Replicate<T, V>
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+    _phantom_v: PhantomData<V>,
+    replicas: usize,
+    _phantom_t: PhantomData<T>,
+    consumers: HashMap<usize, ReceiveBatch<MpscConsumer>>,
+    task: usize,
+}
+
+struct ReplicateProducer<T, V>
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+    parent: V,
+    producers: Vec<MpscProducer>,
+    copy: bool,
+    // Reused across packets to avoid allocating on the data path.
+    replicas: Vec<Option<Packet<T, V::Metadata>>>,
+}
+
+impl<T, V> Executable for ReplicateProducer<T, V>
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+    #[inline]
+    fn execute(&mut self) {
+        self.parent.act(); // Let the parent get some packets.
+        {
+            let iter = PayloadEnumerator::<T, V::Metadata>::new(&mut self.parent);
+            while let Some(ParsedDescriptor { mut packet, .. }) = iter.next(&mut self.parent) {
+                packet.save_header_and_offset();
+                // Take all references before enqueuing anything, otherwise a consumer running on another core might
+                // free the mbuf before we are done with it.
+                for _ in 1..self.producers.len() {
+                    let replica = if self.copy {
+                        packet.copy()
+                    } else {
+                        Some(packet.reference())
+                    };
+                    self.replicas.push(replica);
+                }
+                self.producers[0].enqueue_one_or_free(packet);
+                for (producer, replica) in self.producers[1..].iter().zip(self.replicas.drain(..)) {
+                    if let Some(replica) = replica {
+                        producer.enqueue_one_or_free(replica);
+                    }
+                }
+            }
+        }
+        self.parent.get_packet_batch().clear_packets();
+        self.parent.done();
+    }
+
+    #[inline]
+    fn dependencies(&mut self) -> Vec<usize> {
+        self.parent.get_task_dependencies()
+    }
+}
+
+#[cfg_attr(feature = "dev", allow(len_without_is_empty))]
+impl<T, V> Replicate<T, V>
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+    /// Create a new replication node. When `copy` is false all replicas share the same mbuf (whose reference count is
+    /// incremented) and should be treated as read-only. When `copy` is true, replica 0 receives the original packet and
+    /// all other replicas receive a copy, so each of them can be modified independently.
+    pub fn new<S: Scheduler + Sized>(parent: V, replicas: usize, copy: bool, sched: &mut S) -> Replicate<T, V> {
+        assert!(replicas > 0, "Must replicate to at least one batch");
+        let mut producers = Vec::with_capacity(replicas);
+        let mut consumers = HashMap::with_capacity(replicas);
+        for i in 0..replicas {
+            let (prod, consumer) = new_mpsc_queue_pair();
+            producers.push(prod);
+            consumers.insert(i, consumer);
+        }
+        let task = sched
+            .add_task(ReplicateProducer {
+                parent: parent,
+                producers: producers,
+                copy: copy,
+                replicas: Vec::with_capacity(replicas),
+            })
+            .unwrap();
+        Replicate {
+            _phantom_v: PhantomData,
+            replicas: replicas,
+            _phantom_t: PhantomData,
+            consumers: consumers,
+            task: task,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.replicas
+    }
+
+    pub fn get_replica(&mut self, replica: usize) -> Option<RestoreHeader<T, V::Metadata, ReceiveBatch<MpscConsumer>>> {
+        match self.consumers.remove(&replica) {
+            Some(mut p) => {
+                {
+                    p.get_packet_batch().add_parent_task(self.task)
+                };
+                Some(RestoreHeader::new(p))
+            }
+            None => None,
+        }
+    }
+}
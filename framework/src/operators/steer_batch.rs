@@ -0,0 +1,126 @@
+use super::Batch;
+use super::ReceiveBatch;
+use super::act::Act;
+use super::iterator::*;
+use allocators::CacheAligned;
+use headers::EndOffset;
+use interface::Packet;
+use queues::*;
+use scheduler::Executable;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub type SteerFn<T, M> = Box<FnMut(&Packet<T, M>) -> usize + Send>;
+
+/// A set of queues used to steer packets to pipelines running on other cores (e.g., by flow hash to pin flows to a
+/// stateful core). Clone this to steer from more than one pipeline, all clones share the same queues and drop counters.
+#[derive(Clone)]
+pub struct Steering {
+    producers: Vec<MpscProducer>,
+    dropped: Vec<Arc<CacheAligned<AtomicUsize>>>,
+}
+
+#[cfg_attr(feature = "dev", allow(len_without_is_empty))]
+impl Steering {
+    /// Number of targets.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.producers.len()
+    }
+
+    /// Steer a packet to `target`, the packet is dropped (and freed) if the target's queue is full.
+    #[inline]
+    pub fn enqueue<T: EndOffset, M: Sized + Send>(&self, target: usize, packet: Packet<T, M>) -> bool {
+        if self.producers[target].enqueue_one_or_free(packet) {
+            true
+        } else {
+            self.dropped[target].fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+
+    /// Number of packets dropped because the queue for `target` was full.
+    pub fn dropped(&self, target: usize) -> usize {
+        self.dropped[target].load(Ordering::Relaxed)
+    }
+}
+
+/// Create a `Steering` with `targets` queues of `size` slots each, along with the batches used to receive packets from
+/// each of them.
+pub fn new_steering_with_size(targets: usize, size: usize) -> (Steering, Vec<ReceiveBatch<MpscConsumer>>) {
+    assert!(targets > 0, "Must steer to at least one target");
+    let mut producers = Vec::with_capacity(targets);
+    let mut consumers = Vec::with_capacity(targets);
+    for _ in 0..targets {
+        let (prod, consumer) = new_mpsc_queue_pair_with_size(size);
+        producers.push(prod);
+        consumers.push(consumer);
+    }
+    (
+        Steering {
+            producers: producers,
+            dropped: (0..targets)
+                .map(|_| Arc::new(CacheAligned::allocate(AtomicUsize::new(0))))
+                .collect(),
+        },
+        consumers,
+    )
+}
+
+const DEFAULT_STEERING_QUEUE_SIZE: usize = 1024;
+
+pub fn new_steering(targets: usize) -> (Steering, Vec<ReceiveBatch<MpscConsumer>>) {
+    new_steering_with_size(targets, DEFAULT_STEERING_QUEUE_SIZE)
+}
+
+/// Steers packets from the parent batch to one of the `Steering` targets. The steering function's return value is taken
+/// modulo the number of targets, so a flow hash can be returned directly.
+pub struct SteerBatch<T, V>
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+    parent: V,
+    steering: Steering,
+    steer_fn: SteerFn<T, V::Metadata>,
+}
+
+impl<T, V> SteerBatch<T, V>
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+    pub fn new(parent: V, steering: Steering, steer_fn: SteerFn<T, V::Metadata>) -> SteerBatch<T, V> {
+        SteerBatch {
+            parent: parent,
+            steering: steering,
+            steer_fn: steer_fn,
+        }
+    }
+}
+
+impl<T, V> Executable for SteerBatch<T, V>
+where
+    T: EndOffset + 'static,
+    V: Batch + BatchIterator<Header = T> + Act + 'static,
+{
+    #[inline]
+    fn execute(&mut self) {
+        self.parent.act();
+        {
+            let targets = self.steering.len();
+            let iter = PayloadEnumerator::<T, V::Metadata>::new(&mut self.parent);
+            while let Some(ParsedDescriptor { packet, .. }) = iter.next(&mut self.parent) {
+                let target = (self.steer_fn)(&packet) % targets;
+                self.steering.enqueue(target, packet);
+            }
+        }
+        self.parent.get_packet_batch().clear_packets();
+        self.parent.done();
+    }
+
+    #[inline]
+    fn dependencies(&mut self) -> Vec<usize> {
+        self.parent.get_task_dependencies()
+    }
+}
@@ -4,7 +4,7 @@ use super::iterator::*;
 use super::packet_batch::PacketBatch;
 use common::*;
 use headers::EndOffset;
-use interface::Packet;
+use interface::{mbuf_owner, Packet};
 use interface::PacketTx;
 use std::marker::PhantomData;
 
@@ -71,6 +71,7 @@ where
         if !self.applied {
             self.parent.act();
             {
+                let _owner = mbuf_owner("transform");
                 let iter = PayloadEnumerator::<T, V::Metadata>::new(&mut self.parent);
                 while let Some(ParsedDescriptor { mut packet, .. }) = iter.next(&mut self.parent) {
                     (self.transformer)(&mut packet);
@@ -1,7 +1,7 @@
 use common::*;
 use headers::EndOffset;
-use interface::{Packet, PacketRx};
-use native::zcsi::MBuf;
+use interface::{untrack_mbufs, Packet, PacketRx};
+use native::zcsi::{mbuf_free, MBuf};
 use operators::ReceiveBatch;
 use std::clone::Clone;
 use std::cmp::min;
@@ -234,6 +234,20 @@ impl MpscProducer {
     pub fn enqueue_one<T: EndOffset, M: Sized + Send>(&self, packet: Packet<T, M>) -> bool {
         unsafe { self.mpsc_queue.enqueue_one(packet.get_mbuf()) }
     }
+
+    /// Enqueue a single packet, freeing it if the queue is full. Returns true if the packet was enqueued.
+    pub fn enqueue_one_or_free<T: EndOffset, M: Sized + Send>(&self, packet: Packet<T, M>) -> bool {
+        unsafe {
+            let mbuf = packet.get_mbuf();
+            if self.mpsc_queue.enqueue_one(mbuf) {
+                true
+            } else {
+                untrack_mbufs(&[mbuf]);
+                mbuf_free(mbuf);
+                false
+            }
+        }
+    }
 }
 
 pub struct MpscConsumer {
@@ -243,7 +257,12 @@ pub struct MpscConsumer {
 impl PacketRx for MpscConsumer {
     #[inline]
     fn recv(&self, mbufs: &mut [*mut MBuf]) -> Result<u32> {
-        Ok(self.mpsc_queue.dequeue(mbufs) as u32)
+        let recv = self.mpsc_queue.dequeue(mbufs);
+        // NIC timestamps belong to the pipeline that received these packets first.
+        for mbuf in &mbufs[..recv] {
+            unsafe { (**mbuf).clear_hw_rx_timestamp() }
+        }
+        Ok(recv as u32)
     }
 }
 
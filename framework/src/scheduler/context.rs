@@ -1,11 +1,16 @@
 use allocators::CacheAligned;
-use config::NetbricksConfiguration;
+use config::{NetbricksConfiguration, PortConfiguration};
 use interface::{PmdPort, PortQueue, VirtualPort, VirtualQueue};
 use interface::dpdk::{init_system, init_thread};
+use native::libnuma;
+use operators::{new_exception_path, new_steering, Batch, ExceptionPath, ReceiveBatch, Steering};
+use queues::MpscConsumer;
 use scheduler::*;
+use state::{read_checkpoint, write_checkpoint, Checkpoint};
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::sync::Arc;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{sync_channel, SyncSender};
 use std::thread::{self, JoinHandle, Thread};
 
@@ -31,6 +36,17 @@ impl<'a> BarrierHandle<'a> {
     }
 }
 
+/// Releases a barrier when dropped, so paused schedulers resume even if the code run while they are paused panics.
+struct ReleaseOnDrop<'a>(Option<BarrierHandle<'a>>);
+
+impl<'a> Drop for ReleaseOnDrop<'a> {
+    fn drop(&mut self) {
+        if let Some(handle) = self.0.take() {
+            handle.release();
+        }
+    }
+}
+
 /// `NetBricksContext` contains handles to all schedulers, and provides mechanisms for coordination.
 #[derive(Default)]
 pub struct NetBricksContext {
@@ -152,6 +168,70 @@ impl NetBricksContext {
         }
     }
 
+    /// Install a pipeline on each of `cores` that receives packets steered to it, and return the `Steering` used to
+    /// send packets to these pipelines (target `i` runs on `cores[i]`). Clone the `Steering` into the pipelines that
+    /// should steer packets, e.g. to rebalance flows across cores when the NIC's RSS does not spread them well.
+    pub fn add_steered_pipeline<T>(&mut self, cores: &[i32], run: Arc<T>) -> Result<Steering>
+    where
+        T: Fn(ReceiveBatch<MpscConsumer>, &mut StandaloneScheduler) + Send + Sync + 'static,
+    {
+        if cores.is_empty() {
+            return Err(ErrorKind::ConfigurationError(String::from("Steering requires at least one core")).into());
+        }
+        if let Some(core) = cores
+            .iter()
+            .find(|core| !self.scheduler_channels.contains_key(*core))
+        {
+            return Err(ErrorKind::NoRunningSchedulerOnCore(*core).into());
+        }
+        let (steering, consumers) = new_steering(cores.len());
+        for (core, consumer) in cores.iter().zip(consumers.into_iter()) {
+            // Run commands must be callable more than once, but each consumer can only be handed out once.
+            let consumer = Mutex::new(Some(consumer));
+            let boxed_run = run.clone();
+            self.scheduler_channels[core]
+                .send(SchedulerCommand::Run(Arc::new(move |s| {
+                    if let Some(consumer) = consumer.lock().unwrap().take() {
+                        boxed_run(consumer, s)
+                    }
+                })))
+                .unwrap();
+        }
+        Ok(steering)
+    }
+
+    /// Set up an exception path to the host kernel stack through the TAP interface `ifname`, serviced by `core`.
+    /// Packets handed to the returned `ExceptionPath` (see `Batch::exception`) are written to the interface, and
+    /// `run` is called to install a pipeline that receives the kernel's packets (e.g., replies to send out a port).
+    /// `core` should be dedicated to the exception path, since writing to the kernel is slow.
+    pub fn add_exception_path<T>(&mut self, ifname: &str, core: i32, run: Arc<T>) -> Result<ExceptionPath>
+    where
+        T: Fn(ReceiveBatch<AlignedPortQueue>, &mut StandaloneScheduler) + Send + Sync + 'static,
+    {
+        if !self.scheduler_channels.contains_key(&core) {
+            return Err(ErrorKind::NoRunningSchedulerOnCore(core).into());
+        }
+        if self.rx_queues.contains_key(&core) {
+            println!("Warning: exception path {} shares core {} with port queues", ifname, core);
+        }
+        let name = format!("tap:{}", ifname);
+        let port = try!(PmdPort::new(&name[..], core));
+        let queue = try!(PmdPort::new_queue_pair(&port, 0, 0));
+        self.ports.insert(name, port);
+        let (exception, consumer) = new_exception_path();
+        // Run commands must be callable more than once, but the consumer can only be handed out once.
+        let consumer = Mutex::new(Some(consumer));
+        self.scheduler_channels[&core]
+            .send(SchedulerCommand::Run(Arc::new(move |s| {
+                if let Some(consumer) = consumer.lock().unwrap().take() {
+                    s.add_task(consumer.send(queue.clone())).unwrap();
+                    run(ReceiveBatch::new(queue.clone()), s)
+                }
+            })))
+            .unwrap();
+        Ok(exception)
+    }
+
     /// Start scheduling pipelines.
     pub fn execute(&mut self) {
         for (core, channel) in &self.scheduler_channels {
@@ -185,6 +265,25 @@ impl NetBricksContext {
         )
     }
 
+    /// Run `f` with all schedulers paused (see `barrier`), e.g., to sync control plane stores before checkpointing
+    /// them. Pipelines resume once `f` returns (or panics).
+    pub fn paused<R, F: FnOnce() -> R>(&mut self, f: F) -> R {
+        let _release = ReleaseOnDrop(Some(self.barrier()));
+        f()
+    }
+
+    /// Pause all schedulers, write `state` to `out` (see `write_checkpoint`), and resume them. Use `paused` to also
+    /// sync stores first.
+    pub fn checkpoint(&mut self, state: &[&Checkpoint], out: &mut Write) -> Result<()> {
+        self.paused(|| write_checkpoint(state, out))
+    }
+
+    /// Pause all schedulers, restore `state` (in the order it was passed to `checkpoint`) from `input`, and resume
+    /// them.
+    pub fn restore(&mut self, state: &mut [&mut Checkpoint], input: &mut Read) -> Result<()> {
+        self.paused(|| read_checkpoint(state, input))
+    }
+
     /// Stop all schedulers, safely shutting down the system.
     pub fn stop(&mut self) {
         for (core, channel) in &self.scheduler_channels {
@@ -212,6 +311,40 @@ impl NetBricksContext {
     }
 }
 
+/// Warn about RX queues polled by a core on a different NUMA node than the port or the queue's mempool, since every
+/// packet then crosses the interconnect.
+fn warn_remote_numa(configuration: &NetbricksConfiguration, port: &PortConfiguration, instance: &PmdPort) {
+    if unsafe { libnuma::numa_available() } == -1 {
+        return;
+    }
+    let port_node = instance.socket_id();
+    for (rx_q, core) in port.rx_queues.iter().enumerate() {
+        let core_node = unsafe { libnuma::numa_node_of_cpu(*core) };
+        if core_node < 0 {
+            continue;
+        }
+        if let Some(node) = port_node {
+            if node != core_node {
+                println!(
+                    "Warning: core {} (node {}) polls queue {} of port {} attached to node {}",
+                    core, core_node, rx_q, port.name, node
+                );
+            }
+        }
+        let pool_node = port.rx_mempool(rx_q)
+            .and_then(|name| configuration.mempools.iter().find(|p| p.name == name))
+            .and_then(|pool| pool.socket);
+        if let Some(node) = pool_node {
+            if node != core_node {
+                println!(
+                    "Warning: core {} (node {}) receives queue {} of port {} into a mempool on node {}",
+                    core, core_node, rx_q, port.name, node
+                );
+            }
+        }
+    }
+}
+
 /// Initialize the system from a configuration.
 pub fn initialize_system(configuration: &NetbricksConfiguration) -> Result<NetBricksContext> {
     init_system(configuration);
@@ -237,6 +370,7 @@ pub fn initialize_system(configuration: &NetbricksConfiguration) -> Result<NetBr
             }
 
             let port_instance = &ctx.ports[&port.name];
+            warn_remote_numa(configuration, port, port_instance);
 
             for (rx_q, core) in port.rx_queues.iter().enumerate() {
                 let rx_q = rx_q as i32;
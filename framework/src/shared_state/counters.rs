@@ -0,0 +1,93 @@
+use super::{open_shared, ContainerHeader, SharedMemory, CONTAINER_HEADER_SIZE};
+use std::cmp::min;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, Ordering};
+use utils::round_to_pages;
+
+pub(crate) const COUNTER_NAME_LEN: usize = 56;
+
+/// A counter occupies a cache line, so cores updating different counters do not contend.
+#[repr(C)]
+pub(crate) struct Counter {
+    pub name: [u8; COUNTER_NAME_LEN],
+    pub value: AtomicU64,
+}
+
+/// A set of named 64-bit counters stored in a shared memory segment, so that other processes can read them (see
+/// `Directory::register_counters`). Counters are atomic, so they can be updated from any core without taking a
+/// snapshot. Names longer than 55 bytes are truncated.
+pub struct SharedCounters {
+    shared: SharedMemory<ContainerHeader>,
+    counters: *mut Counter,
+}
+
+unsafe impl Send for SharedCounters {}
+unsafe impl Sync for SharedCounters {}
+
+impl SharedCounters {
+    /// Create counters called `names` (all starting at 0) in the shared memory segment `name`.
+    pub fn new(name: &str, names: &[&str]) -> SharedCounters {
+        unsafe {
+            let shared: SharedMemory<ContainerHeader> =
+                open_shared(name, round_to_pages(SharedCounters::segment_size(names.len())));
+            (*shared.mem).len.store(names.len(), Ordering::Relaxed);
+            (*shared.mem).capacity = names.len();
+            let counters = (shared.mem as *mut u8).offset(CONTAINER_HEADER_SIZE as isize) as *mut Counter;
+            for (i, name) in names.iter().enumerate() {
+                let counter = &mut *counters.offset(i as isize);
+                // Leave room for a terminating NUL.
+                let len = min(name.len(), COUNTER_NAME_LEN - 1);
+                counter.name[..len].copy_from_slice(&name.as_bytes()[..len]);
+            }
+            SharedCounters {
+                shared: shared,
+                counters: counters,
+            }
+        }
+    }
+
+    pub(crate) fn segment_size(count: usize) -> usize {
+        CONTAINER_HEADER_SIZE + count * size_of::<Counter>()
+    }
+
+    #[inline]
+    fn counter(&self, idx: usize) -> &Counter {
+        assert!(idx < self.len());
+        unsafe { &*self.counters.offset(idx as isize) }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        unsafe { (*self.shared.mem).len.load(Ordering::Relaxed) }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Index of the counter called `name`.
+    pub fn index(&self, name: &str) -> Option<usize> {
+        let name = name.as_bytes();
+        let name = &name[..min(name.len(), COUNTER_NAME_LEN - 1)];
+        (0..self.len()).find(|idx| {
+            let stored = &self.counter(*idx).name;
+            &stored[..name.len()] == name && stored[name.len()] == 0
+        })
+    }
+
+    #[inline]
+    pub fn add(&self, idx: usize, value: u64) {
+        self.counter(idx).value.fetch_add(value, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn set(&self, idx: usize, value: u64) {
+        self.counter(idx).value.store(value, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn get(&self, idx: usize) -> u64 {
+        self.counter(idx).value.load(Ordering::Relaxed)
+    }
+}
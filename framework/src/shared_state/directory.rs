@@ -1,15 +1,27 @@
-use super::{open_shared, SharedMemory};
-use std::mem::size_of;
+use super::counters::{Counter, COUNTER_NAME_LEN};
+use super::shared_map::{MapSlot, SLOT_VALID};
+use super::{open_existing, open_shared, ContainerHeader, SharedCounters, SharedHashMap, SharedMemory, SharedVec,
+            CONTAINER_HEADER_SIZE};
+use common::*;
+use std::cmp::min;
+use std::hash::Hash;
+use std::mem::{self, size_of};
+use std::ptr;
+use std::slice;
+use std::str;
 use std::sync::atomic::*;
-use utils::PAGE_SIZE;
+use std::thread;
+use std::time::Duration;
+use utils::{round_to_pages, PAGE_SIZE};
 /// A directory of shared structures.
 
 const MAX_LEN: usize = 256; // 255 byte names
 const DIRECTORY_PAGES: usize = 2; // Dedicate 2 pages to the directory.
 const BYTE_SIZE: usize = DIRECTORY_PAGES * PAGE_SIZE;
+const SNAPSHOT_RETRIES: usize = 1000;
 
 /// Directory header for shared data.
-#[repr(packed, C)]
+#[repr(C)]
 pub struct DirectoryHeader {
     entries: AtomicUsize,
     // Used to signal that snapshotting is in progress.
@@ -18,12 +30,48 @@ pub struct DirectoryHeader {
     length: usize,
 }
 
-#[repr(packed, C)]
+/// What a directory entry refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    /// A name with no associated segment.
+    Name,
+    Vec,
+    HashMap,
+    Counters,
+}
+
+impl EntryKind {
+    fn from_raw(kind: usize) -> Option<EntryKind> {
+        match kind {
+            0 => Some(EntryKind::Name),
+            1 => Some(EntryKind::Vec),
+            2 => Some(EntryKind::HashMap),
+            3 => Some(EntryKind::Counters),
+            _ => None,
+        }
+    }
+}
+
+/// An entry in the directory, describing where a container is and how its elements are laid out so that processes
+/// that do not know the element types (e.g., inspection tools) can read them.
+#[repr(C)]
+#[derive(Clone, Copy)]
 pub struct DirectoryEntry {
     pub name: [u8; MAX_LEN],
+    pub kind: usize,
+    /// Size of the container's segment.
+    pub segment_size: usize,
+    /// Bytes between consecutive elements (or map slots).
+    pub stride: usize,
+    /// Offset and size of keys (map keys or counter names) within an element, size is 0 for vectors.
+    pub key_offset: usize,
+    pub key_size: usize,
+    pub value_offset: usize,
+    pub value_size: usize,
 }
 
 pub struct Directory {
+    name: String,
     head: *mut DirectoryHeader,
     data: *mut DirectoryEntry,
     // Need this to make sure memory is not dropped
@@ -32,7 +80,14 @@ pub struct Directory {
     len: usize,
 }
 
+/// Name of the segment holding directory entry `entry` in directory `directory`.
+fn segment_name(directory: &str, entry: &str) -> String {
+    format!("{}.{}", directory, entry)
+}
+
 impl Directory {
+    /// Create a directory in the shared memory segment `name` (which must start with a '/'). Containers registered in
+    /// the directory are placed in segments named `<name>.<entry>`.
     pub fn new(name: &str) -> Directory {
         unsafe {
             let shared = open_shared(name, BYTE_SIZE);
@@ -46,6 +101,7 @@ impl Directory {
             (*head).entries.store(0, Ordering::Release);
             (*head).committed_version.store(1, Ordering::SeqCst);
             Directory {
+                name: String::from(name),
                 head: head,
                 data: entry,
                 _shared_memory: shared,
@@ -55,21 +111,100 @@ impl Directory {
         }
     }
 
-    pub fn register_new_entry(&mut self, name: &str) -> Option<usize> {
+    /// Is there room for an entry called `name`, and is it a valid segment name?
+    fn can_register(&self, name: &str) -> bool {
+        self.entry < self.len && !name.is_empty() && name.len() < MAX_LEN && !name.contains('/')
+    }
+
+    fn register(&mut self, name: &str, layout: DirectoryEntry) -> Option<usize> {
         let entry = self.entry;
-        if entry >= self.len || name.len() >= MAX_LEN {
+        if !self.can_register(name) {
             None
         } else {
             unsafe {
                 let entry_ptr = self.data.offset(entry as isize);
-                (*entry_ptr).name.copy_from_slice(name.as_bytes());
-                (*self.head).entries.store(entry, Ordering::Release);
+                *entry_ptr = layout;
+                (*entry_ptr).name[..name.len()].copy_from_slice(name.as_bytes());
+                // Readers only look at entries once they are complete.
+                (*self.head).entries.store(entry + 1, Ordering::Release);
             }
             self.entry += 1;
             Some(entry)
         }
     }
 
+    fn layout(kind: EntryKind, segment_size: usize) -> DirectoryEntry {
+        DirectoryEntry {
+            name: [0; MAX_LEN],
+            kind: kind as usize,
+            segment_size: round_to_pages(segment_size),
+            stride: 0,
+            key_offset: 0,
+            key_size: 0,
+            value_offset: 0,
+            value_size: 0,
+        }
+    }
+
+    pub fn register_new_entry(&mut self, name: &str) -> Option<usize> {
+        self.register(name, Directory::layout(EntryKind::Name, 0))
+    }
+
+    /// Create a `SharedVec` called `name` with room for `capacity` elements, `None` if the directory is full or the
+    /// name is invalid.
+    pub fn register_vec<T: Copy + 'static>(&mut self, name: &str, capacity: usize) -> Option<SharedVec<T>> {
+        let mut layout = Directory::layout(EntryKind::Vec, SharedVec::<T>::segment_size(capacity));
+        layout.stride = size_of::<T>();
+        layout.value_size = size_of::<T>();
+        if !self.can_register(name) {
+            return None;
+        }
+        // Create the segment before publishing the entry, so readers can always open registered entries.
+        let vec = SharedVec::new_with_capacity(&segment_name(&self.name, name), capacity);
+        self.register(name, layout).map(|_| vec)
+    }
+
+    /// Create a `SharedHashMap` called `name` with room for `capacity` entries.
+    pub fn register_map<K: Copy + Eq + Hash + 'static, V: Copy + 'static>(
+        &mut self,
+        name: &str,
+        capacity: usize,
+    ) -> Option<SharedHashMap<K, V>> {
+        if !self.can_register(name) {
+            return None;
+        }
+        let mut layout = Directory::layout(EntryKind::HashMap, SharedHashMap::<K, V>::segment_size(capacity));
+        let slot = MapSlot {
+            state: 0u8,
+            key: unsafe { mem::zeroed::<K>() },
+            value: unsafe { mem::zeroed::<V>() },
+        };
+        let base = &slot as *const MapSlot<K, V> as usize;
+        layout.stride = size_of::<MapSlot<K, V>>();
+        layout.key_offset = &slot.key as *const K as usize - base;
+        layout.key_size = size_of::<K>();
+        layout.value_offset = &slot.value as *const V as usize - base;
+        layout.value_size = size_of::<V>();
+        let map = SharedHashMap::new_with_capacity(&segment_name(&self.name, name), capacity);
+        self.register(name, layout).map(|_| map)
+    }
+
+    /// Create `SharedCounters` called `name`, with one counter for each of `names`.
+    pub fn register_counters(&mut self, name: &str, names: &[&str]) -> Option<SharedCounters> {
+        if !self.can_register(name) {
+            return None;
+        }
+        let mut layout = Directory::layout(EntryKind::Counters, SharedCounters::segment_size(names.len()));
+        layout.stride = size_of::<Counter>();
+        layout.key_size = COUNTER_NAME_LEN;
+        layout.value_offset = COUNTER_NAME_LEN;
+        layout.value_size = size_of::<u64>();
+        let counters = SharedCounters::new(&segment_name(&self.name, name), names);
+        self.register(name, layout).map(|_| counters)
+    }
+
+    /// Start updating shared containers. Readers retry until the matching `end_snapshot`, so that they see either the
+    /// state before or after all updates between the two calls.
     #[inline]
     pub fn begin_snapshot(&mut self) {
         unsafe {
@@ -77,6 +212,7 @@ impl Directory {
         }
     }
 
+    /// Publish the updates made since `begin_snapshot`.
     #[inline]
     pub fn end_snapshot(&mut self) {
         unsafe {
@@ -87,3 +223,182 @@ impl Directory {
         }
     }
 }
+
+/// A directory entry, as seen by a reader.
+#[derive(Clone, Debug)]
+pub struct EntryInfo {
+    pub name: String,
+    pub kind: EntryKind,
+    pub segment_size: usize,
+    pub stride: usize,
+    pub key_offset: usize,
+    pub key_size: usize,
+    pub value_offset: usize,
+    pub value_size: usize,
+}
+
+/// Read-only access to a `Directory` created by another process, e.g., to inspect a running NF.
+pub struct DirectoryReader {
+    name: String,
+    head: *const DirectoryHeader,
+    data: *const DirectoryEntry,
+    _shared_memory: SharedMemory<DirectoryHeader>,
+}
+
+impl DirectoryReader {
+    /// Open the directory `name`, as passed to `Directory::new`.
+    pub fn open(name: &str) -> Result<DirectoryReader> {
+        unsafe {
+            let shared: SharedMemory<DirectoryHeader> = try!(open_existing(name));
+            if shared.size < BYTE_SIZE {
+                return Err(ErrorKind::InvalidSharedEntry(String::from(name)).into());
+            }
+            let head = shared.mem as *const DirectoryHeader;
+            Ok(DirectoryReader {
+                name: String::from(name),
+                head: head,
+                data: head.offset(1) as *const DirectoryEntry,
+                _shared_memory: shared,
+            })
+        }
+    }
+
+    /// Entries registered so far.
+    pub fn entries(&self) -> Vec<EntryInfo> {
+        unsafe {
+            let count = (*self.head).entries.load(Ordering::Acquire);
+            let room = (BYTE_SIZE - size_of::<DirectoryHeader>()) / size_of::<DirectoryEntry>();
+            let count = min(count, room);
+            slice::from_raw_parts(self.data, count)
+                .iter()
+                .filter_map(|e| {
+                    let len = e.name.iter().position(|b| *b == 0).unwrap_or(MAX_LEN);
+                    EntryKind::from_raw(e.kind).map(|kind| EntryInfo {
+                        name: String::from_utf8_lossy(&e.name[..len]).into_owned(),
+                        kind: kind,
+                        segment_size: e.segment_size,
+                        stride: e.stride,
+                        key_offset: e.key_offset,
+                        key_size: e.key_size,
+                        value_offset: e.value_offset,
+                        value_size: e.value_size,
+                    })
+                })
+                .collect()
+        }
+    }
+
+    /// Map the container described by `entry`.
+    pub fn open_entry(&self, entry: &EntryInfo) -> Result<EntryReader> {
+        if entry.kind == EntryKind::Name || entry.key_offset + entry.key_size > entry.stride
+            || entry.value_offset + entry.value_size > entry.stride
+        {
+            return Err(ErrorKind::InvalidSharedEntry(entry.name.clone()).into());
+        }
+        let shared: SharedMemory<ContainerHeader> =
+            unsafe { try!(open_existing(&segment_name(&self.name, &entry.name))) };
+        if shared.size < entry.segment_size || shared.size < CONTAINER_HEADER_SIZE {
+            return Err(ErrorKind::InvalidSharedEntry(entry.name.clone()).into());
+        }
+        Ok(EntryReader {
+            info: entry.clone(),
+            shared: shared,
+        })
+    }
+
+    /// Run `read` on a consistent version of the shared state, i.e., not while the writer is between
+    /// `begin_snapshot` and `end_snapshot`, retrying if the writer starts a snapshot while `read` runs. Returns the
+    /// result along with the version read, or `None` if no consistent version could be read.
+    pub fn read_snapshot<R, F: FnMut() -> R>(&self, mut read: F) -> Option<(usize, R)> {
+        let head = unsafe { &*self.head };
+        for _ in 0..SNAPSHOT_RETRIES {
+            let version = head.committed_version.load(Ordering::Acquire);
+            if head.current_version.load(Ordering::Acquire) == version {
+                let result = read();
+                fence(Ordering::Acquire);
+                if head.current_version.load(Ordering::Relaxed) == version {
+                    return Some((version, result));
+                }
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        None
+    }
+}
+
+/// Read-only access to a container registered in a directory, in terms of raw keys and values.
+pub struct EntryReader {
+    info: EntryInfo,
+    shared: SharedMemory<ContainerHeader>,
+}
+
+impl EntryReader {
+    pub fn info(&self) -> &EntryInfo {
+        &self.info
+    }
+
+    /// Raw bytes of element (or map slot) `idx`.
+    fn element(&self, idx: usize) -> &[u8] {
+        unsafe {
+            let base = (self.shared.mem as *const u8).offset((CONTAINER_HEADER_SIZE + idx * self.info.stride) as isize);
+            slice::from_raw_parts(base, self.info.stride)
+        }
+    }
+
+    /// State of map slot `idx`, loaded with `Acquire` so that the key and value written before it are visible.
+    fn slot_state(&self, idx: usize) -> u8 {
+        unsafe { (*(self.element(idx).as_ptr() as *const AtomicU8)).load(Ordering::Acquire) }
+    }
+
+    /// Key and value of element `idx`.
+    fn record(&self, idx: usize) -> (&[u8], &[u8]) {
+        let element = self.element(idx);
+        let key = &element[self.info.key_offset..self.info.key_offset + self.info.key_size];
+        let value = &element[self.info.value_offset..self.info.value_offset + self.info.value_size];
+        match self.info.kind {
+            // Counter names are NUL terminated.
+            EntryKind::Counters => (&key[..key.iter().position(|b| *b == 0).unwrap_or(key.len())], value),
+            _ => (key, value),
+        }
+    }
+
+    /// Keys (empty for vectors) and values of all elements. Elements added before the call are complete, but use
+    /// `DirectoryReader::read_snapshot` to get a consistent view of elements that the writer updates in place.
+    pub fn records(&self) -> Vec<(&[u8], &[u8])> {
+        if self.info.stride == 0 {
+            return vec![];
+        }
+        let (len, capacity) = unsafe { ((*self.shared.mem).len.load(Ordering::Acquire), (*self.shared.mem).capacity) };
+        // Do not trust the header to stay within the segment.
+        let room = (self.shared.size - CONTAINER_HEADER_SIZE) / self.info.stride;
+        match self.info.kind {
+            EntryKind::HashMap => (0..min(capacity, room))
+                .filter(|i| self.slot_state(*i) == SLOT_VALID)
+                .map(|i| self.record(i))
+                .collect(),
+            _ => (0..min(len, room)).map(|i| self.record(i)).collect(),
+        }
+    }
+
+    /// Values of all elements, for readers that know the element type.
+    pub fn values<T: Copy>(&self) -> Vec<T> {
+        assert_eq!(size_of::<T>(), self.info.value_size, "Wrong value type for {}", self.info.name);
+        self.records()
+            .iter()
+            .map(|&(_, v)| unsafe { ptr::read_unaligned(v.as_ptr() as *const T) })
+            .collect()
+    }
+
+    /// Counter names and values, for `Counters` entries.
+    pub fn counters(&self) -> Vec<(String, u64)> {
+        self.records()
+            .iter()
+            .map(|&(k, v)| {
+                (
+                    str::from_utf8(k).unwrap_or("").to_string(),
+                    unsafe { (*(v.as_ptr() as *const AtomicU64)).load(Ordering::Relaxed) },
+                )
+            })
+            .collect()
+    }
+}
@@ -1,17 +1,40 @@
 /// Shareable data structures.
 pub mod directory;
+pub use self::counters::*;
+pub use self::port_stats::*;
+pub use self::shared_map::*;
 pub use self::shared_vec::*;
+mod counters;
+mod port_stats;
+mod shared_map;
 mod shared_vec;
-use libc::{self, c_void, close, ftruncate, mmap, munmap, shm_open, shm_unlink};
+use common::*;
+use libc::{self, c_void, close, fstat, ftruncate, mmap, munmap, shm_open, shm_unlink};
 use std::ffi::CString;
 use std::io::Error;
+use std::mem;
 use std::ptr;
+use std::sync::atomic::AtomicUsize;
 use utils::PAGE_SIZE;
 
+/// Containers place their data this far into their segment, after their header.
+const CONTAINER_HEADER_SIZE: usize = 64;
+
+/// Header at the start of every container segment.
+#[repr(C)]
+struct ContainerHeader {
+    /// Number of elements (for vectors and counters) or entries (for maps). Stored with `Release` after the element
+    /// it counts is written, so readers that load it with `Acquire` see every element below `len`.
+    len: AtomicUsize,
+    capacity: usize,
+}
+
 struct SharedMemory<T> {
     pub mem: *mut T,
     name: CString,
     size: usize,
+    /// Segments are unlinked when the process that created them drops them.
+    owner: bool,
 }
 
 impl<T> Drop for SharedMemory<T> {
@@ -20,15 +43,17 @@ impl<T> Drop for SharedMemory<T> {
             let size = self.size;
             let _ret = munmap(self.mem as *mut c_void, size); // Unmap pages.
                                                               // Record munmap failure.
-            let shm_ret = shm_unlink(self.name.as_ptr());
-            assert!(shm_ret == 0, "Could not unlink shared memory region");
+            if self.owner {
+                let shm_ret = shm_unlink(self.name.as_ptr());
+                assert!(shm_ret == 0, "Could not unlink shared memory region");
+            }
         }
     }
 }
 
 unsafe fn open_shared<T>(name: &str, size: usize) -> SharedMemory<T> {
     // Make sure size is page aligned
-    assert!(size & !PAGE_SIZE == 0);
+    assert!(size & (PAGE_SIZE - 1) == 0);
     let name = CString::new(name).unwrap();
     let mut fd = shm_open(
         name.as_ptr(),
@@ -50,11 +75,12 @@ unsafe fn open_shared<T>(name: &str, size: usize) -> SharedMemory<T> {
     assert!(fd >= 0, "Could not create shared memory segment");
     let ftret = ftruncate(fd, size as i64);
     assert!(ftret == 0, "Could not truncate");
+    // The mapping must be shared for other processes to see updates.
     let address = mmap(
         ptr::null_mut(),
         size,
         libc::PROT_READ | libc::PROT_WRITE,
-        libc::MAP_POPULATE | libc::MAP_PRIVATE,
+        libc::MAP_POPULATE | libc::MAP_SHARED,
         fd,
         0,
     );
@@ -68,5 +94,33 @@ unsafe fn open_shared<T>(name: &str, size: usize) -> SharedMemory<T> {
         mem: address as *mut T,
         name: name,
         size: size,
+        owner: true,
+    }
+}
+
+/// Map an existing segment created by another process, read-only. The segment is not unlinked when dropped.
+unsafe fn open_existing<T>(name: &str) -> Result<SharedMemory<T>> {
+    let name = CString::new(name).unwrap();
+    let fd = shm_open(name.as_ptr(), libc::O_RDONLY, 0);
+    if fd < 0 {
+        return Err(Error::last_os_error().into());
     }
+    let mut stat: libc::stat = mem::zeroed();
+    if fstat(fd, &mut stat) != 0 {
+        let err = Error::last_os_error();
+        close(fd);
+        return Err(err.into());
+    }
+    let size = stat.st_size as usize;
+    let address = mmap(ptr::null_mut(), size, libc::PROT_READ, libc::MAP_SHARED, fd, 0);
+    close(fd);
+    if address == libc::MAP_FAILED {
+        return Err(Error::last_os_error().into());
+    }
+    Ok(SharedMemory {
+        mem: address as *mut T,
+        name: name,
+        size: size,
+        owner: false,
+    })
 }
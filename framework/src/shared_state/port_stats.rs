@@ -0,0 +1,52 @@
+use super::SharedCounters;
+use super::directory::Directory;
+use interface::PmdPort;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Publishes packet counts for ports in a `Directory`, so they can be read by other processes. Each port gets a set of
+/// counters called `port.<name>` holding the total packets received and sent (`rx` and `tx`) and per queue counts
+/// (`rxq<n>` and `txq<n>`). Counts are copied when `update` is called, e.g., from the control loop.
+pub struct PortStatsExporter {
+    ports: Vec<(Arc<PmdPort>, usize, usize, SharedCounters)>,
+}
+
+impl PortStatsExporter {
+    /// Register counters for `ports` (e.g., `NetBricksContext::ports`). Ports that cannot be registered (because the
+    /// directory is full) are skipped.
+    pub fn new(directory: &mut Directory, ports: &HashMap<String, Arc<PmdPort>>) -> PortStatsExporter {
+        let mut exported = Vec::with_capacity(ports.len());
+        for (name, port) in ports {
+            let (rxqs, txqs) = (port.rxqs() as usize, port.txqs() as usize);
+            let mut names = vec![String::from("rx"), String::from("tx")];
+            names.extend((0..rxqs).map(|q| format!("rxq{}", q)));
+            names.extend((0..txqs).map(|q| format!("txq{}", q)));
+            let names: Vec<_> = names.iter().map(|n| n.as_str()).collect();
+            let entry = format!("port.{}", name.replace('/', "_"));
+            match directory.register_counters(&entry, &names) {
+                Some(counters) => exported.push((port.clone(), rxqs, txqs, counters)),
+                None => println!("Could not export stats for port {}", name),
+            }
+        }
+        PortStatsExporter { ports: exported }
+    }
+
+    /// Copy current packet counts to the shared counters.
+    pub fn update(&self) {
+        for &(ref port, rxqs, txqs, ref counters) in &self.ports {
+            let (mut rx, mut tx) = (0, 0);
+            for q in 0..rxqs {
+                let count = port.rx_stats(q as i32);
+                counters.set(2 + q, count as u64);
+                rx += count;
+            }
+            for q in 0..txqs {
+                let count = port.tx_stats(q as i32);
+                counters.set(2 + rxqs + q, count as u64);
+                tx += count;
+            }
+            counters.set(0, rx as u64);
+            counters.set(1, tx as u64);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use super::{open_shared, ContainerHeader, SharedMemory, CONTAINER_HEADER_SIZE};
+use fnv::FnvHasher;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU8, Ordering};
+use utils::round_to_pages;
+
+pub(crate) const SLOT_EMPTY: u8 = 0;
+pub(crate) const SLOT_VALID: u8 = 1;
+pub(crate) const SLOT_REMOVED: u8 = 2;
+
+/// Layout of an entry in a `SharedHashMap`, readers find the key and value using the offsets recorded in the
+/// `Directory`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct MapSlot<K: Copy, V: Copy> {
+    pub state: u8,
+    pub key: K,
+    pub value: V,
+}
+
+/// A fixed capacity hash map (open addressing with linear probing) stored in a shared memory segment, so that other
+/// processes can read it (see `Directory::register_map`). Keys and values are copied into shared memory, so they must
+/// be plain data, e.g., `Flow`s and counters. Readers in other processes always see inserted entries in full, but must
+/// use `DirectoryReader::read_snapshot` to get consistent values for entries that are replaced.
+pub struct SharedHashMap<K: Copy + Eq + Hash + 'static, V: Copy + 'static> {
+    shared: SharedMemory<ContainerHeader>,
+    slots: *mut MapSlot<K, V>,
+    mask: usize,
+    phantom: PhantomData<(K, V)>,
+}
+
+unsafe impl<K: Copy + Eq + Hash + Send + 'static, V: Copy + Send + 'static> Send for SharedHashMap<K, V> {}
+
+impl<K: Copy + Eq + Hash + 'static, V: Copy + 'static> SharedHashMap<K, V> {
+    /// Create a map with room for `capacity` (rounded up to a power of two) entries in the shared memory segment
+    /// `name`.
+    pub fn new_with_capacity(name: &str, capacity: usize) -> SharedHashMap<K, V> {
+        let slots = SharedHashMap::<K, V>::slots_for(capacity);
+        unsafe {
+            // The segment is zeroed, i.e., all slots are empty.
+            let shared: SharedMemory<ContainerHeader> =
+                open_shared(name, round_to_pages(SharedHashMap::<K, V>::segment_size(capacity)));
+            (*shared.mem).len.store(0, Ordering::Relaxed);
+            (*shared.mem).capacity = slots;
+            let data = (shared.mem as *mut u8).offset(CONTAINER_HEADER_SIZE as isize) as *mut MapSlot<K, V>;
+            SharedHashMap {
+                shared: shared,
+                slots: data,
+                mask: slots - 1,
+                phantom: PhantomData,
+            }
+        }
+    }
+
+    fn slots_for(capacity: usize) -> usize {
+        capacity.next_power_of_two()
+    }
+
+    pub(crate) fn segment_size(capacity: usize) -> usize {
+        CONTAINER_HEADER_SIZE + SharedHashMap::<K, V>::slots_for(capacity) * size_of::<MapSlot<K, V>>()
+    }
+
+    #[inline]
+    fn slot(&self, idx: usize) -> *mut MapSlot<K, V> {
+        unsafe { self.slots.offset(idx as isize) }
+    }
+
+    /// Set the state of slot `idx` with `Release`, so that readers that see the new state also see the key and value
+    /// written before it.
+    #[inline]
+    fn set_state(&self, idx: usize, state: u8) {
+        unsafe { (*(&(*self.slot(idx)).state as *const u8 as *const AtomicU8)).store(state, Ordering::Release) };
+    }
+
+    #[inline]
+    fn home(key: &K) -> usize {
+        let mut hasher = FnvHasher::default();
+        key.hash(&mut hasher);
+        hasher.finish() as usize
+    }
+
+    /// Index of the slot holding `key`, and of the first slot it could be inserted in.
+    fn find(&self, key: &K) -> (Option<usize>, Option<usize>) {
+        let home = SharedHashMap::<K, V>::home(key);
+        let mut free = None;
+        for i in 0..self.capacity() {
+            let idx = (home + i) & self.mask;
+            let slot = unsafe { &*self.slot(idx) };
+            match slot.state {
+                SLOT_EMPTY => return (None, free.or(Some(idx))),
+                SLOT_VALID if slot.key == *key => return (Some(idx), free),
+                SLOT_REMOVED if free.is_none() => free = Some(idx),
+                _ => {}
+            }
+        }
+        (None, free)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        unsafe { (*self.shared.mem).len.load(Ordering::Relaxed) }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.mask + 1
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.find(key)
+            .0
+            .map(|idx| unsafe { (*self.slot(idx)).value })
+    }
+
+    /// Insert or replace the value for `key`, returning false if the map is full.
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        match self.find(&key) {
+            (Some(idx), _) => unsafe { (*self.slot(idx)).value = value },
+            (None, Some(idx)) => {
+                unsafe {
+                    let slot = &mut *self.slot(idx);
+                    slot.key = key;
+                    slot.value = value;
+                }
+                // Publish the key and value before marking the slot valid and counting it.
+                self.set_state(idx, SLOT_VALID);
+                let len = self.len();
+                unsafe { (*self.shared.mem).len.store(len + 1, Ordering::Release) };
+            }
+            (None, None) => return false,
+        }
+        true
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.find(key).0.map(|idx| {
+            self.set_state(idx, SLOT_REMOVED);
+            let len = self.len();
+            unsafe {
+                (*self.shared.mem).len.store(len - 1, Ordering::Release);
+                (*self.slot(idx)).value
+            }
+        })
+    }
+
+    /// Remove all entries, e.g., before copying a new version of some state into the map.
+    pub fn clear(&mut self) {
+        for idx in 0..self.capacity() {
+            self.set_state(idx, SLOT_EMPTY);
+        }
+        unsafe { (*self.shared.mem).len.store(0, Ordering::Release) };
+    }
+
+    pub fn iter(&self) -> SharedHashMapIter<K, V> {
+        SharedHashMapIter { map: self, idx: 0 }
+    }
+}
+
+pub struct SharedHashMapIter<'a, K: Copy + Eq + Hash + 'static, V: Copy + 'static> {
+    map: &'a SharedHashMap<K, V>,
+    idx: usize,
+}
+
+impl<'a, K: Copy + Eq + Hash + 'static, V: Copy + 'static> Iterator for SharedHashMapIter<'a, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        while self.idx < self.map.capacity() {
+            let slot = unsafe { &*self.map.slot(self.idx) };
+            self.idx += 1;
+            if slot.state == SLOT_VALID {
+                return Some((slot.key, slot.value));
+            }
+        }
+        None
+    }
+}
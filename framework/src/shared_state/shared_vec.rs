@@ -1,75 +1,146 @@
-use super::{open_shared, SharedMemory};
+use super::{open_shared, ContainerHeader, SharedMemory, CONTAINER_HEADER_SIZE};
 use std::borrow::Borrow;
 use std::hash::{Hash, Hasher};
+use std::mem::size_of;
 use std::ops::{Index, IndexMut, Range, RangeFrom, RangeTo};
+use std::slice;
+use std::sync::atomic::Ordering;
 use utils::round_to_pages;
 
-#[allow(dead_code)] // FIXME: While WIP
-pub struct SharedVec<T: Sized + 'static> {
-    vec: Vec<T>,
-    shared: SharedMemory<T>,
-    modified: bool,
+/// A fixed capacity vector stored in a shared memory segment, so that other processes can read it (see
+/// `Directory::register_vec`). Elements are copied into shared memory, so they must be plain data. Readers in other
+/// processes always see pushed elements in full, but must use `DirectoryReader::read_snapshot` to get consistent
+/// values for elements that are updated in place.
+pub struct SharedVec<T: Copy + 'static> {
+    shared: SharedMemory<ContainerHeader>,
+    data: *mut T,
 }
 
-impl<T: Sized + 'static> SharedVec<T> {
+unsafe impl<T: Copy + Send + 'static> Send for SharedVec<T> {}
+
+impl<T: Copy + 'static> SharedVec<T> {
+    /// Create a vector with room for `capacity` elements in the shared memory segment `name`.
     pub fn new_with_capacity(name: &str, capacity: usize) -> SharedVec<T> {
-        let capacity_pages = round_to_pages(capacity);
+        let capacity_pages = round_to_pages(SharedVec::<T>::segment_size(capacity));
         unsafe {
+            let shared: SharedMemory<ContainerHeader> = open_shared(name, capacity_pages);
+            (*shared.mem).len.store(0, Ordering::Relaxed);
+            (*shared.mem).capacity = capacity;
+            let data = (shared.mem as *mut u8).offset(CONTAINER_HEADER_SIZE as isize) as *mut T;
             SharedVec {
-                vec: Vec::with_capacity(capacity),
-                shared: open_shared(name, capacity_pages),
-                modified: false,
+                shared: shared,
+                data: data,
+            }
+        }
+    }
+
+    pub(crate) fn segment_size(capacity: usize) -> usize {
+        CONTAINER_HEADER_SIZE + capacity * size_of::<T>()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        unsafe { (*self.shared.mem).len.load(Ordering::Relaxed) }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        unsafe { (*self.shared.mem).capacity }
+    }
+
+    /// Append `value`, returning false if the vector is full.
+    #[inline]
+    pub fn push(&mut self, value: T) -> bool {
+        let len = self.len();
+        if len == self.capacity() {
+            return false;
+        }
+        unsafe {
+            *self.data.offset(len as isize) = value;
+            // Publish the element before counting it.
+            (*self.shared.mem).len.store(len + 1, Ordering::Release);
+        }
+        true
+    }
+
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        let len = self.len();
+        if len == 0 {
+            None
+        } else {
+            unsafe {
+                (*self.shared.mem).len.store(len - 1, Ordering::Release);
+                Some(*self.data.offset(len as isize - 1))
             }
         }
     }
+
+    pub fn clear(&mut self) {
+        unsafe { (*self.shared.mem).len.store(0, Ordering::Release) };
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.data, self.len()) }
+    }
+
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.data, self.len()) }
+    }
 }
 
-impl<T: Sized + 'static> Borrow<[T]> for SharedVec<T> {
+impl<T: Copy + 'static> Borrow<[T]> for SharedVec<T> {
     fn borrow(&self) -> &[T] {
-        self.vec.borrow()
+        self.as_slice()
     }
 }
 
-impl<T: Sized + Hash + 'static> Hash for SharedVec<T> {
+impl<T: Copy + Hash + 'static> Hash for SharedVec<T> {
     fn hash<H>(&self, state: &mut H)
     where
         H: Hasher,
     {
-        self.vec.hash(state)
+        self.as_slice().hash(state)
     }
 }
 
-impl<T: Sized + 'static> Index<usize> for SharedVec<T> {
+impl<T: Copy + 'static> Index<usize> for SharedVec<T> {
     type Output = T;
     fn index(&self, index: usize) -> &T {
-        self.vec.index(index)
+        self.as_slice().index(index)
     }
 }
 
-impl<T: Sized + 'static> Index<Range<usize>> for SharedVec<T> {
+impl<T: Copy + 'static> Index<Range<usize>> for SharedVec<T> {
     type Output = [T];
     fn index(&self, index: Range<usize>) -> &[T] {
-        self.vec.index(index)
+        self.as_slice().index(index)
     }
 }
 
-impl<T: Sized + 'static> Index<RangeTo<usize>> for SharedVec<T> {
+impl<T: Copy + 'static> Index<RangeTo<usize>> for SharedVec<T> {
     type Output = [T];
     fn index(&self, index: RangeTo<usize>) -> &[T] {
-        self.vec.index(index)
+        self.as_slice().index(index)
     }
 }
 
-impl<T: Sized + 'static> Index<RangeFrom<usize>> for SharedVec<T> {
+impl<T: Copy + 'static> Index<RangeFrom<usize>> for SharedVec<T> {
     type Output = [T];
     fn index(&self, index: RangeFrom<usize>) -> &[T] {
-        self.vec.index(index)
+        self.as_slice().index(index)
     }
 }
 
-impl<T: Sized + 'static> IndexMut<usize> for SharedVec<T> {
+impl<T: Copy + 'static> IndexMut<usize> for SharedVec<T> {
     fn index_mut(&mut self, index: usize) -> &mut T {
-        self.modified = true;
-        self.vec.index_mut(index)
+        self.as_mut_slice().index_mut(index)
     }
 }
@@ -0,0 +1,333 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use common::*;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hash};
+use std::io::{Cursor, Read, Write};
+use std::sync::{Arc, Mutex, RwLock};
+use utils::Flow;
+
+/// Identifies a stream written by `write_checkpoint`.
+const CHECKPOINT_MAGIC: u32 = 0x4e42_4350;
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// State that can be written to a byte stream and restored into another instance, e.g., to upgrade or move an NF
+/// without dropping connections (see `NetBricksContext::checkpoint`). `restore` replaces the current contents, and
+/// expects an instance configured like the one that was checkpointed (e.g., with the same sketch dimensions or buffer
+/// sizes); a mismatch is reported as `ErrorKind::BadCheckpoint`. Integers are written in little-endian order.
+///
+/// State that is shared between the control plane and pipelines should be checkpointed while pipelines are paused,
+/// which is what `NetBricksContext::checkpoint` does.
+pub trait Checkpoint {
+    /// Write the state to `out`.
+    fn checkpoint(&self, out: &mut Write) -> Result<()>;
+
+    /// Replace the state with the one read from `input`, as written by `checkpoint`.
+    fn restore(&mut self, input: &mut Read) -> Result<()>;
+}
+
+/// Error for a checkpoint that does not match the state it is restored into.
+pub fn bad_checkpoint<T>(description: String) -> Result<T> {
+    Err(ErrorKind::BadCheckpoint(description).into())
+}
+
+/// Check that a size or setting read from a checkpoint matches the one of the instance being restored.
+pub fn expect_checkpoint(what: &str, found: usize, expected: usize) -> Result<()> {
+    if found == expected {
+        Ok(())
+    } else {
+        bad_checkpoint(format!("{} is {}, expected {}", what, found, expected))
+    }
+}
+
+pub fn write_len(out: &mut Write, len: usize) -> Result<()> {
+    try!(out.write_u64::<LittleEndian>(len as u64));
+    Ok(())
+}
+
+pub fn read_len(input: &mut Read) -> Result<usize> {
+    Ok(try!(input.read_u64::<LittleEndian>()) as usize)
+}
+
+/// Read a value written by `Checkpoint::checkpoint`.
+pub fn restore_value<T: Checkpoint + Default>(input: &mut Read) -> Result<T> {
+    let mut value: T = Default::default();
+    try!(value.restore(input));
+    Ok(value)
+}
+
+/// Write a fixed size array of values, e.g., the counters of a sketch.
+pub fn checkpoint_slice<T: Checkpoint>(values: &[T], out: &mut Write) -> Result<()> {
+    try!(write_len(out, values.len()));
+    for value in values {
+        try!(value.checkpoint(out));
+    }
+    Ok(())
+}
+
+/// Restore an array written by `checkpoint_slice`, which must have the same length as `values`.
+pub fn restore_slice<T: Checkpoint>(values: &mut [T], input: &mut Read) -> Result<()> {
+    try!(expect_checkpoint("array length", try!(read_len(input)), values.len()));
+    for value in values.iter_mut() {
+        try!(value.restore(input));
+    }
+    Ok(())
+}
+
+/// Write each of `state` to `out`, along with its length so that restoring can check that every piece of state is
+/// consumed entirely.
+pub fn write_checkpoint(state: &[&Checkpoint], out: &mut Write) -> Result<()> {
+    try!(out.write_u32::<LittleEndian>(CHECKPOINT_MAGIC));
+    try!(out.write_u32::<LittleEndian>(CHECKPOINT_VERSION));
+    try!(write_len(out, state.len()));
+    let mut buffer = Vec::new();
+    for s in state {
+        buffer.clear();
+        try!(s.checkpoint(&mut buffer));
+        try!(write_len(out, buffer.len()));
+        try!(out.write_all(&buffer));
+    }
+    Ok(())
+}
+
+/// Restore each of `state` (in the order passed to `write_checkpoint`) from `input`.
+pub fn read_checkpoint(state: &mut [&mut Checkpoint], input: &mut Read) -> Result<()> {
+    if try!(input.read_u32::<LittleEndian>()) != CHECKPOINT_MAGIC {
+        return bad_checkpoint(String::from("not a checkpoint"));
+    }
+    try!(expect_checkpoint(
+        "version",
+        try!(input.read_u32::<LittleEndian>()) as usize,
+        CHECKPOINT_VERSION as usize
+    ));
+    try!(expect_checkpoint("number of states", try!(read_len(input)), state.len()));
+    for (i, s) in state.iter_mut().enumerate() {
+        let len = try!(read_len(input));
+        let mut buffer = Vec::new();
+        try!((&mut *input).take(len as u64).read_to_end(&mut buffer));
+        try!(expect_checkpoint("checkpoint length", buffer.len(), len));
+        let mut cursor = Cursor::new(&buffer[..]);
+        try!(s.restore(&mut cursor));
+        if cursor.position() as usize != len {
+            return bad_checkpoint(format!("state {} did not read its entire checkpoint", i));
+        }
+    }
+    Ok(())
+}
+
+impl Checkpoint for u8 {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        try!(out.write_u8(*self));
+        Ok(())
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        *self = try!(input.read_u8());
+        Ok(())
+    }
+}
+
+impl Checkpoint for bool {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        (*self as u8).checkpoint(out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        *self = try!(input.read_u8()) != 0;
+        Ok(())
+    }
+}
+
+macro_rules! checkpoint_integer {
+    ($t: ty, $write: ident, $read: ident) => {
+        impl Checkpoint for $t {
+            fn checkpoint(&self, out: &mut Write) -> Result<()> {
+                try!(out.$write::<LittleEndian>(*self));
+                Ok(())
+            }
+
+            fn restore(&mut self, input: &mut Read) -> Result<()> {
+                *self = try!(input.$read::<LittleEndian>());
+                Ok(())
+            }
+        }
+    }
+}
+
+checkpoint_integer!(u16, write_u16, read_u16);
+checkpoint_integer!(u32, write_u32, read_u32);
+checkpoint_integer!(u64, write_u64, read_u64);
+checkpoint_integer!(i16, write_i16, read_i16);
+checkpoint_integer!(i32, write_i32, read_i32);
+checkpoint_integer!(i64, write_i64, read_i64);
+checkpoint_integer!(f64, write_f64, read_f64);
+
+impl Checkpoint for usize {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        write_len(out, *self)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        *self = try!(read_len(input));
+        Ok(())
+    }
+}
+
+impl Checkpoint for Flow {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        // Copy fields out of the packed struct rather than borrowing them.
+        let (src_ip, dst_ip, src_port, dst_port, proto) =
+            (self.src_ip, self.dst_ip, self.src_port, self.dst_port, self.proto);
+        try!(src_ip.checkpoint(out));
+        try!(dst_ip.checkpoint(out));
+        try!(src_port.checkpoint(out));
+        try!(dst_port.checkpoint(out));
+        proto.checkpoint(out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        *self = Flow {
+            src_ip: try!(restore_value(input)),
+            dst_ip: try!(restore_value(input)),
+            src_port: try!(restore_value(input)),
+            dst_port: try!(restore_value(input)),
+            proto: try!(restore_value(input)),
+        };
+        Ok(())
+    }
+}
+
+impl<T: Checkpoint + Default> Checkpoint for Option<T> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        match *self {
+            Some(ref value) => {
+                try!(true.checkpoint(out));
+                value.checkpoint(out)
+            }
+            None => false.checkpoint(out),
+        }
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        *self = if try!(restore_value::<bool>(input)) {
+            Some(try!(restore_value(input)))
+        } else {
+            None
+        };
+        Ok(())
+    }
+}
+
+impl<A: Checkpoint, B: Checkpoint> Checkpoint for (A, B) {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        try!(self.0.checkpoint(out));
+        self.1.checkpoint(out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        try!(self.0.restore(input));
+        self.1.restore(input)
+    }
+}
+
+impl<T: Checkpoint + Default> Checkpoint for Vec<T> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        try!(write_len(out, self.len()));
+        for value in self {
+            try!(value.checkpoint(out));
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        let len = try!(read_len(input));
+        self.clear();
+        // Do not reserve space based on the (unchecked) length.
+        for _ in 0..len {
+            self.push(try!(restore_value(input)));
+        }
+        Ok(())
+    }
+}
+
+/// Maps are restored with the hasher they are created with, e.g., a NAT's `HashMap<Flow, Flow, FnvHash>`.
+impl<K, V, S> Checkpoint for HashMap<K, V, S>
+where
+    K: Checkpoint + Default + Hash + Eq,
+    V: Checkpoint + Default,
+    S: BuildHasher,
+{
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        try!(write_len(out, self.len()));
+        for (key, value) in self {
+            try!(key.checkpoint(out));
+            try!(value.checkpoint(out));
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        let len = try!(read_len(input));
+        self.clear();
+        for _ in 0..len {
+            let key = try!(restore_value(input));
+            self.insert(key, try!(restore_value(input)));
+        }
+        Ok(())
+    }
+}
+
+impl<T, S> Checkpoint for HashSet<T, S>
+where
+    T: Checkpoint + Default + Hash + Eq,
+    S: BuildHasher,
+{
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        try!(write_len(out, self.len()));
+        for value in self {
+            try!(value.checkpoint(out));
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        let len = try!(read_len(input));
+        self.clear();
+        for _ in 0..len {
+            self.insert(try!(restore_value(input)));
+        }
+        Ok(())
+    }
+}
+
+/// State shared with pipelines through a lock is checkpointed while holding the lock.
+impl<T: Checkpoint> Checkpoint for Arc<RwLock<T>> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        match self.read() {
+            Ok(g) => g.checkpoint(out),
+            Err(_) => bad_checkpoint(String::from("lock poisoned")),
+        }
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        match self.write() {
+            Ok(mut g) => g.restore(input),
+            Err(_) => bad_checkpoint(String::from("lock poisoned")),
+        }
+    }
+}
+
+impl<T: Checkpoint> Checkpoint for Arc<Mutex<T>> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        match self.lock() {
+            Ok(g) => g.checkpoint(out),
+            Err(_) => bad_checkpoint(String::from("lock poisoned")),
+        }
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        match self.lock() {
+            Ok(mut g) => g.restore(input),
+            Err(_) => bad_checkpoint(String::from("lock poisoned")),
+        }
+    }
+}
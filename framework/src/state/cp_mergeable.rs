@@ -1,6 +1,10 @@
+use super::checkpoint::Checkpoint;
+use super::expiry::{checkpoint_aged, merge_aged, restore_aged, AgedFlows, ExpiryPolicy, FlowExpiry, FlowValues};
+use common::*;
+use std::cmp::min;
 use std::collections::HashMap;
-use std::collections::hash_map::Iter;
 use std::hash::BuildHasherDefault;
+use std::io::{Read, Write};
 use std::ops::AddAssign;
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
 use twox_hash::XxHash;
@@ -13,10 +17,8 @@ const VEC_SIZE: usize = 1 << 24;
 /// guarantee ordering for things being merged. The merge function is implemented by implementing the
 /// [`AddAssign`](https://doc.rust-lang.org/std/ops/trait.AddAssign.html) trait and overriding the `add_assign` method
 /// there. We assume that the stored quantity needs to only be accessed from the control plane, and cannot be accessed
-/// from the data plane.
-///
-/// #[FIXME]
-/// Garbage collection.
+/// from the data plane. The data path only buffers updates, flows are expired (see `ExpiryPolicy`) by the control
+/// plane.
 #[derive(Clone)]
 pub struct CpMergeableStoreDataPath<T: AddAssign<T> + Default + Clone> {
     /// Contains the counts on the data path.
@@ -29,9 +31,10 @@ pub struct CpMergeableStoreDataPath<T: AddAssign<T> + Default + Clone> {
 }
 
 pub struct CpMergeableStoreControlPlane<T: AddAssign<T> + Default + Clone> {
-    /// The actual values.
-    flow_counters: HashMap<Flow, T, XxHasher>,
+    /// The actual values, along with when they were last updated.
+    flow_counters: AgedFlows<T, XxHasher>,
     channel: Receiver<Vec<(Flow, T)>>,
+    expiry: FlowExpiry,
 }
 
 impl<T: AddAssign<T> + Default + Clone> CpMergeableStoreDataPath<T> {
@@ -54,10 +57,19 @@ impl<T: AddAssign<T> + Default + Clone> CpMergeableStoreDataPath<T> {
 
 impl<T: AddAssign<T> + Default + Clone> CpMergeableStoreControlPlane<T> {
     fn update_internal(&mut self, v: Vec<(Flow, T)>) {
+        let now = self.expiry.now();
         for (flow, c) in v {
-            *(self.flow_counters
-                .entry(flow)
-                .or_insert_with(Default::default)) += c;
+            merge_aged(&mut self.flow_counters, flow, c, now);
+        }
+        self.expiry.expire(&mut self.flow_counters, now);
+    }
+
+    /// Remove flows that have expired under the store's `ExpiryPolicy`. This happens as updates are received, call
+    /// this periodically to also expire flows when there are few updates.
+    pub fn expire(&mut self) {
+        if self.expiry.is_enabled() {
+            let now = self.expiry.now();
+            self.expiry.expire(&mut self.flow_counters, now);
         }
     }
 
@@ -71,13 +83,13 @@ impl<T: AddAssign<T> + Default + Clone> CpMergeableStoreControlPlane<T> {
 
     pub fn get(&self, flow: &Flow) -> T {
         match self.flow_counters.get(flow) {
-            Some(i) => i.clone(),
+            Some(&(ref i, _)) => i.clone(),
             None => Default::default(),
         }
     }
 
-    pub fn iter(&self) -> Iter<Flow, T> {
-        self.flow_counters.iter()
+    pub fn iter(&self) -> FlowValues<T> {
+        FlowValues::new(self.flow_counters.iter())
     }
 
     pub fn len(&self) -> usize {
@@ -93,7 +105,20 @@ impl<T: AddAssign<T> + Default + Clone> CpMergeableStoreControlPlane<T> {
     pub fn remove(&mut self, flow: &Flow) -> T {
         self.flow_counters
             .remove(flow)
-            .unwrap_or_else(Default::default)
+            .map_or_else(Default::default, |(v, _)| v)
+    }
+}
+
+/// Updates still buffered by the data path (or in the channel) are not included, call `recv` (with pipelines paused)
+/// before checkpointing. Restored flows are treated as just updated by the store's `ExpiryPolicy`.
+impl<T: AddAssign<T> + Default + Clone + Checkpoint> Checkpoint for CpMergeableStoreControlPlane<T> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        checkpoint_aged(&self.flow_counters, out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        let now = self.expiry.now();
+        restore_aged(&mut self.flow_counters, input, now)
     }
 }
 
@@ -105,8 +130,21 @@ pub fn new_cp_mergeable_store<T: AddAssign<T> + Default + Clone>(
 ) -> (
     CpMergeableStoreDataPath<T>,
     Box<CpMergeableStoreControlPlane<T>>,
+) {
+    new_cp_mergeable_store_with_expiry(delay, channel_size, Default::default())
+}
+
+/// Create a `CpMergeableStore` whose control plane removes flows according to `expiry`.
+pub fn new_cp_mergeable_store_with_expiry<T: AddAssign<T> + Default + Clone>(
+    delay: usize,
+    channel_size: usize,
+    expiry: ExpiryPolicy,
+) -> (
+    CpMergeableStoreDataPath<T>,
+    Box<CpMergeableStoreControlPlane<T>>,
 ) {
     let (sender, receiver) = sync_channel(channel_size);
+    let capacity = expiry.max_flows.map_or(VEC_SIZE, |max| min(max, VEC_SIZE));
     (
         CpMergeableStoreDataPath {
             cache: Vec::with_capacity(delay),
@@ -116,8 +154,9 @@ pub fn new_cp_mergeable_store<T: AddAssign<T> + Default + Clone>(
         },
         box CpMergeableStoreControlPlane {
             // FIXME: Don't need this to be quite this big?
-            flow_counters: HashMap::with_capacity_and_hasher(VEC_SIZE, Default::default()),
+            flow_counters: HashMap::with_capacity_and_hasher(capacity, Default::default()),
             channel: receiver,
+            expiry: FlowExpiry::new(expiry),
         },
     )
 }
@@ -1,7 +1,10 @@
+use super::checkpoint::{write_len, Checkpoint};
+use super::expiry::{restore_aged, AgedFlows, ExpiryPolicy, FlowExpiry, FlowValues};
+use common::*;
 use fnv::FnvHasher;
 use std::collections::HashMap;
-use std::collections::hash_map::Iter;
 use std::hash::BuildHasherDefault;
+use std::io::{Read, Write};
 use std::ops::AddAssign;
 use utils::Flow;
 
@@ -10,27 +13,31 @@ use utils::Flow;
 /// [`AddAssign`](https://doc.rust-lang.org/std/ops/trait.AddAssign.html) trait and overriding the `add_assign` method
 /// there. We assume that the quantity stored here does not need to be accessed by the control plane and can only be
 /// accessed from the data plane. The `cache_size` should be tuned depending on whether gets or puts are the most common
-/// operation in this table.
-///
-/// #[FIXME]
-/// Garbage collection.
+/// operation in this table. Flows are kept forever unless the store is created with an `ExpiryPolicy`.
 type FnvHash = BuildHasherDefault<FnvHasher>;
 const VEC_SIZE: usize = 1 << 24;
 #[derive(Clone)]
 pub struct DpMergeableStore<T: AddAssign<T> + Default> {
-    /// Contains the counts on the data path.
-    state: HashMap<Flow, T, FnvHash>,
+    /// Contains the counts on the data path, along with when they were last updated.
+    state: AgedFlows<T, FnvHash>,
     cache: Vec<(Flow, T)>,
     cache_size: usize,
+    expiry: FlowExpiry,
 }
 
 const CACHE_SIZE: usize = 1 << 14;
 impl<T: AddAssign<T> + Default> DpMergeableStore<T> {
     pub fn with_cache_and_size(cache: usize, size: usize) -> DpMergeableStore<T> {
+        DpMergeableStore::with_expiry(cache, size, Default::default())
+    }
+
+    /// Create a store that removes flows according to `expiry`.
+    pub fn with_expiry(cache: usize, size: usize, expiry: ExpiryPolicy) -> DpMergeableStore<T> {
         DpMergeableStore {
             state: HashMap::with_capacity_and_hasher(size, Default::default()),
             cache: Vec::with_capacity(cache),
             cache_size: cache,
+            expiry: FlowExpiry::new(expiry),
         }
     }
 
@@ -39,7 +46,21 @@ impl<T: AddAssign<T> + Default> DpMergeableStore<T> {
     }
 
     fn merge_cache(&mut self) {
-        self.state.extend(self.cache.drain(0..));
+        if !self.cache.is_empty() {
+            let now = self.expiry.now();
+            self.state
+                .extend(self.cache.drain(0..).map(|(f, v)| (f, (v, now))));
+        }
+    }
+
+    /// Remove flows that have expired under the store's `ExpiryPolicy`. This happens as updates are merged, call this
+    /// periodically to also expire flows when there are few updates.
+    pub fn expire(&mut self) {
+        self.merge_cache();
+        if self.expiry.is_enabled() {
+            let now = self.expiry.now();
+            self.expiry.expire(&mut self.state, now);
+        }
     }
 
     /// Change the value for the given `Flow`.
@@ -49,7 +70,7 @@ impl<T: AddAssign<T> + Default> DpMergeableStore<T> {
             self.cache.push((flow, inc));
         }
         if self.cache.len() >= self.cache_size {
-            self.merge_cache();
+            self.expire();
         }
     }
 
@@ -57,16 +78,18 @@ impl<T: AddAssign<T> + Default> DpMergeableStore<T> {
     #[inline]
     pub fn remove(&mut self, flow: &Flow) -> T {
         self.merge_cache();
-        self.state.remove(flow).unwrap_or_else(Default::default)
+        self.state
+            .remove(flow)
+            .map_or_else(Default::default, |(v, _)| v)
     }
 
     /// Iterate over all the stored entries. This is a bit weird to do in the data plane.
     ///
     /// #[Warning]
     /// This might have severe performance penalties.
-    pub fn iter(&mut self) -> Iter<Flow, T> {
+    pub fn iter(&mut self) -> FlowValues<T> {
         self.merge_cache();
-        self.state.iter()
+        FlowValues::new(self.state.iter())
     }
 
     /// Length of the table.
@@ -80,3 +103,23 @@ impl<T: AddAssign<T> + Default> DpMergeableStore<T> {
         self.state.is_empty() && self.cache.is_empty()
     }
 }
+
+/// Restored flows are treated as just updated by the store's `ExpiryPolicy`.
+impl<T: AddAssign<T> + Default + Checkpoint> Checkpoint for DpMergeableStore<T> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        // Cached updates replace stored values when merged, so write them last.
+        try!(write_len(out, self.state.len() + self.cache.len()));
+        let stored = self.state.iter().map(|(f, &(ref v, _))| (f, v));
+        for (flow, value) in stored.chain(self.cache.iter().map(|&(ref f, ref v)| (f, v))) {
+            try!(flow.checkpoint(out));
+            try!(value.checkpoint(out));
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        self.cache.clear();
+        let now = self.expiry.now();
+        restore_aged(&mut self.state, input, now)
+    }
+}
@@ -0,0 +1,211 @@
+use super::checkpoint::{read_len, restore_value, write_len, Checkpoint};
+use common::*;
+use std::collections::HashMap;
+use std::collections::hash_map::Iter;
+use std::hash::BuildHasher;
+use std::io::{Read, Write};
+use std::ops::AddAssign;
+use std::time::Duration;
+use utils::{tsc_now_ns, Flow};
+
+/// When over `max_flows`, evict this fraction of `max_flows` beyond the cap, so that the (expensive) search for the
+/// least recently updated flows is not repeated on every update.
+const EVICTION_SLACK: usize = 8;
+/// Scan for expired flows this many times per TTL.
+const SCANS_PER_TTL: u64 = 4;
+
+/// Limits on the flows kept by a mergeable store. Flows that have not been updated for `ttl` expire, and when a store
+/// holds more than `max_flows` flows the least recently updated are evicted (slightly more than necessary, so stores
+/// hover a little below the cap). Both limits are checked as updates are merged and when `expire` is called on the
+/// store, so expiry is approximate: flows live for up to 1.25 `ttl`. The default policy keeps flows forever. Update
+/// times are read from `tsc_now_ns` unless another clock is set with `with_clock`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExpiryPolicy {
+    pub ttl: Option<Duration>,
+    pub max_flows: Option<usize>,
+    clock: Option<fn() -> u64>,
+}
+
+impl ExpiryPolicy {
+    /// Expire flows that have not been updated for `ttl`.
+    pub fn with_ttl(ttl: Duration) -> ExpiryPolicy {
+        ExpiryPolicy {
+            ttl: Some(ttl),
+            ..Default::default()
+        }
+    }
+
+    /// Keep at most `max_flows` flows, evicting the least recently updated.
+    pub fn with_max_flows(max_flows: usize) -> ExpiryPolicy {
+        ExpiryPolicy {
+            max_flows: Some(max_flows),
+            ..Default::default()
+        }
+    }
+
+    /// Read update times (in ns) from `clock` rather than `tsc_now_ns`, e.g., to control time in tests.
+    pub fn with_clock(self, clock: fn() -> u64) -> ExpiryPolicy {
+        ExpiryPolicy {
+            clock: Some(clock),
+            ..self
+        }
+    }
+
+    /// Current time (in ns) according to this policy's clock.
+    #[inline]
+    pub(crate) fn now(&self) -> u64 {
+        match self.clock {
+            Some(clock) => clock(),
+            None => tsc_now_ns(),
+        }
+    }
+
+    /// Does this policy ever remove flows?
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.ttl.is_some() || self.max_flows.is_some()
+    }
+
+    fn ttl_ns(&self) -> Option<u64> {
+        self.ttl
+            .map(|ttl| ttl.as_secs() * 1_000_000_000 + ttl.subsec_nanos() as u64)
+    }
+}
+
+/// Flows along with their value and the time (see `ExpiryPolicy::now`) at which they were last updated.
+pub(crate) type AgedFlows<T, S> = HashMap<Flow, (T, u64), S>;
+
+/// Applies an `ExpiryPolicy` to a store's `AgedFlows`.
+#[derive(Clone)]
+pub(crate) struct FlowExpiry {
+    policy: ExpiryPolicy,
+    next_scan: u64,
+}
+
+impl FlowExpiry {
+    pub fn new(policy: ExpiryPolicy) -> FlowExpiry {
+        FlowExpiry {
+            policy: policy,
+            next_scan: 0,
+        }
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.policy.is_enabled()
+    }
+
+    #[inline]
+    pub fn now(&self) -> u64 {
+        self.policy.now()
+    }
+
+    /// Remove the flows that should be removed at `now`, returning them along with when they were last updated.
+    pub fn expire<T, S: BuildHasher>(&mut self, flows: &mut AgedFlows<T, S>, now: u64) -> Vec<(Flow, u64)> {
+        if !self.is_enabled() {
+            return vec![];
+        }
+        let ttl = self.policy.ttl_ns();
+        let over_cap = self.policy.max_flows.map_or(false, |max| flows.len() > max);
+        let scan = ttl.is_some() && now >= self.next_scan;
+        if !over_cap && !scan {
+            return vec![];
+        }
+        let cutoff = match ttl {
+            Some(ttl) => {
+                self.next_scan = now + ttl / SCANS_PER_TTL;
+                now.saturating_sub(ttl)
+            }
+            None => 0,
+        };
+        let mut expired: Vec<_> = flows
+            .iter()
+            .filter(|&(_, &(_, t))| t < cutoff)
+            .map(|(f, &(_, t))| (*f, t))
+            .collect();
+        if let Some(max) = self.policy.max_flows {
+            let remaining = flows.len() - expired.len();
+            if remaining > max {
+                let mut by_age: Vec<_> = flows
+                    .iter()
+                    .filter(|&(_, &(_, t))| t >= cutoff)
+                    .map(|(f, &(_, t))| (t, *f))
+                    .collect();
+                by_age.sort();
+                let evict = remaining - (max - max / EVICTION_SLACK);
+                expired.extend(by_age.into_iter().take(evict).map(|(t, f)| (f, t)));
+            }
+        }
+        for &(ref flow, _) in &expired {
+            flows.remove(flow);
+        }
+        expired
+    }
+}
+
+/// Add `inc` to `flow`'s value, recording that it was updated at `now` unless it is already known to have been
+/// updated later.
+#[inline]
+pub(crate) fn merge_aged<T: AddAssign<T> + Default, S: BuildHasher>(
+    flows: &mut AgedFlows<T, S>,
+    flow: Flow,
+    inc: T,
+    now: u64,
+) {
+    let entry = flows.entry(flow).or_insert_with(|| (Default::default(), now));
+    entry.0 += inc;
+    if entry.1 < now {
+        entry.1 = now;
+    }
+}
+
+/// Write the values (but not update times, which are meaningless in another process) of `flows`, in the same format
+/// as a `HashMap<Flow, T>`.
+pub(crate) fn checkpoint_aged<T: Checkpoint, S: BuildHasher>(flows: &AgedFlows<T, S>, out: &mut Write) -> Result<()> {
+    try!(write_len(out, flows.len()));
+    for (flow, &(ref value, _)) in flows {
+        try!(flow.checkpoint(out));
+        try!(value.checkpoint(out));
+    }
+    Ok(())
+}
+
+/// Replace `flows` with those written by `checkpoint_aged`, treating them as updated at `now`.
+pub(crate) fn restore_aged<T: Checkpoint + Default, S: BuildHasher>(
+    flows: &mut AgedFlows<T, S>,
+    input: &mut Read,
+    now: u64,
+) -> Result<()> {
+    let len = try!(read_len(input));
+    flows.clear();
+    for _ in 0..len {
+        let flow: Flow = try!(restore_value(input));
+        flows.insert(flow, (try!(restore_value(input)), now));
+    }
+    Ok(())
+}
+
+/// Iterator over the flows and values of a store, see `DpMergeableStore::iter`.
+pub struct FlowValues<'a, T: 'a> {
+    inner: Iter<'a, Flow, (T, u64)>,
+}
+
+impl<'a, T> FlowValues<'a, T> {
+    pub(crate) fn new(inner: Iter<'a, Flow, (T, u64)>) -> FlowValues<'a, T> {
+        FlowValues { inner: inner }
+    }
+}
+
+impl<'a, T> Iterator for FlowValues<'a, T> {
+    type Item = (&'a Flow, &'a T);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a Flow, &'a T)> {
+        self.inner.next().map(|(f, &(ref v, _))| (f, v))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
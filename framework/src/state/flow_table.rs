@@ -0,0 +1,342 @@
+use super::checkpoint::{bad_checkpoint, read_len, restore_value, write_len, Checkpoint};
+use allocators::{allocate_cache_line, free_cache_line, CacheAligned};
+use common::*;
+use std::cell::UnsafeCell;
+use std::io::{Read, Write};
+use std::mem::size_of;
+use std::ptr;
+use std::sync::atomic::{fence, spin_loop_hint, AtomicBool, AtomicUsize, Ordering};
+use utils::{crc_hash, Flow};
+
+/// Number of slots, starting at a flow's home slot, that may hold the flow.
+const MAX_PROBE: usize = 32;
+const LOCK_STRIPES: usize = 64;
+
+const EMPTY: u8 = 0;
+const VALID: u8 = 1;
+const REMOVED: u8 = 2;
+
+#[derive(Clone, Copy)]
+struct SlotData<V: Copy> {
+    state: u8,
+    key: Flow,
+    value: V,
+}
+
+/// A slot is protected by a sequence lock: `seq` is odd while the slot is being written, and readers retry if it
+/// changed while they read the slot.
+struct Slot<V: Copy> {
+    seq: AtomicUsize,
+    data: UnsafeCell<SlotData<V>>,
+}
+
+impl<V: Copy> Slot<V> {
+    /// A consistent snapshot of the slot, along with its sequence number.
+    #[inline]
+    fn read(&self) -> (usize, SlotData<V>) {
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq & 1 == 0 {
+                // This may race with a writer, in which case the sequence number changes and the copy is discarded.
+                let data = unsafe { ptr::read_volatile(self.data.get()) };
+                fence(Ordering::Acquire);
+                if self.seq.load(Ordering::Relaxed) == seq {
+                    return (seq, data);
+                }
+            }
+            spin_loop_hint();
+        }
+    }
+
+    /// Write `data`, provided the slot has not changed since it was read at `seq`.
+    #[inline]
+    fn write_if_unchanged(&self, seq: usize, data: SlotData<V>) -> bool {
+        if self.seq
+            .compare_exchange(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        // Readers that see the new data must also see the odd sequence number.
+        fence(Ordering::Release);
+        unsafe { ptr::write_volatile(self.data.get(), data) };
+        self.seq.store(seq + 2, Ordering::Release);
+        true
+    }
+}
+
+/// Where a flow is, or could be inserted.
+struct Probe<V> {
+    found: Option<(usize, usize, V)>,
+    free: Option<(usize, usize)>,
+}
+
+/// Releases a lock stripe when dropped.
+struct StripeGuard<'a>(&'a AtomicBool);
+
+impl<'a> Drop for StripeGuard<'a> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// A fixed size hash table keyed on `Flow` that can be shared between cores, e.g., so that the cores serving both
+/// directions of a connection see the same NAT or load balancer state. All slots are allocated (cache line aligned)
+/// when the table is created, and a flow is stored within 32 slots of its home slot (linear probing), so inserts fail
+/// when that neighbourhood is full.
+///
+/// The table is not lock-free. Lookups take no locks and do not write to the table, but retry a slot while it is being
+/// written, so a lookup waits for a writer that is descheduled mid-write. Writes to a flow (inserts, updates and
+/// removals) take a spin lock striped by the flow's home slot, so writes to different flows rarely contend but a
+/// writer can wait for another one holding the same stripe. Pipelines sharing a table should therefore run on
+/// dedicated cores. Values are copied in and out of the table, so they must be `Copy`. Share the table between
+/// pipelines using an `Arc`. The table is in process memory, not in a `shared_state` segment, so it cannot be read by
+/// `nb-inspect`; copy flows into a `SharedHashMap` to export them.
+pub struct FlowTable<V: Copy> {
+    slots: *mut Slot<V>,
+    mask: usize,
+    len: AtomicUsize,
+    locks: Vec<CacheAligned<AtomicBool>>,
+}
+
+unsafe impl<V: Copy + Send> Send for FlowTable<V> {}
+unsafe impl<V: Copy + Send> Sync for FlowTable<V> {}
+
+impl<V: Copy + Default> FlowTable<V> {
+    /// Create a table that can hold at least `capacity` flows. Slots are sized so the table is at most 3/4 full.
+    pub fn with_capacity(capacity: usize) -> FlowTable<V> {
+        let len = (capacity + capacity / 3).next_power_of_two();
+        let slots = unsafe { allocate_cache_line(len * size_of::<Slot<V>>()) as *mut Slot<V> };
+        for i in 0..len {
+            unsafe {
+                ptr::write(
+                    slots.offset(i as isize),
+                    Slot {
+                        seq: AtomicUsize::new(0),
+                        data: UnsafeCell::new(SlotData {
+                            state: EMPTY,
+                            key: Default::default(),
+                            value: Default::default(),
+                        }),
+                    },
+                )
+            };
+        }
+        FlowTable {
+            slots: slots,
+            mask: len - 1,
+            len: AtomicUsize::new(0),
+            locks: (0..LOCK_STRIPES)
+                .map(|_| CacheAligned::allocate(AtomicBool::new(false)))
+                .collect(),
+        }
+    }
+}
+
+impl<V: Copy> FlowTable<V> {
+    #[inline]
+    fn slot(&self, idx: usize) -> &Slot<V> {
+        unsafe { &*self.slots.offset(idx as isize) }
+    }
+
+    #[inline]
+    fn home(&self, flow: &Flow) -> usize {
+        crc_hash(flow, 0) as usize & self.mask
+    }
+
+    #[inline]
+    fn lock(&self, home: usize) -> StripeGuard {
+        let lock: &AtomicBool = &self.locks[home & (LOCK_STRIPES - 1)];
+        while lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            spin_loop_hint();
+        }
+        StripeGuard(lock)
+    }
+
+    /// Find `flow`, and the first slot where it could be inserted.
+    #[inline]
+    fn probe(&self, flow: &Flow, home: usize) -> Probe<V> {
+        let mut free = None;
+        for i in 0..MAX_PROBE {
+            let idx = (home + i) & self.mask;
+            let (seq, data) = self.slot(idx).read();
+            match data.state {
+                EMPTY => {
+                    // Slots never become empty again, so the flow cannot be further along.
+                    if free.is_none() {
+                        free = Some((idx, seq));
+                    }
+                    break;
+                }
+                VALID if data.key == *flow => {
+                    return Probe {
+                        found: Some((idx, seq, data.value)),
+                        free: free,
+                    }
+                }
+                REMOVED if free.is_none() => free = Some((idx, seq)),
+                _ => {}
+            }
+        }
+        Probe {
+            found: None,
+            free: free,
+        }
+    }
+
+    /// Look up the value for `flow`.
+    #[inline]
+    pub fn get(&self, flow: &Flow) -> Option<V> {
+        let home = self.home(flow);
+        for i in 0..MAX_PROBE {
+            let (_, data) = self.slot((home + i) & self.mask).read();
+            match data.state {
+                EMPTY => return None,
+                VALID if data.key == *flow => return Some(data.value),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Write `flow` to the slot found by `probe`. Returns `None` if there is no room, and `Some(false)` if another core
+    /// modified the slot. Must hold the flow's lock.
+    #[inline]
+    fn write(&self, flow: Flow, value: V, probe: &Probe<V>) -> Option<bool> {
+        let data = SlotData {
+            state: VALID,
+            key: flow,
+            value: value,
+        };
+        match (probe.found, probe.free) {
+            (Some((idx, seq, _)), _) => Some(self.slot(idx).write_if_unchanged(seq, data)),
+            (None, Some((idx, seq))) => {
+                let written = self.slot(idx).write_if_unchanged(seq, data);
+                if written {
+                    self.len.fetch_add(1, Ordering::Relaxed);
+                }
+                Some(written)
+            }
+            (None, None) => None,
+        }
+    }
+
+    /// Insert or replace the value for `flow`. Returns false if there is no room for the flow.
+    pub fn insert(&self, flow: Flow, value: V) -> bool {
+        self.update(flow, |_| value).is_some()
+    }
+
+    /// Return the value for `flow`, inserting `value` if the flow is not present. Returns `None` if there is no room
+    /// for the flow.
+    pub fn get_or_insert(&self, flow: Flow, value: V) -> Option<V> {
+        let home = self.home(&flow);
+        let _guard = self.lock(home);
+        // Another core may claim the free slot we found for a different flow, in which case we look again. Each retry
+        // means another write succeeded, and we give up only once there is no free slot left.
+        loop {
+            let probe = self.probe(&flow, home);
+            if let Some((_, _, v)) = probe.found {
+                return Some(v);
+            }
+            match self.write(flow, value, &probe) {
+                Some(true) => return Some(value),
+                Some(false) => {}
+                None => return None,
+            }
+        }
+    }
+
+    /// Set the value for `flow` to `update(current)`, where `current` is `None` if the flow is not present. Returns the
+    /// new value, or `None` if there is no room for the flow. `update` is called again if another core takes the slot
+    /// the flow was about to be inserted into.
+    pub fn update<F: Fn(Option<V>) -> V>(&self, flow: Flow, update: F) -> Option<V> {
+        let home = self.home(&flow);
+        let _guard = self.lock(home);
+        // See `get_or_insert` for why this retries.
+        loop {
+            let probe = self.probe(&flow, home);
+            let value = update(probe.found.map(|(_, _, v)| v));
+            match self.write(flow, value, &probe) {
+                Some(true) => return Some(value),
+                Some(false) => {}
+                None => return None,
+            }
+        }
+    }
+
+    /// Remove `flow`, returning its value.
+    pub fn remove(&self, flow: &Flow) -> Option<V> {
+        let home = self.home(flow);
+        let _guard = self.lock(home);
+        if let Some((idx, seq, value)) = self.probe(flow, home).found {
+            let data = SlotData {
+                state: REMOVED,
+                key: *flow,
+                value: value,
+            };
+            // Only writers holding this flow's lock modify a slot holding the flow.
+            if self.slot(idx).write_if_unchanged(seq, data) {
+                self.len.fetch_sub(1, Ordering::Relaxed);
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Number of flows in the table.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of slots in the table.
+    pub fn capacity(&self) -> usize {
+        self.mask + 1
+    }
+}
+
+/// Checkpoints are consistent for each flow, but only consistent across flows if no other core writes to the table
+/// while it is checkpointed. Restoring fails if a flow does not fit in the table.
+impl<V: Copy + Default + Checkpoint> Checkpoint for FlowTable<V> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        let flows: Vec<_> = (0..self.capacity())
+            .map(|idx| self.slot(idx).read().1)
+            .filter(|data| data.state == VALID)
+            .collect();
+        try!(write_len(out, flows.len()));
+        for data in flows {
+            try!(data.key.checkpoint(out));
+            try!(data.value.checkpoint(out));
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        let len = try!(read_len(input));
+        // No other core can access the table, so slots can be emptied directly.
+        for idx in 0..self.capacity() {
+            unsafe { (*self.slot(idx).data.get()).state = EMPTY };
+        }
+        self.len.store(0, Ordering::Relaxed);
+        for _ in 0..len {
+            let flow: Flow = try!(restore_value(input));
+            if !self.insert(flow, try!(restore_value(input))) {
+                return bad_checkpoint(String::from("flow table is full"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<V: Copy> Drop for FlowTable<V> {
+    fn drop(&mut self) {
+        // Values are `Copy`, so there is nothing to drop.
+        unsafe { free_cache_line(self.slots as *mut u8, self.capacity() * size_of::<Slot<V>>()) };
+    }
+}
@@ -0,0 +1,265 @@
+use super::checkpoint::{expect_checkpoint, read_len, write_len, Checkpoint};
+use common::*;
+use fnv::FnvHasher;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::hash_set;
+use std::hash::{BuildHasherDefault, Hash};
+use std::io::{Read, Write};
+use std::ops::AddAssign;
+
+type FnvHash = BuildHasherDefault<FnvHasher>;
+
+/// State that can be merged with another copy of itself, in the style of a state based CRDT. `merge` must be
+/// commutative and associative: shards updated independently (e.g., on different cores) are merged by the control
+/// plane in no particular order. `Default` is the empty state, i.e., merging with it does nothing.
+pub trait Merge: Default + Clone {
+    /// Merge `other` into `self`.
+    fn merge(&mut self, other: &Self);
+
+    /// Reset to the empty state, keeping any configuration (e.g., the dimensions of a sketch).
+    fn clear(&mut self) {
+        *self = Default::default();
+    }
+}
+
+/// Sums values, i.e., the merge used by the `AddAssign` based stores.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Sum<T: AddAssign<T> + Default + Clone>(pub T);
+
+impl<T: AddAssign<T> + Default + Clone> Merge for Sum<T> {
+    #[inline]
+    fn merge(&mut self, other: &Sum<T>) {
+        self.0 += other.0.clone();
+    }
+}
+
+/// Largest value seen, `None` if no values have been seen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Max<T: Ord + Clone>(pub Option<T>);
+
+impl<T: Ord + Clone> Max<T> {
+    pub fn new(value: T) -> Max<T> {
+        Max(Some(value))
+    }
+
+    #[inline]
+    pub fn update(&mut self, value: T) {
+        if self.0.as_ref().map_or(true, |v| value > *v) {
+            self.0 = Some(value);
+        }
+    }
+}
+
+impl<T: Ord + Clone> Default for Max<T> {
+    fn default() -> Max<T> {
+        Max(None)
+    }
+}
+
+impl<T: Ord + Clone> Merge for Max<T> {
+    #[inline]
+    fn merge(&mut self, other: &Max<T>) {
+        if let Some(ref v) = other.0 {
+            self.update(v.clone());
+        }
+    }
+}
+
+/// Smallest value seen, `None` if no values have been seen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Min<T: Ord + Clone>(pub Option<T>);
+
+impl<T: Ord + Clone> Min<T> {
+    pub fn new(value: T) -> Min<T> {
+        Min(Some(value))
+    }
+
+    #[inline]
+    pub fn update(&mut self, value: T) {
+        if self.0.as_ref().map_or(true, |v| value < *v) {
+            self.0 = Some(value);
+        }
+    }
+}
+
+impl<T: Ord + Clone> Default for Min<T> {
+    fn default() -> Min<T> {
+        Min(None)
+    }
+}
+
+impl<T: Ord + Clone> Merge for Min<T> {
+    #[inline]
+    fn merge(&mut self, other: &Min<T>) {
+        if let Some(ref v) = other.0 {
+            self.update(v.clone());
+        }
+    }
+}
+
+/// Set of all values seen. This grows without bound, use a `HyperLogLog` when only the number of distinct values is
+/// needed.
+#[derive(Clone, Debug)]
+pub struct SetUnion<T: Hash + Eq + Clone> {
+    set: HashSet<T, FnvHash>,
+}
+
+impl<T: Hash + Eq + Clone> SetUnion<T> {
+    #[inline]
+    pub fn insert(&mut self, value: T) -> bool {
+        self.set.insert(value)
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.set.contains(value)
+    }
+
+    pub fn iter(&self) -> hash_set::Iter<T> {
+        self.set.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+}
+
+impl<T: Hash + Eq + Clone> Default for SetUnion<T> {
+    fn default() -> SetUnion<T> {
+        SetUnion {
+            set: HashSet::with_hasher(Default::default()),
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone> Merge for SetUnion<T> {
+    fn merge(&mut self, other: &SetUnion<T>) {
+        self.set.extend(other.set.iter().cloned());
+    }
+}
+
+const DEFAULT_TOP_K: usize = 16;
+
+/// The `k` keys with the largest counts. Counts for up to `2k` keys are kept, and the smallest are dropped when there
+/// are more, so keys that were dropped and later become popular again have their counts underestimated. This is exact
+/// when there are at most `2k` keys.
+#[derive(Clone, Debug)]
+pub struct TopK<K: Hash + Eq + Clone> {
+    k: usize,
+    counts: HashMap<K, u64, FnvHash>,
+}
+
+impl<K: Hash + Eq + Clone> TopK<K> {
+    pub fn new(k: usize) -> TopK<K> {
+        TopK {
+            k: k,
+            counts: HashMap::with_capacity_and_hasher(2 * k + 1, Default::default()),
+        }
+    }
+
+    /// Add `count` to `key`.
+    #[inline]
+    pub fn add(&mut self, key: K, count: u64) {
+        *self.counts.entry(key).or_insert(0) += count;
+        self.trim();
+    }
+
+    /// The top `k` keys and their counts, largest first.
+    pub fn top(&self) -> Vec<(K, u64)> {
+        let mut top = self.sorted();
+        top.truncate(self.k);
+        top
+    }
+
+    fn sorted(&self) -> Vec<(K, u64)> {
+        let mut all: Vec<_> = self.counts.iter().map(|(k, c)| (k.clone(), *c)).collect();
+        all.sort_by(|a, b| b.1.cmp(&a.1));
+        all
+    }
+
+    fn trim(&mut self) {
+        if self.counts.len() > 2 * self.k {
+            let mut keep = self.sorted();
+            keep.truncate(self.k);
+            self.counts.clear();
+            self.counts.extend(keep);
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> Default for TopK<K> {
+    fn default() -> TopK<K> {
+        TopK::new(DEFAULT_TOP_K)
+    }
+}
+
+impl<K: Hash + Eq + Clone> Merge for TopK<K> {
+    fn merge(&mut self, other: &TopK<K>) {
+        for (key, count) in &other.counts {
+            *self.counts.entry(key.clone()).or_insert(0) += *count;
+        }
+        self.trim();
+    }
+
+    fn clear(&mut self) {
+        self.counts.clear();
+    }
+}
+
+impl<T: AddAssign<T> + Default + Clone + Checkpoint> Checkpoint for Sum<T> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        self.0.checkpoint(out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        self.0.restore(input)
+    }
+}
+
+impl<T: Ord + Clone + Default + Checkpoint> Checkpoint for Max<T> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        self.0.checkpoint(out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        self.0.restore(input)
+    }
+}
+
+impl<T: Ord + Clone + Default + Checkpoint> Checkpoint for Min<T> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        self.0.checkpoint(out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        self.0.restore(input)
+    }
+}
+
+impl<T: Hash + Eq + Clone + Default + Checkpoint> Checkpoint for SetUnion<T> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        self.set.checkpoint(out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        self.set.restore(input)
+    }
+}
+
+impl<K: Hash + Eq + Clone + Default + Checkpoint> Checkpoint for TopK<K> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        try!(write_len(out, self.k));
+        self.counts.checkpoint(out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        try!(expect_checkpoint("top k", try!(read_len(input)), self.k));
+        try!(self.counts.restore(input));
+        self.trim();
+        Ok(())
+    }
+}
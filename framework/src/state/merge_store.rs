@@ -0,0 +1,256 @@
+use super::checkpoint::{read_len, restore_value, Checkpoint};
+use super::merge::Merge;
+use common::*;
+use fnv::FnvHasher;
+use std::cmp::min;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::hash_map::Iter;
+use std::hash::{BuildHasherDefault, Hash};
+use std::io::{Read, Write};
+use std::sync::{Arc, RwLock};
+use utils::Flow;
+
+type FnvHash = BuildHasherDefault<FnvHasher>;
+const SHARD_SIZE: usize = 1 << 10;
+pub(crate) const PUBLISH_INTERVAL: usize = 1 << 10;
+const MAX_PUBLISH_INTERVAL: usize = 1 << 20;
+
+/// Decides when a data plane instance publishes its local updates to a shard shared with the control plane, backing
+/// off while the control plane is reading the shard. Used by `MergeStoreDP` and `SketchDP`.
+pub(crate) struct Publisher<S> {
+    shard: Arc<RwLock<S>>,
+    updates: usize,
+    publish_interval: usize,
+    base_publish_interval: usize,
+}
+
+impl<S> Publisher<S> {
+    pub fn new(shard: Arc<RwLock<S>>, publish_interval: usize) -> Publisher<S> {
+        Publisher {
+            shard: shard,
+            updates: 0,
+            publish_interval: publish_interval,
+            base_publish_interval: publish_interval,
+        }
+    }
+
+    /// Count an update, calling `publish` with the shard once enough updates have accumulated and the shard is free.
+    #[inline]
+    pub fn updated<F: FnOnce(&mut S)>(&mut self, publish: F) {
+        self.updates += 1;
+        if self.updates >= self.publish_interval {
+            match self.shard.try_write() {
+                Ok(mut g) => {
+                    publish(&mut g);
+                    self.updates = 0;
+                    self.publish_interval = self.base_publish_interval;
+                }
+                // The control plane is reading the shard, try again later.
+                _ => self.publish_interval = min(self.publish_interval * 2, MAX_PUBLISH_INTERVAL),
+            }
+        }
+    }
+
+    /// Call `publish` with the shard, waiting for the control plane if necessary.
+    pub fn publish<F: FnOnce(&mut S)>(&mut self, publish: F) {
+        match self.shard.write() {
+            Ok(mut g) => {
+                publish(&mut g);
+                self.updates = 0;
+                self.publish_interval = self.base_publish_interval;
+            }
+            _ => panic!("Could not acquire write lock"),
+        }
+    }
+
+    pub fn shard(&self) -> &Arc<RwLock<S>> {
+        &self.shard
+    }
+}
+
+/// A store associating a `Merge` value (e.g., a `HyperLogLog` of sources, or a `TopK` of ports) with each key, where
+/// keys can be any hashable type (`Flow` by default, but also, e.g., an address or prefix). Each data plane store
+/// (usually one per core) accumulates updates locally and periodically publishes them to its shard, the control plane
+/// merges all shards in `sync`. Unlike the `MergeableStore*` types, values for the same key from different shards are
+/// merged rather than replaced, so keys do not need to be partitioned between cores.
+pub struct MergeStoreCP<V: Merge, K: Hash + Eq + Clone = Flow> {
+    merged: HashMap<K, V, FnvHash>,
+    /// Values restored from a checkpoint, merged with the shards when syncing.
+    restored: HashMap<K, V, FnvHash>,
+    template: V,
+    shards: Vec<Arc<RwLock<HashMap<K, V, FnvHash>>>>,
+}
+
+impl<V: Merge, K: Hash + Eq + Clone> MergeStoreCP<V, K> {
+    pub fn new() -> MergeStoreCP<V, K> {
+        MergeStoreCP::with_template(Default::default())
+    }
+
+    /// Create a store whose values start as (empty) copies of `template`, e.g., `HyperLogLog::new(14)`, for values
+    /// whose configuration differs from their `Default`.
+    pub fn with_template(template: V) -> MergeStoreCP<V, K> {
+        let mut template = template;
+        template.clear();
+        MergeStoreCP {
+            merged: HashMap::with_hasher(Default::default()),
+            restored: HashMap::with_hasher(Default::default()),
+            template: template,
+            shards: Vec::new(),
+        }
+    }
+
+    /// Create a data plane store that publishes its updates every `publish_interval` updates.
+    pub fn dp_store_with_interval_and_size(&mut self, publish_interval: usize, size: usize) -> MergeStoreDP<V, K> {
+        let shard = Arc::new(RwLock::new(HashMap::with_capacity_and_hasher(
+            size,
+            Default::default(),
+        )));
+        self.shards.push(shard.clone());
+        MergeStoreDP {
+            delta: HashMap::with_capacity_and_hasher(size, Default::default()),
+            template: self.template.clone(),
+            publisher: Publisher::new(shard, publish_interval),
+        }
+    }
+
+    pub fn dp_store(&mut self) -> MergeStoreDP<V, K> {
+        self.dp_store_with_interval_and_size(PUBLISH_INTERVAL, SHARD_SIZE)
+    }
+
+    /// Merge all shards. Shards that are being written to are skipped, and their values are missing until the next
+    /// sync.
+    pub fn sync(&mut self) {
+        self.merged.clone_from(&self.restored);
+        for shard in &self.shards {
+            if let Ok(g) = shard.try_read() {
+                for (k, v) in g.iter() {
+                    match self.merged.entry(k.clone()) {
+                        Entry::Occupied(mut e) => e.get_mut().merge(v),
+                        Entry::Vacant(e) => {
+                            e.insert(v.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, key: &K) -> V {
+        match self.merged.get(key) {
+            Some(v) => v.clone(),
+            None => self.template.clone(),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<K, V> {
+        self.merged.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.merged.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.merged.is_empty()
+    }
+}
+
+impl<V: Merge, K: Hash + Eq + Clone> Default for MergeStoreCP<V, K> {
+    fn default() -> MergeStoreCP<V, K> {
+        MergeStoreCP::new()
+    }
+}
+
+/// Checkpoints hold the merged values as of the last `sync`, so sync (with pipelines paused) first. Updates the data
+/// plane stores have not published are not included. Restored values replace any previously restored values and are
+/// merged with later updates; shards are cleared.
+impl<V: Merge + Checkpoint, K: Hash + Eq + Clone + Default + Checkpoint> Checkpoint for MergeStoreCP<V, K> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        self.merged.checkpoint(out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        let len = try!(read_len(input));
+        self.restored.clear();
+        for _ in 0..len {
+            let key = try!(restore_value(input));
+            // Values are restored into copies of the template, so they keep its configuration.
+            let mut value = self.template.clone();
+            try!(value.restore(input));
+            self.restored.insert(key, value);
+        }
+        for shard in &self.shards {
+            if let Ok(mut g) = shard.write() {
+                g.clear();
+            }
+        }
+        self.merged.clone_from(&self.restored);
+        Ok(())
+    }
+}
+
+/// The data plane side of a `MergeStoreCP`. Updates are applied to a local delta, which is merged into the shared
+/// shard (and reset) when publishing. Since values are merged rather than copied, `merge` need not be idempotent.
+pub struct MergeStoreDP<V: Merge, K: Hash + Eq + Clone = Flow> {
+    delta: HashMap<K, V, FnvHash>,
+    template: V,
+    publisher: Publisher<HashMap<K, V, FnvHash>>,
+}
+
+impl<V: Merge, K: Hash + Eq + Clone> MergeStoreDP<V, K> {
+    /// Update the value for `key` in place, e.g., `store.update_with(flow.dst_ip, |h| h.insert(&flow.src_ip))`.
+    #[inline]
+    pub fn update_with<F: FnOnce(&mut V)>(&mut self, key: K, update: F) {
+        let template = &self.template;
+        update(self.delta.entry(key).or_insert_with(|| template.clone()));
+        self.updated();
+    }
+
+    /// Merge `value` into the value for `key`.
+    #[inline]
+    pub fn merge(&mut self, key: K, value: &V) {
+        match self.delta.entry(key) {
+            Entry::Occupied(mut e) => e.get_mut().merge(value),
+            Entry::Vacant(e) => {
+                e.insert(value.clone());
+            }
+        }
+        self.updated();
+    }
+
+    #[inline]
+    fn updated(&mut self) {
+        let delta = &mut self.delta;
+        self.publisher
+            .updated(|shard| MergeStoreDP::merge_delta(shard, delta));
+    }
+
+    fn merge_delta(shard: &mut HashMap<K, V, FnvHash>, delta: &mut HashMap<K, V, FnvHash>) {
+        for (k, v) in delta.drain() {
+            if let Some(existing) = shard.get_mut(&k) {
+                existing.merge(&v);
+                continue;
+            }
+            shard.insert(k, v);
+        }
+    }
+
+    /// Publish all pending updates, waiting for the control plane if necessary.
+    pub fn publish(&mut self) {
+        let delta = &mut self.delta;
+        self.publisher
+            .publish(|shard| MergeStoreDP::merge_delta(shard, delta));
+    }
+
+    /// Remove `key` from this store's shard, along with any pending updates. Other shards are not affected.
+    pub fn remove(&mut self, key: &K) {
+        self.delta.remove(key);
+        match self.publisher.shard().write() {
+            Ok(mut g) => {
+                g.remove(key);
+            }
+            _ => panic!("Could not acquire write lock"),
+        }
+    }
+}
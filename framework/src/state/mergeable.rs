@@ -1,8 +1,11 @@
+use super::checkpoint::Checkpoint;
+use super::expiry::{checkpoint_aged, restore_aged, AgedFlows, ExpiryPolicy, FlowExpiry, FlowValues};
+use common::*;
 use fnv::FnvHasher;
 use std::cmp::max;
 use std::collections::HashMap;
-use std::collections::hash_map::Iter;
 use std::hash::BuildHasherDefault;
+use std::io::{Read, Write};
 use std::ops::AddAssign;
 use std::sync::{Arc, RwLock, RwLockReadGuard};
 use utils::Flow;
@@ -12,10 +15,10 @@ use utils::Flow;
 /// [`AddAssign`](https://doc.rust-lang.org/std/ops/trait.AddAssign.html) trait and overriding the `add_assign` method
 /// there. We assume that the quantity stored here does not need to be accessed by the control plane and can only be
 /// accessed from the data plane. The `cache_size` should be tuned depending on whether gets or puts are the most common
-/// operation in this table.
+/// operation in this table. Flows are expired (see `ExpiryPolicy`) by the control plane in `sync`, and the removals
+/// are propagated to the data plane stores.
 ///
 /// #[FIXME]
-/// Garbage collection.
 /// The current version does not work well with large flow tables. The problem is we need to record a set of differences
 /// rather than copying the entire hashmap. This of course comes with some consistency issues, so we need to fix this.
 type FnvHash = BuildHasherDefault<FnvHasher>;
@@ -24,16 +27,32 @@ const CACHE_SIZE: usize = 1 << 10;
 const MAX_CACHE_SIZE: usize = 1 << 20;
 const CHAN_SIZE: usize = 128;
 
+/// Data plane entries, along with when (see `ExpiryPolicy`) they were last merged.
+type DpMap<T> = AgedFlows<T, FnvHash>;
+
 pub struct MergeableStoreCP<T: AddAssign<T> + Default + Clone> {
-    flow_counters: HashMap<Flow, T, FnvHash>,
-    hashmaps: Vec<Arc<RwLock<HashMap<Flow, T, FnvHash>>>>,
+    /// Values as of the last sync, along with when they were last merged by a data plane store.
+    flow_counters: DpMap<T>,
+    /// Values restored from a checkpoint, added to those from the data plane stores when syncing.
+    restored: DpMap<T>,
+    hashmaps: Vec<Arc<RwLock<DpMap<T>>>>,
+    policy: ExpiryPolicy,
+    expiry: FlowExpiry,
 }
 
 impl<T: AddAssign<T> + Default + Clone> MergeableStoreCP<T> {
     pub fn new() -> MergeableStoreCP<T> {
+        MergeableStoreCP::new_with_expiry(Default::default())
+    }
+
+    /// Create a store that removes flows according to `expiry` when syncing.
+    pub fn new_with_expiry(expiry: ExpiryPolicy) -> MergeableStoreCP<T> {
         MergeableStoreCP {
             flow_counters: HashMap::with_capacity_and_hasher(VEC_SIZE << 6, Default::default()),
+            restored: HashMap::with_hasher(Default::default()),
             hashmaps: Vec::with_capacity(CHAN_SIZE),
+            policy: expiry,
+            expiry: FlowExpiry::new(expiry),
         }
     }
 
@@ -49,6 +68,7 @@ impl<T: AddAssign<T> + Default + Clone> MergeableStoreCP<T> {
             cache_size: cache,
             base_cache_size: cache,
             len: 0,
+            policy: self.policy,
         }
     }
 
@@ -56,9 +76,9 @@ impl<T: AddAssign<T> + Default + Clone> MergeableStoreCP<T> {
         MergeableStoreCP::dp_store_with_cache_and_size(self, CACHE_SIZE, VEC_SIZE)
     }
 
-    fn hmap_to_vec(hash: &RwLockReadGuard<HashMap<Flow, T, FnvHash>>) -> Vec<(Flow, T)> {
+    fn hmap_to_vec(hash: &RwLockReadGuard<DpMap<T>>) -> Vec<(Flow, T, u64)> {
         let mut t = Vec::with_capacity(hash.len());
-        t.extend(hash.iter().map(|(f, v)| (*f, v.clone())));
+        t.extend(hash.iter().map(|(f, &(ref v, t))| (*f, v.clone(), t)));
         t
     }
 
@@ -75,19 +95,51 @@ impl<T: AddAssign<T> + Default + Clone> MergeableStoreCP<T> {
         }
         self.flow_counters.clear();
         for mut copy in copies {
-            self.flow_counters.extend(copy.drain(0..));
+            for (f, v, t) in copy.drain(0..) {
+                // Later stores replace values, but the flow was last updated by whichever store saw it last.
+                let t = self.flow_counters.get(&f).map_or(t, |&(_, other)| max(t, other));
+                self.flow_counters.insert(f, (v, t));
+            }
+        }
+        for (f, &(ref restored, t)) in &self.restored {
+            let entry = self.flow_counters.entry(*f).or_insert_with(|| (Default::default(), t));
+            let mut value = restored.clone();
+            value += entry.0.clone();
+            *entry = (value, max(t, entry.1));
+        }
+        if !self.expiry.is_enabled() {
+            return;
+        }
+        let now = self.expiry.now();
+        let expired = self.expiry.expire(&mut self.flow_counters, now);
+        if expired.is_empty() {
+            return;
+        }
+        for &(ref flow, _) in &expired {
+            self.restored.remove(flow);
+        }
+        // Data plane stores that are busy are cleaned up on a later sync. Entries updated since we copied them are
+        // kept.
+        for hmap in &self.hashmaps {
+            if let Ok(mut g) = hmap.try_write() {
+                for &(ref flow, t) in &expired {
+                    if g.get(flow).map_or(false, |&(_, updated)| updated <= t) {
+                        g.remove(flow);
+                    }
+                }
+            }
         }
     }
 
     pub fn get(&self, flow: &Flow) -> T {
         match self.flow_counters.get(flow) {
-            Some(i) => i.clone(),
+            Some(&(ref i, _)) => i.clone(),
             None => Default::default(),
         }
     }
 
-    pub fn iter(&self) -> Iter<Flow, T> {
-        self.flow_counters.iter()
+    pub fn iter(&self) -> FlowValues<T> {
+        FlowValues::new(self.flow_counters.iter())
     }
 
     pub fn len(&self) -> usize {
@@ -99,21 +151,44 @@ impl<T: AddAssign<T> + Default + Clone> MergeableStoreCP<T> {
     }
 }
 
+/// Checkpoints hold the values as of the last `sync`, so sync (with pipelines paused) first; updates still cached by
+/// data plane stores are not included. Restored values replace those in the data plane stores and any previously
+/// restored values, and are added to the values from the data plane stores on each sync.
+impl<T: AddAssign<T> + Default + Clone + Checkpoint> Checkpoint for MergeableStoreCP<T> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        checkpoint_aged(&self.flow_counters, out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        let now = self.expiry.now();
+        try!(restore_aged(&mut self.flow_counters, input, now));
+        for hmap in &self.hashmaps {
+            if let Ok(mut g) = hmap.write() {
+                g.clear();
+            }
+        }
+        self.restored = self.flow_counters.clone();
+        Ok(())
+    }
+}
+
 #[derive(Clone)]
 pub struct MergeableStoreDP<T: AddAssign<T> + Default + Clone> {
     /// Contains the counts on the data path.
-    flow_counters: Arc<RwLock<HashMap<Flow, T, FnvHash>>>,
+    flow_counters: Arc<RwLock<DpMap<T>>>,
     cache: Vec<(Flow, T)>,
     base_cache_size: usize,
     cache_size: usize,
     len: usize,
+    policy: ExpiryPolicy,
 }
 
 impl<T: AddAssign<T> + Default + Clone> MergeableStoreDP<T> {
     fn merge_cache(&mut self) {
         match self.flow_counters.try_write() {
             Ok(mut g) => {
-                g.extend(self.cache.drain(0..));
+                let now = self.policy.now();
+                g.extend(self.cache.drain(0..).map(|(f, v)| (f, (v, now))));
                 self.cache_size = self.base_cache_size;
                 self.len = g.len();
             }
@@ -138,10 +213,11 @@ impl<T: AddAssign<T> + Default + Clone> MergeableStoreDP<T> {
         // self.merge_cache();
         match self.flow_counters.write() {
             Ok(mut g) => {
-                g.extend(self.cache.drain(0..));
+                let now = self.policy.now();
+                g.extend(self.cache.drain(0..).map(|(f, v)| (f, (v, now))));
                 self.cache_size = self.base_cache_size;
                 self.len = g.len();
-                g.remove(flow).unwrap_or_else(Default::default)
+                g.remove(flow).map_or_else(Default::default, |(v, _)| v)
             }
             _ => panic!("Could not acquire write lock"),
         }
@@ -1,10 +1,22 @@
+pub use self::checkpoint::*;
 pub use self::cp_mergeable::*;
 pub use self::dp_mergeable::*;
+pub use self::expiry::{ExpiryPolicy, FlowValues};
+pub use self::flow_table::FlowTable;
+pub use self::merge::*;
+pub use self::merge_store::*;
 pub use self::mergeable::*;
 pub use self::reordered_buffer::*;
 pub use self::ring_buffer::*;
+pub use self::sketch::*;
+mod checkpoint;
 mod dp_mergeable;
 mod cp_mergeable;
+mod expiry;
+mod flow_table;
+mod merge;
+mod merge_store;
 mod mergeable;
 mod ring_buffer;
+mod sketch;
 pub mod reordered_buffer;
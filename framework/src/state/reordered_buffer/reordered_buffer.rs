@@ -1,6 +1,7 @@
 use common::*;
-use state::RingBuffer;
+use state::{bad_checkpoint, expect_checkpoint, read_len, restore_value, write_len, Checkpoint, RingBuffer};
 use std::cmp::{max, min};
+use std::io::{Read, Write};
 use std::u16;
 use utils::*;
 
@@ -168,6 +169,9 @@ impl SegmentList {
                     // No more merges are possible so exit this loop.
                     break;
                 }
+            } else {
+                // There is a gap before the next segment, so nothing further can be merged.
+                break;
             }
         }
     }
@@ -215,7 +219,7 @@ impl SegmentList {
                     // We are on to segments that are further down, insert
                     idx = self.insert_before_node(idx, seq, len);
                     break;
-                } else if self.storage[idx as usize].seq <= seq {
+                } else if seg_seq <= seq && seq <= seg_end {
                     // println!("Overlapping");
                     // Overlapping segment
                     let new_end = max(seg_end, end);
@@ -247,6 +251,11 @@ impl SegmentList {
     fn remove_head(&mut self) {
         let head = self.head;
         self.head = self.storage[head as usize].next;
+        if self.head == -1 {
+            self.tail = -1;
+        } else {
+            self.storage[self.head as usize].prev = -1;
+        }
         self.remove_node(head);
     }
 
@@ -533,3 +542,73 @@ impl ReorderedBuffer {
         read
     }
 }
+
+/// Checkpoints hold the connection state along with all buffered data (in-order and out-of-order), which is inserted
+/// again when restoring. The restored buffer must have the same size.
+impl Checkpoint for ReorderedBuffer {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        try!(write_len(out, self.buffer_size));
+        let state: u8 = match self.state {
+            State::Closed => 0,
+            State::Connected => 1,
+            State::ConnectedOutOfOrder => 2,
+        };
+        try!(state.checkpoint(out));
+        try!(self.head_seq.checkpoint(out));
+        // Buffered data, as (sequence number, length) ranges in sequence order.
+        let mut ranges = Vec::new();
+        match self.state {
+            State::Closed => {}
+            State::Connected => if self.available() > 0 {
+                ranges.push((self.head_seq, self.available()));
+            },
+            State::ConnectedOutOfOrder => {
+                let mut idx = self.segment_list.head;
+                while idx != -1 {
+                    let segment = self.segment_list.get_segment(idx);
+                    ranges.push((segment.seq, segment.length as usize));
+                    idx = segment.next;
+                }
+            }
+        }
+        try!(write_len(out, ranges.len()));
+        let mut data = Vec::new();
+        for (seq, len) in ranges {
+            data.resize(len, 0);
+            let read = self.data.peek(seq.wrapping_sub(self.head_seq) as usize, &mut data);
+            try!(seq.checkpoint(out));
+            try!(write_len(out, read));
+            try!(out.write_all(&data[..read]));
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        try!(expect_checkpoint("reordered buffer size", try!(read_len(input)), self.buffer_size));
+        let state: u8 = try!(restore_value(input));
+        let head_seq: u32 = try!(restore_value(input));
+        let ranges = try!(read_len(input));
+        if state > 2 {
+            return bad_checkpoint(format!("unknown reordered buffer state {}", state));
+        }
+        self.reset();
+        if state == 0 {
+            return Ok(());
+        }
+        self.seq(head_seq, &[]);
+        let mut data = Vec::new();
+        for _ in 0..ranges {
+            let seq: u32 = try!(restore_value(input));
+            let len = try!(read_len(input));
+            if len >= self.buffer_size {
+                return bad_checkpoint(format!("{} bytes do not fit in reordered buffer", len));
+            }
+            data.resize(len, 0);
+            try!(input.read_exact(&mut data));
+            if let InsertionResult::OutOfMemory { .. } = self.add_data(seq, &data) {
+                return bad_checkpoint(String::from("reordered buffer is out of space"));
+            }
+        }
+        Ok(())
+    }
+}
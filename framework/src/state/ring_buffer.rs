@@ -1,3 +1,4 @@
+use super::checkpoint::{bad_checkpoint, expect_checkpoint, read_len, write_len, Checkpoint};
 use common::*;
 use std::cmp::min;
 use std::io::{Read, Write};
@@ -39,7 +40,7 @@ impl RingBuffer {
 
     /// Reads data from self.vec, wrapping around the end of the Vec if necessary. Returns the
     /// number of bytes written.
-    fn wrapped_read(&mut self, offset: usize, data: &mut [u8]) -> usize {
+    fn wrapped_read(&self, offset: usize, data: &mut [u8]) -> usize {
         let mut bytes: usize = 0;
         assert!(offset < self.size);
         assert!(data.len() <= self.size);
@@ -135,6 +136,16 @@ impl RingBuffer {
         self.read_from_head_with_increment(data, len)
     }
 
+    /// Read data starting `offset` bytes after the read head, without moving the head. This includes data written
+    /// beyond the tail (using `write_at_offset_from_tail`), so the caller must know which bytes hold data. Returns
+    /// bytes read.
+    #[inline]
+    pub fn peek(&self, offset: usize, data: &mut [u8]) -> usize {
+        let to_read = min(self.mask.saturating_sub(offset), data.len());
+        let index = self.head.wrapping_add(offset) & self.mask;
+        self.wrapped_read(index, &mut data[..to_read])
+    }
+
     /// Seek the read head by `seek` bytes (without actually reading any data). `seek` must be less-than-or-equal to the
     /// number of available bytes.
     #[inline]
@@ -164,6 +175,31 @@ impl RingBuffer {
     }
 }
 
+/// Checkpoints hold the data available to be read, data written at an offset beyond the tail is not included.
+impl Checkpoint for RingBuffer {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        let mut data = vec![0; self.available()];
+        self.peek(0, &mut data);
+        try!(write_len(out, self.size));
+        try!(write_len(out, data.len()));
+        try!(out.write_all(&data));
+        Ok(())
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        try!(expect_checkpoint("ring buffer size", try!(read_len(input)), self.size));
+        let len = try!(read_len(input));
+        if len > self.mask {
+            return bad_checkpoint(format!("ring buffer data is {} bytes, more than it can hold", len));
+        }
+        let mut data = vec![0; len];
+        try!(input.read_exact(&mut data));
+        self.clear();
+        self.write_at_tail(&data);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
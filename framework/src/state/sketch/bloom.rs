@@ -0,0 +1,131 @@
+use common::*;
+use state::{checkpoint_slice, expect_checkpoint, restore_slice, restore_value, Checkpoint, Merge};
+use super::{index_bits, mix64, SketchKey};
+use std::cmp::{max, min};
+use std::f64::consts::LN_2;
+use std::io::{Read, Write};
+
+const DEFAULT_BITS: usize = 1 << 16;
+const DEFAULT_HASHES: u32 = 4;
+const MAX_HASHES: u32 = 16;
+
+/// Bloom filter: set membership with false positives but no false negatives. Filters with the same dimensions are
+/// merged by union. Use a `CuckooFilter` if keys need to be removed.
+#[derive(Clone, Debug)]
+pub struct BloomFilter {
+    bits: u32,
+    hashes: u32,
+    words: Box<[u64]>,
+}
+
+impl BloomFilter {
+    /// Create a filter with `bits` bits (rounded up to a power of two, at least 64) and `hashes` hash functions.
+    pub fn new(bits: usize, hashes: u32) -> BloomFilter {
+        assert!(hashes > 0 && hashes <= MAX_HASHES, "Bloom filter hashes must be in [1, {}]", MAX_HASHES);
+        let bits = max(index_bits(bits), 6);
+        BloomFilter {
+            bits: bits,
+            hashes: hashes,
+            words: vec![0; 1 << (bits - 6)].into_boxed_slice(),
+        }
+    }
+
+    /// Create a filter sized for `expected` keys with a false positive rate of `rate`.
+    pub fn with_rate(expected: usize, rate: f64) -> BloomFilter {
+        let bits = (-(expected as f64) * rate.ln() / (LN_2 * LN_2)).ceil() as usize;
+        let hashes = ((bits as f64 / max(expected, 1) as f64) * LN_2).round() as u32;
+        BloomFilter::new(bits, min(max(hashes, 1), MAX_HASHES))
+    }
+
+    /// Bit positions for a key, using double hashing.
+    #[inline]
+    fn positions<K: SketchKey>(&self, key: &K) -> (u64, u64) {
+        let hash = mix64(key.sketch_hash());
+        (hash >> 32, (hash & 0xffff_ffff) | 1)
+    }
+
+    #[inline]
+    fn bit(&self, h1: u64, h2: u64, i: u32) -> usize {
+        (h1.wrapping_add((i as u64).wrapping_mul(h2)) & ((1 << self.bits) - 1)) as usize
+    }
+
+    /// Add `key`, returning true if it was (probably) already present.
+    #[inline]
+    pub fn insert<K: SketchKey>(&mut self, key: &K) -> bool {
+        let (h1, h2) = self.positions(key);
+        let mut present = true;
+        for i in 0..self.hashes {
+            let bit = self.bit(h1, h2, i);
+            let mask = 1 << (bit & 63);
+            present &= self.words[bit >> 6] & mask != 0;
+            self.words[bit >> 6] |= mask;
+        }
+        present
+    }
+
+    /// Is `key` (probably) present?
+    #[inline]
+    pub fn contains<K: SketchKey>(&self, key: &K) -> bool {
+        let (h1, h2) = self.positions(key);
+        (0..self.hashes).all(|i| {
+            let bit = self.bit(h1, h2, i);
+            self.words[bit >> 6] & (1 << (bit & 63)) != 0
+        })
+    }
+
+    /// Fraction of bits set, the false positive rate is roughly this to the power of the number of hashes.
+    pub fn fill_ratio(&self) -> f64 {
+        let set: u32 = self.words.iter().map(|w| w.count_ones()).sum();
+        set as f64 / (1u64 << self.bits) as f64
+    }
+
+    pub fn clear(&mut self) {
+        for w in self.words.iter_mut() {
+            *w = 0;
+        }
+    }
+}
+
+impl Default for BloomFilter {
+    fn default() -> BloomFilter {
+        BloomFilter::new(DEFAULT_BITS, DEFAULT_HASHES)
+    }
+}
+
+impl Merge for BloomFilter {
+    fn merge(&mut self, other: &BloomFilter) {
+        assert!(
+            self.bits == other.bits && self.hashes == other.hashes,
+            "Cannot merge Bloom filters with different dimensions"
+        );
+        for (w, o) in self.words.iter_mut().zip(other.words.iter()) {
+            *w |= *o;
+        }
+    }
+
+    fn clear(&mut self) {
+        BloomFilter::clear(self)
+    }
+}
+
+impl Checkpoint for BloomFilter {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        try!(self.bits.checkpoint(out));
+        try!(self.hashes.checkpoint(out));
+        checkpoint_slice(&self.words, out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        try!(expect_checkpoint(
+            "Bloom filter bits",
+            try!(restore_value::<u32>(input)) as usize,
+            self.bits as usize
+        ));
+        try!(expect_checkpoint(
+            "Bloom filter hashes",
+            try!(restore_value::<u32>(input)) as usize,
+            self.hashes as usize
+        ));
+        restore_slice(&mut self.words, input)
+    }
+}
@@ -0,0 +1,140 @@
+use common::*;
+use state::{checkpoint_slice, expect_checkpoint, restore_slice, restore_value, Checkpoint, Merge};
+use super::{index_bits, mix64, row_index, SketchKey, MAX_ROWS};
+use std::cmp::{max, min};
+use std::f64::consts::E;
+use std::io::{Read, Write};
+
+const DEFAULT_WIDTH: usize = 2048;
+const DEFAULT_DEPTH: usize = 4;
+
+/// Count-Min sketch: estimates the count for a key, never underestimating it, and overestimating by at most
+/// `2 * total / width` with probability `1 - 2^-depth`. Sketches with the same dimensions are merged by adding
+/// counters.
+#[derive(Clone, Debug)]
+pub struct CountMinSketch {
+    bits: u32,
+    depth: usize,
+    counters: Box<[u64]>,
+    total: u64,
+}
+
+impl CountMinSketch {
+    /// Create a sketch with `depth` (at most 8) rows of `width` counters, `width` is rounded up to a power of two.
+    pub fn new(width: usize, depth: usize) -> CountMinSketch {
+        assert!(depth > 0 && depth <= MAX_ROWS, "Count-Min depth must be in [1, {}]", MAX_ROWS);
+        let bits = index_bits(width);
+        CountMinSketch {
+            bits: bits,
+            depth: depth,
+            counters: vec![0; depth << bits].into_boxed_slice(),
+            total: 0,
+        }
+    }
+
+    /// Create a sketch whose estimates are within `epsilon * total` of the true count with probability `1 - delta`.
+    pub fn with_error(epsilon: f64, delta: f64) -> CountMinSketch {
+        let width = (E / epsilon).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil() as usize;
+        CountMinSketch::new(width, min(max(depth, 1), MAX_ROWS))
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        1 << self.bits
+    }
+
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Add `count` to `key`, returning the new estimate for `key`.
+    #[inline]
+    pub fn add<K: SketchKey>(&mut self, key: &K, count: u64) -> u64 {
+        self.add_hash(mix64(key.sketch_hash()), count)
+    }
+
+    /// Add `count` to a key given its hash (from `mix64`), returning the new estimate.
+    #[inline]
+    pub(crate) fn add_hash(&mut self, hash: u64, count: u64) -> u64 {
+        self.total += count;
+        let mut estimate = u64::max_value();
+        for row in 0..self.depth {
+            let idx = (row << self.bits) + row_index(hash, row, self.bits);
+            self.counters[idx] += count;
+            estimate = min(estimate, self.counters[idx]);
+        }
+        estimate
+    }
+
+    /// Estimated count for `key`.
+    #[inline]
+    pub fn estimate<K: SketchKey>(&self, key: &K) -> u64 {
+        self.estimate_hash(mix64(key.sketch_hash()))
+    }
+
+    #[inline]
+    pub(crate) fn estimate_hash(&self, hash: u64) -> u64 {
+        (0..self.depth)
+            .map(|row| self.counters[(row << self.bits) + row_index(hash, row, self.bits)])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Sum of all counts added.
+    #[inline]
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn clear(&mut self) {
+        for c in self.counters.iter_mut() {
+            *c = 0;
+        }
+        self.total = 0;
+    }
+}
+
+impl Default for CountMinSketch {
+    fn default() -> CountMinSketch {
+        CountMinSketch::new(DEFAULT_WIDTH, DEFAULT_DEPTH)
+    }
+}
+
+impl Merge for CountMinSketch {
+    fn merge(&mut self, other: &CountMinSketch) {
+        assert!(
+            self.bits == other.bits && self.depth == other.depth,
+            "Cannot merge Count-Min sketches with different dimensions"
+        );
+        for (c, o) in self.counters.iter_mut().zip(other.counters.iter()) {
+            *c += *o;
+        }
+        self.total += other.total;
+    }
+
+    fn clear(&mut self) {
+        CountMinSketch::clear(self)
+    }
+}
+
+impl Checkpoint for CountMinSketch {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        try!(self.bits.checkpoint(out));
+        try!(self.depth.checkpoint(out));
+        try!(self.total.checkpoint(out));
+        checkpoint_slice(&self.counters, out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        try!(expect_checkpoint(
+            "Count-Min width bits",
+            try!(restore_value::<u32>(input)) as usize,
+            self.bits as usize
+        ));
+        try!(expect_checkpoint("Count-Min depth", try!(restore_value(input)), self.depth));
+        try!(self.total.restore(input));
+        restore_slice(&mut self.counters, input)
+    }
+}
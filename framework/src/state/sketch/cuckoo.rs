@@ -0,0 +1,253 @@
+use common::*;
+use state::{bad_checkpoint, checkpoint_slice, expect_checkpoint, restore_slice, restore_value, Checkpoint, Merge};
+use super::{index_bits, mix64, SketchKey};
+use std::io::{Read, Write};
+
+const BUCKET_SIZE: usize = 4;
+const DEFAULT_BUCKETS: usize = 1 << 12;
+const MAX_KICKS: usize = 500;
+
+/// Cuckoo filter: set membership with false positives (about `8 / 2^16` with 16-bit fingerprints) that, unlike a
+/// `BloomFilter`, supports removal. Each insert stores a fingerprint, even if the key already appears to be present
+/// (since it might be a different key with the same fingerprint), so a key inserted twice is present until removed
+/// twice. Inserts fail once the filter is around 95% full. Filters with the same number of buckets are merged by adding
+/// the other filter's fingerprints, so a key inserted into both is present until removed from both.
+#[derive(Clone, Debug)]
+pub struct CuckooFilter {
+    bits: u32,
+    fingerprints: Box<[u16]>,
+    /// Fingerprint (and bucket) displaced by the last insert that ran out of kicks.
+    victim: Option<(usize, u16)>,
+    len: usize,
+    failed: usize,
+    rng: u64,
+}
+
+impl CuckooFilter {
+    /// Create a filter with `buckets` buckets (rounded up to a power of two) of 4 fingerprints each.
+    pub fn new(buckets: usize) -> CuckooFilter {
+        let bits = index_bits(buckets);
+        CuckooFilter {
+            bits: bits,
+            fingerprints: vec![0; BUCKET_SIZE << bits].into_boxed_slice(),
+            victim: None,
+            len: 0,
+            failed: 0,
+            rng: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    /// Create a filter with room for `capacity` keys.
+    pub fn with_capacity(capacity: usize) -> CuckooFilter {
+        // Aim for the filter to be at most ~90% full.
+        CuckooFilter::new((capacity * 10 / 9 + BUCKET_SIZE - 1) / BUCKET_SIZE)
+    }
+
+    #[inline]
+    fn mask(&self) -> usize {
+        (1 << self.bits) - 1
+    }
+
+    #[inline]
+    fn fingerprint_and_index<K: SketchKey>(&self, key: &K) -> (u16, usize) {
+        let hash = mix64(key.sketch_hash());
+        // 0 marks an empty slot.
+        let fp = match hash as u16 {
+            0 => 1,
+            fp => fp,
+        };
+        (fp, (hash >> 32) as usize & self.mask())
+    }
+
+    #[inline]
+    fn alt_index(&self, index: usize, fp: u16) -> usize {
+        (index ^ mix64(fp as u32) as usize) & self.mask()
+    }
+
+    #[inline]
+    fn bucket(&self, index: usize) -> &[u16] {
+        &self.fingerprints[index * BUCKET_SIZE..(index + 1) * BUCKET_SIZE]
+    }
+
+    #[inline]
+    fn bucket_contains(&self, index: usize, fp: u16) -> bool {
+        self.bucket(index).iter().any(|f| *f == fp)
+    }
+
+    fn contains_fingerprint(&self, index: usize, fp: u16) -> bool {
+        let alt = self.alt_index(index, fp);
+        self.bucket_contains(index, fp) || self.bucket_contains(alt, fp) || match self.victim {
+            Some((i, f)) => f == fp && (i == index || i == alt),
+            None => false,
+        }
+    }
+
+    #[inline]
+    fn try_bucket(&mut self, index: usize, fp: u16) -> bool {
+        for slot in &mut self.fingerprints[index * BUCKET_SIZE..(index + 1) * BUCKET_SIZE] {
+            if *slot == 0 {
+                *slot = fp;
+                return true;
+            }
+        }
+        false
+    }
+
+    #[inline]
+    fn next_random(&mut self) -> usize {
+        // xorshift64
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng as usize
+    }
+
+    fn insert_fingerprint(&mut self, index: usize, fp: u16) -> bool {
+        if self.victim.is_some() {
+            self.failed += 1;
+            return false;
+        }
+        let alt = self.alt_index(index, fp);
+        if self.try_bucket(index, fp) || self.try_bucket(alt, fp) {
+            self.len += 1;
+            return true;
+        }
+        let mut index = if self.next_random() & 1 == 0 { index } else { alt };
+        let mut fp = fp;
+        for _ in 0..MAX_KICKS {
+            let slot = index * BUCKET_SIZE + self.next_random() % BUCKET_SIZE;
+            let evicted = self.fingerprints[slot];
+            self.fingerprints[slot] = fp;
+            fp = evicted;
+            index = self.alt_index(index, fp);
+            if self.try_bucket(index, fp) {
+                self.len += 1;
+                return true;
+            }
+        }
+        // The key was inserted, but some other key was displaced and is kept aside.
+        self.victim = Some((index, fp));
+        self.len += 1;
+        true
+    }
+
+    /// Add `key`. Returns false if the filter is full.
+    #[inline]
+    pub fn insert<K: SketchKey>(&mut self, key: &K) -> bool {
+        let (fp, index) = self.fingerprint_and_index(key);
+        self.insert_fingerprint(index, fp)
+    }
+
+    /// Is `key` (probably) present?
+    #[inline]
+    pub fn contains<K: SketchKey>(&self, key: &K) -> bool {
+        let (fp, index) = self.fingerprint_and_index(key);
+        self.contains_fingerprint(index, fp)
+    }
+
+    /// Remove `key`, returning true if it was (probably) present. Removing a key that was never inserted may remove
+    /// another key with the same fingerprint.
+    pub fn remove<K: SketchKey>(&mut self, key: &K) -> bool {
+        let (fp, index) = self.fingerprint_and_index(key);
+        let alt = self.alt_index(index, fp);
+        if let Some((i, f)) = self.victim {
+            if f == fp && (i == index || i == alt) {
+                self.victim = None;
+                self.len -= 1;
+                return true;
+            }
+        }
+        let slot = [index, alt]
+            .iter()
+            .flat_map(|i| i * BUCKET_SIZE..(i + 1) * BUCKET_SIZE)
+            .find(|slot| self.fingerprints[*slot] == fp);
+        match slot {
+            Some(slot) => {
+                self.fingerprints[slot] = 0;
+                self.len -= 1;
+                // There is room for the victim now. The insert cannot fail since there is no victim, and counts the
+                // victim again.
+                if let Some((index, fp)) = self.victim.take() {
+                    self.len -= 1;
+                    let inserted = self.insert_fingerprint(index, fp);
+                    debug_assert!(inserted);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of keys in the filter.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of inserts (including those while merging) that failed because the filter was full.
+    pub fn failed_inserts(&self) -> usize {
+        self.failed
+    }
+
+    pub fn clear(&mut self) {
+        for f in self.fingerprints.iter_mut() {
+            *f = 0;
+        }
+        self.victim = None;
+        self.len = 0;
+        self.failed = 0;
+    }
+}
+
+impl Default for CuckooFilter {
+    fn default() -> CuckooFilter {
+        CuckooFilter::new(DEFAULT_BUCKETS)
+    }
+}
+
+impl Merge for CuckooFilter {
+    fn merge(&mut self, other: &CuckooFilter) {
+        assert_eq!(self.bits, other.bits, "Cannot merge cuckoo filters with different sizes");
+        for (slot, fp) in other.fingerprints.iter().enumerate() {
+            if *fp != 0 {
+                self.insert_fingerprint(slot / BUCKET_SIZE, *fp);
+            }
+        }
+        if let Some((index, fp)) = other.victim {
+            self.insert_fingerprint(index, fp);
+        }
+        self.failed += other.failed;
+    }
+
+    fn clear(&mut self) {
+        CuckooFilter::clear(self)
+    }
+}
+
+impl Checkpoint for CuckooFilter {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        try!(self.bits.checkpoint(out));
+        try!(checkpoint_slice(&self.fingerprints, out));
+        try!(self.victim.checkpoint(out));
+        try!(self.len.checkpoint(out));
+        self.failed.checkpoint(out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        try!(expect_checkpoint(
+            "cuckoo filter bucket bits",
+            try!(restore_value::<u32>(input)) as usize,
+            self.bits as usize
+        ));
+        try!(restore_slice(&mut self.fingerprints, input));
+        self.victim = try!(restore_value(input));
+        if self.victim.map_or(false, |(index, _)| index > self.mask()) {
+            return bad_checkpoint(String::from("cuckoo filter victim is out of range"));
+        }
+        try!(self.len.restore(input));
+        self.failed.restore(input)
+    }
+}
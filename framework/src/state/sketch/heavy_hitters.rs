@@ -0,0 +1,135 @@
+use super::{mix64, CountMinSketch, SketchKey};
+use common::*;
+use state::{bad_checkpoint, expect_checkpoint, read_len, restore_value, write_len, Checkpoint, Merge};
+use std::io::{Read, Write};
+use utils::Flow;
+
+const DEFAULT_HEAVY_HITTERS: usize = 32;
+
+/// Tracks the `k` keys with the largest counts (e.g., the flows sending the most bytes), using a `CountMinSketch` to
+/// count all keys and a fixed table of candidates. Counts are Count-Min estimates, so they may be overestimated.
+/// Trackers with the same dimensions are merged by merging the sketches and picking the top `k` of both candidate sets.
+#[derive(Clone, Debug)]
+pub struct HeavyHitters<K: SketchKey + Eq = Flow> {
+    k: usize,
+    sketch: CountMinSketch,
+    /// Candidates with their estimates, has room for `2k` entries so merging does not allocate.
+    candidates: Vec<(K, u64)>,
+}
+
+impl<K: SketchKey + Eq> HeavyHitters<K> {
+    /// Track `k` keys, counting with a Count-Min sketch with the given dimensions.
+    pub fn new(k: usize, width: usize, depth: usize) -> HeavyHitters<K> {
+        HeavyHitters::with_sketch(k, CountMinSketch::new(width, depth))
+    }
+
+    /// Track `k` keys, counting with `sketch`.
+    pub fn with_sketch(k: usize, sketch: CountMinSketch) -> HeavyHitters<K> {
+        assert!(k > 0, "Must track at least one heavy hitter");
+        HeavyHitters {
+            k: k,
+            sketch: sketch,
+            candidates: Vec::with_capacity(2 * k),
+        }
+    }
+
+    /// Add `count` to `key`.
+    #[inline]
+    pub fn add(&mut self, key: &K, count: u64) {
+        let estimate = self.sketch.add_hash(mix64(key.sketch_hash()), count);
+        if let Some(candidate) = self.candidates.iter_mut().find(|c| c.0 == *key) {
+            candidate.1 = estimate;
+            return;
+        }
+        if self.candidates.len() < self.k {
+            self.candidates.push((*key, estimate));
+            return;
+        }
+        let (smallest, _) = self.candidates
+            .iter()
+            .enumerate()
+            .min_by_key(|&(_, c)| c.1)
+            .unwrap();
+        if self.candidates[smallest].1 < estimate {
+            self.candidates[smallest] = (*key, estimate);
+        }
+    }
+
+    /// Estimated count for `key`.
+    #[inline]
+    pub fn estimate(&self, key: &K) -> u64 {
+        self.sketch.estimate(key)
+    }
+
+    /// Sum of all counts added.
+    pub fn total(&self) -> u64 {
+        self.sketch.total()
+    }
+
+    /// The heavy hitters and their estimated counts, largest first.
+    pub fn top(&self) -> Vec<(K, u64)> {
+        let mut top = self.candidates.clone();
+        top.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+        top
+    }
+
+    /// Heavy hitters that account for at least `fraction` of the total count, largest first.
+    pub fn above(&self, fraction: f64) -> Vec<(K, u64)> {
+        let threshold = (self.total() as f64 * fraction) as u64;
+        let mut top = self.top();
+        top.retain(|c| c.1 >= threshold);
+        top
+    }
+
+    pub fn clear(&mut self) {
+        self.sketch.clear();
+        self.candidates.clear();
+    }
+}
+
+impl<K: SketchKey + Eq> Default for HeavyHitters<K> {
+    fn default() -> HeavyHitters<K> {
+        HeavyHitters::with_sketch(DEFAULT_HEAVY_HITTERS, Default::default())
+    }
+}
+
+impl<K: SketchKey + Eq> Merge for HeavyHitters<K> {
+    fn merge(&mut self, other: &HeavyHitters<K>) {
+        assert_eq!(self.k, other.k, "Cannot merge heavy hitters tracking a different number of keys");
+        self.sketch.merge(&other.sketch);
+        for candidate in &other.candidates {
+            if !self.candidates.iter().any(|c| c.0 == candidate.0) {
+                self.candidates.push(*candidate);
+            }
+        }
+        for candidate in &mut self.candidates {
+            candidate.1 = self.sketch.estimate(&candidate.0);
+        }
+        self.candidates.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+        self.candidates.truncate(self.k);
+    }
+
+    fn clear(&mut self) {
+        HeavyHitters::clear(self)
+    }
+}
+
+impl<K: SketchKey + Eq + Default + Checkpoint> Checkpoint for HeavyHitters<K> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        try!(write_len(out, self.k));
+        try!(self.sketch.checkpoint(out));
+        self.candidates.checkpoint(out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        try!(expect_checkpoint("heavy hitters k", try!(read_len(input)), self.k));
+        try!(self.sketch.restore(input));
+        let candidates: Vec<(K, u64)> = try!(restore_value(input));
+        if candidates.len() > 2 * self.k {
+            return bad_checkpoint(String::from("too many heavy hitter candidates"));
+        }
+        self.candidates.clear();
+        self.candidates.extend(candidates);
+        Ok(())
+    }
+}
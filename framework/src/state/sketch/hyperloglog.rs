@@ -0,0 +1,127 @@
+use common::*;
+use state::{checkpoint_slice, expect_checkpoint, restore_slice, restore_value, Checkpoint, Merge};
+use super::{mix64, SketchKey};
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use twox_hash::XxHash;
+
+const DEFAULT_HLL_PRECISION: u8 = 12;
+
+/// Estimates the number of distinct values seen (e.g., distinct sources talking to a destination) using `2^precision`
+/// one byte registers, with a standard error of about `1.04 / sqrt(2^precision)`: 1.6% for the default precision of
+/// 12. Only sketches with the same precision can be merged.
+#[derive(Clone, Debug)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Box<[u8]>,
+}
+
+impl HyperLogLog {
+    /// Create a sketch with `2^precision` registers, `precision` must be between 4 and 16.
+    pub fn new(precision: u8) -> HyperLogLog {
+        assert!(precision >= 4 && precision <= 16, "HyperLogLog precision must be in [4, 16]");
+        HyperLogLog {
+            precision: precision,
+            registers: vec![0; 1 << precision].into_boxed_slice(),
+        }
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// Record `value`.
+    #[inline]
+    pub fn insert<T: Hash + ?Sized>(&mut self, value: &T) {
+        let mut hasher = XxHash::default();
+        value.hash(&mut hasher);
+        self.insert_hash(hasher.finish());
+    }
+
+    /// Record `key`, hashing it with `crc_hash` rather than `Hash`.
+    #[inline]
+    pub fn insert_key<K: SketchKey>(&mut self, key: &K) {
+        self.insert_hash(mix64(key.sketch_hash()));
+    }
+
+    /// Record a value given its (well mixed) 64-bit hash.
+    #[inline]
+    pub fn insert_hash(&mut self, hash: u64) {
+        let p = self.precision as u32;
+        let idx = (hash >> (64 - p)) as usize;
+        // The guard bit bounds the rank when the remaining bits are all zero.
+        let rank = ((hash << p) | (1 << (p - 1))).leading_zeros() as u8 + 1;
+        if self.registers[idx] < rank {
+            self.registers[idx] = rank;
+        }
+    }
+
+    /// Estimated number of distinct values.
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let mut sum = 0.0;
+        let mut zeros = 0;
+        for &r in self.registers.iter() {
+            sum += 1.0 / ((1u64 << r) as f64);
+            if r == 0 {
+                zeros += 1;
+            }
+        }
+        let estimate = alpha * m * m / sum;
+        if estimate <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate for small cardinalities.
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for r in self.registers.iter_mut() {
+            *r = 0;
+        }
+    }
+}
+
+impl Default for HyperLogLog {
+    fn default() -> HyperLogLog {
+        HyperLogLog::new(DEFAULT_HLL_PRECISION)
+    }
+}
+
+impl Merge for HyperLogLog {
+    fn merge(&mut self, other: &HyperLogLog) {
+        assert_eq!(self.precision, other.precision, "Cannot merge HyperLogLogs with different precision");
+        for (r, o) in self.registers.iter_mut().zip(other.registers.iter()) {
+            if *r < *o {
+                *r = *o;
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        HyperLogLog::clear(self)
+    }
+}
+
+impl Checkpoint for HyperLogLog {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        try!(self.precision.checkpoint(out));
+        checkpoint_slice(&self.registers, out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        try!(expect_checkpoint(
+            "HyperLogLog precision",
+            try!(restore_value::<u8>(input)) as usize,
+            self.precision as usize
+        ));
+        restore_slice(&mut self.registers, input)
+    }
+}
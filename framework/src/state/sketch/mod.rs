@@ -0,0 +1,76 @@
+// Probabilistic structures for line-rate measurement. Sketches allocate all their memory when created, so they can be
+// updated from `transform` or `map` closures without allocating, and all of them implement `Merge` so that per-core
+// instances can be combined on the control plane (see `SketchCP`). Sketches are keyed by `SketchKey`s, which are
+// hashed with `crc_hash`.
+pub use self::bloom::*;
+pub use self::count_min::*;
+pub use self::cuckoo::*;
+pub use self::heavy_hitters::*;
+pub use self::hyperloglog::*;
+pub use self::shared::*;
+use utils::{crc_hash, Flow};
+
+mod bloom;
+mod count_min;
+mod cuckoo;
+mod heavy_hitters;
+mod hyperloglog;
+mod shared;
+
+const SKETCH_SEED: u32 = 0x9e37_79b9;
+
+/// Keys that can be recorded in sketches. Keys are hashed (by default) by running `crc_hash` over their bytes, so
+/// implementations using the default must be plain data without padding or pointers, e.g., `Flow`, addresses or
+/// ports.
+pub trait SketchKey: Copy {
+    #[inline]
+    fn sketch_hash(&self) -> u32 {
+        crc_hash(self, SKETCH_SEED)
+    }
+}
+
+impl SketchKey for Flow {}
+impl SketchKey for u8 {}
+impl SketchKey for u16 {}
+impl SketchKey for u32 {}
+impl SketchKey for u64 {}
+
+/// Spread a 32-bit key hash over 64 bits (the splitmix64 finalizer). CRC32 is linear, so hashes for the same key with
+/// different seeds are not independent. Instead sketches derive the hashes they need from this.
+#[inline]
+pub(crate) fn mix64(hash: u32) -> u64 {
+    let mut z = (hash as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Odd multipliers for the per-row (multiply-shift) hashes used by `CountMinSketch`.
+const ROW_MULTIPLIERS: [u64; 8] = [
+    0x9e37_79b9_7f4a_7c15,
+    0xc2b2_ae3d_27d4_eb4f,
+    0x1656_67b1_9e37_79f9,
+    0xd6e8_feb8_6659_fd93,
+    0xff51_afd7_ed55_8ccd,
+    0xc4ce_b9fe_1a85_ec53,
+    0x8cb9_2ba7_2f3d_8dd7,
+    0xa076_1d64_78bd_642f,
+];
+
+/// Index of `hash` (from `mix64`) in row `row` of a table with `2^bits` columns.
+#[inline]
+pub(crate) fn row_index(hash: u64, row: usize, bits: u32) -> usize {
+    if bits == 0 {
+        0
+    } else {
+        (hash.wrapping_mul(ROW_MULTIPLIERS[row]) >> (64 - bits)) as usize
+    }
+}
+
+pub(crate) const MAX_ROWS: usize = 8;
+
+/// Number of bits needed to index `n` (rounded up to a power of two) entries.
+#[inline]
+pub(crate) fn index_bits(n: usize) -> u32 {
+    n.next_power_of_two().trailing_zeros()
+}
@@ -0,0 +1,116 @@
+use common::*;
+use state::merge_store::{Publisher, PUBLISH_INTERVAL};
+use state::{Checkpoint, Merge};
+use std::io::{Read, Write};
+use std::sync::{Arc, RwLock};
+
+/// Control plane view of a sketch (or any other `Merge` value) updated by several cores. Each data plane instance
+/// (`SketchDP`) updates a private copy and periodically merges it into its shard, and `sync` merges all shards, much as
+/// `MergeableStoreCP::sync` does for flow stores. All instances are copies of the same template, so they have the same
+/// dimensions and can be merged.
+pub struct SketchCP<V: Merge> {
+    merged: V,
+    /// Sketch restored from a checkpoint, merged with the shards when syncing.
+    restored: V,
+    template: V,
+    shards: Vec<Arc<RwLock<V>>>,
+}
+
+impl<V: Merge> SketchCP<V> {
+    /// Create a sketch whose instances are (empty) copies of `template`.
+    pub fn new(template: V) -> SketchCP<V> {
+        let mut template = template;
+        template.clear();
+        SketchCP {
+            merged: template.clone(),
+            restored: template.clone(),
+            template: template,
+            shards: Vec::new(),
+        }
+    }
+
+    /// Create a data plane instance that publishes its updates every `publish_interval` updates.
+    pub fn dp_sketch_with_interval(&mut self, publish_interval: usize) -> SketchDP<V> {
+        let shard = Arc::new(RwLock::new(self.template.clone()));
+        self.shards.push(shard.clone());
+        SketchDP {
+            local: self.template.clone(),
+            publisher: Publisher::new(shard, publish_interval),
+        }
+    }
+
+    pub fn dp_sketch(&mut self) -> SketchDP<V> {
+        self.dp_sketch_with_interval(PUBLISH_INTERVAL)
+    }
+
+    /// Merge all shards. Shards that are being written to are skipped until the next sync.
+    pub fn sync(&mut self) {
+        self.merged.clone_from(&self.restored);
+        for shard in &self.shards {
+            if let Ok(g) = shard.try_read() {
+                self.merged.merge(&g);
+            }
+        }
+    }
+
+    /// The merged sketch, as of the last `sync`.
+    pub fn get(&self) -> &V {
+        &self.merged
+    }
+
+    /// Reset every shard and any restored sketch, e.g., at the start of a measurement epoch. Updates not yet published
+    /// by the data plane are kept.
+    pub fn reset(&mut self) {
+        for shard in &self.shards {
+            if let Ok(mut g) = shard.write() {
+                g.clear();
+            }
+        }
+        self.restored.clear();
+        self.merged.clear();
+    }
+}
+
+/// Checkpoints hold the merged sketch as of the last `sync`, see `MergeStoreCP`. The restored sketch replaces any
+/// previously restored one and is merged with later updates, and must have the template's dimensions.
+impl<V: Merge + Checkpoint> Checkpoint for SketchCP<V> {
+    fn checkpoint(&self, out: &mut Write) -> Result<()> {
+        self.merged.checkpoint(out)
+    }
+
+    fn restore(&mut self, input: &mut Read) -> Result<()> {
+        let mut restored = self.template.clone();
+        try!(restored.restore(input));
+        self.reset();
+        self.merged = restored.clone();
+        self.restored = restored;
+        Ok(())
+    }
+}
+
+/// A per-core instance of a `SketchCP`. Updating it does not allocate or take locks, except when publishing.
+pub struct SketchDP<V: Merge> {
+    local: V,
+    publisher: Publisher<V>,
+}
+
+impl<V: Merge> SketchDP<V> {
+    /// Update the sketch, e.g., `sketch.update(|s| s.add(&flow, len))`.
+    #[inline]
+    pub fn update<F: FnOnce(&mut V)>(&mut self, update: F) {
+        update(&mut self.local);
+        let local = &mut self.local;
+        self.publisher.updated(|shard| SketchDP::merge_local(shard, local));
+    }
+
+    fn merge_local(shard: &mut V, local: &mut V) {
+        shard.merge(local);
+        local.clear();
+    }
+
+    /// Publish all pending updates, waiting for the control plane if necessary.
+    pub fn publish(&mut self) {
+        let local = &mut self.local;
+        self.publisher.publish(|shard| SketchDP::merge_local(shard, local));
+    }
+}
@@ -0,0 +1,25 @@
+/// Add `data` to a running ones' complement sum (RFC 1071), treating it as a sequence of big-endian 16 bit words. An
+/// odd trailing byte is padded with zero, so only the last piece of a checksummed region may have an odd length.
+#[inline]
+pub fn checksum_add(data: &[u8], sum: u32) -> u32 {
+    let mut sum = sum as u64;
+    for word in data.chunks(2) {
+        let hi = word[0] as u64;
+        let lo = if word.len() > 1 { word[1] as u64 } else { 0 };
+        sum += (hi << 8) | lo;
+    }
+    while sum >> 32 != 0 {
+        sum = (sum & 0xffff_ffff) + (sum >> 32);
+    }
+    sum as u32
+}
+
+/// Fold a running sum into the final 16 bit checksum (in host byte order, as expected by the header setters).
+#[inline]
+pub fn checksum_finish(sum: u32) -> u16 {
+    let mut sum = sum;
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
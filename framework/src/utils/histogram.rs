@@ -0,0 +1,180 @@
+use std::cmp::max;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const DEFAULT_PRECISION: u32 = 7;
+
+/// A log-linear (HDR-style) histogram of latencies in nanoseconds. Each power of two is split into `2^(precision - 1)`
+/// equally sized buckets, so recorded values are accurate to within a relative error of `2^-(precision - 1)`, while
+/// covering the full `u64` range in a few thousand buckets. Recording is lock-free, so a histogram can be shared
+/// between the core recording samples and a thread reporting on them.
+pub struct LatencyHistogram {
+    precision: u32,
+    buckets: Vec<AtomicUsize>,
+    count: AtomicUsize,
+    sum: AtomicUsize,
+}
+
+impl LatencyHistogram {
+    /// Create a histogram with 7 bits of precision (values accurate to within 1.6%).
+    pub fn new() -> LatencyHistogram {
+        LatencyHistogram::with_precision(DEFAULT_PRECISION)
+    }
+
+    /// Create a histogram using `precision` bits for each power of two. `precision` must be between 1 and 16.
+    pub fn with_precision(precision: u32) -> LatencyHistogram {
+        assert!(precision >= 1 && precision <= 16, "Precision must be between 1 and 16 bits");
+        let len = bucket_index(precision, u64::max_value()) + 1;
+        LatencyHistogram {
+            precision: precision,
+            buckets: (0..len).map(|_| AtomicUsize::new(0)).collect(),
+            count: AtomicUsize::new(0),
+            sum: AtomicUsize::new(0),
+        }
+    }
+
+    /// Record a single value.
+    #[inline]
+    pub fn record(&self, value: u64) {
+        self.record_n(value, 1)
+    }
+
+    /// Record `n` occurrences of `value`.
+    #[inline]
+    pub fn record_n(&self, value: u64, n: usize) {
+        self.buckets[bucket_index(self.precision, value)].fetch_add(n, Ordering::Relaxed);
+        self.count.fetch_add(n, Ordering::Relaxed);
+        self.sum.fetch_add((value as usize).wrapping_mul(n), Ordering::Relaxed);
+    }
+
+    /// Number of recorded values.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Mean of recorded values, 0 if the histogram is empty.
+    pub fn mean(&self) -> f64 {
+        match self.count() {
+            0 => 0.0,
+            count => self.sum.load(Ordering::Relaxed) as f64 / count as f64,
+        }
+    }
+
+    /// Smallest recorded value (lower bound of its bucket), 0 if the histogram is empty.
+    pub fn min(&self) -> u64 {
+        self.buckets
+            .iter()
+            .position(|b| b.load(Ordering::Relaxed) > 0)
+            .map_or(0, |idx| bucket_low(self.precision, idx))
+    }
+
+    /// Largest recorded value (upper bound of its bucket), 0 if the histogram is empty.
+    pub fn max(&self) -> u64 {
+        self.buckets
+            .iter()
+            .rposition(|b| b.load(Ordering::Relaxed) > 0)
+            .map_or(0, |idx| bucket_high(self.precision, idx))
+    }
+
+    /// Value at `percentile` (between 0 and 100), i.e., the upper bound of the bucket containing that rank. 0 if the
+    /// histogram is empty.
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        let count = self.count();
+        if count == 0 {
+            return 0;
+        }
+        let percentile = percentile.max(0.0).min(100.0);
+        let rank = max(1, ((percentile / 100.0) * count as f64).ceil() as usize);
+        let mut seen = 0;
+        for (idx, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket.load(Ordering::Relaxed);
+            if seen >= rank {
+                return bucket_high(self.precision, idx);
+            }
+        }
+        self.max()
+    }
+
+    /// Add all values recorded in `other` to this histogram. Both histograms must have the same precision.
+    pub fn merge(&self, other: &LatencyHistogram) {
+        assert_eq!(self.precision, other.precision, "Cannot merge histograms with different precision");
+        for (mine, theirs) in self.buckets.iter().zip(other.buckets.iter()) {
+            let n = theirs.load(Ordering::Relaxed);
+            if n > 0 {
+                mine.fetch_add(n, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(other.count.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.sum.fetch_add(other.sum.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    /// Clear all recorded values. Values recorded concurrently with a reset may be partially lost.
+    pub fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.count.store(0, Ordering::Relaxed);
+        self.sum.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> LatencyHistogram {
+        LatencyHistogram::new()
+    }
+}
+
+impl fmt::Display for LatencyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "count {} min {} mean {:.0} p50 {} p90 {} p99 {} p99.9 {} max {} (ns)",
+            self.count(),
+            self.min(),
+            self.mean(),
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0),
+            self.percentile(99.9),
+            self.max()
+        )
+    }
+}
+
+// Values below 2^precision get a bucket each, above that every power of two is split into 2^(precision - 1) buckets.
+#[inline]
+fn bucket_index(precision: u32, value: u64) -> usize {
+    let linear = 1u64 << precision;
+    if value < linear {
+        value as usize
+    } else {
+        let half = 1usize << (precision - 1);
+        let shift = (63 - value.leading_zeros()) - precision + 1;
+        (shift as usize) * half + (value >> shift) as usize
+    }
+}
+
+#[inline]
+fn bucket_low(precision: u32, idx: usize) -> u64 {
+    if idx < (1usize << precision) {
+        idx as u64
+    } else {
+        let half = 1usize << (precision - 1);
+        let shift = idx / half - 1;
+        ((idx - shift * half) as u64) << shift
+    }
+}
+
+#[inline]
+fn bucket_high(precision: u32, idx: usize) -> u64 {
+    if idx < (1usize << precision) {
+        idx as u64
+    } else {
+        let half = 1usize << (precision - 1);
+        let shift = idx / half - 1;
+        let sub = (idx - shift * half) as u64;
+        // Subtract first so that the last bucket's bound (u64::MAX) does not overflow.
+        (sub << shift) - 1 + (1 << shift)
+    }
+}
@@ -1,7 +1,13 @@
 pub use self::asm::*;
+pub use self::checksum::*;
 pub use self::flow::*;
+pub use self::histogram::*;
+pub use self::tsc::*;
 mod flow;
 mod asm;
+mod checksum;
+mod histogram;
+mod tsc;
 
 pub const PAGE_SIZE: usize = 4096; // Page size in bytes, not using huge pages here.
 
@@ -0,0 +1,77 @@
+use super::rdtsc_unsafe;
+use native::zcsi::tsc_frequency;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const CALIBRATION_MS: u64 = 100;
+
+struct TscClock {
+    hz: u64,
+    ns_per_cycle: f64,
+    // TSC reading (in nanoseconds) and wall clock time taken at calibration, used to convert to UNIX time.
+    tsc_ns_at_epoch: u64,
+    unix_ns_at_epoch: u64,
+}
+
+lazy_static! {
+    static ref CLOCK: TscClock = TscClock::calibrate();
+}
+
+fn unix_now_ns() -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("System time before UNIX epoch");
+    now.as_secs() * 1_000_000_000 + now.subsec_nanos() as u64
+}
+
+impl TscClock {
+    fn calibrate() -> TscClock {
+        // Prefer the EAL's estimate, fall back to measuring against the OS clock when DPDK is not initialized.
+        let hz = match unsafe { tsc_frequency() } {
+            0 => {
+                let start = rdtsc_unsafe();
+                thread::sleep(Duration::from_millis(CALIBRATION_MS));
+                (rdtsc_unsafe() - start) * (1000 / CALIBRATION_MS)
+            }
+            hz => hz,
+        };
+        let ns_per_cycle = 1e9 / (hz as f64);
+        let unix_ns = unix_now_ns();
+        let tsc = rdtsc_unsafe();
+        TscClock {
+            hz: hz,
+            ns_per_cycle: ns_per_cycle,
+            tsc_ns_at_epoch: ((tsc as f64) * ns_per_cycle) as u64,
+            unix_ns_at_epoch: unix_ns,
+        }
+    }
+}
+
+/// Calibrated TSC frequency in Hz. The first call calibrates, which may take up to 100ms if DPDK is not initialized.
+#[inline]
+pub fn tsc_hz() -> u64 {
+    CLOCK.hz
+}
+
+/// Convert a TSC reading (or a difference between readings) to nanoseconds.
+#[inline]
+pub fn tsc_to_ns(cycles: u64) -> u64 {
+    ((cycles as f64) * CLOCK.ns_per_cycle) as u64
+}
+
+/// Convert nanoseconds to TSC cycles.
+#[inline]
+pub fn ns_to_tsc(ns: u64) -> u64 {
+    ((ns as f64) / CLOCK.ns_per_cycle) as u64
+}
+
+/// Current time in nanoseconds according to the TSC. The epoch is arbitrary (usually boot), use `tsc_ns_to_unix_ns`
+/// to get wall clock time.
+#[inline]
+pub fn tsc_now_ns() -> u64 {
+    tsc_to_ns(rdtsc_unsafe())
+}
+
+/// Convert a TSC based timestamp in nanoseconds (e.g., `Packet::rx_timestamp`) to nanoseconds since the UNIX epoch.
+#[inline]
+pub fn tsc_ns_to_unix_ns(ns: u64) -> u64 {
+    (CLOCK.unix_ns_at_epoch + ns).wrapping_sub(CLOCK.tsc_ns_at_epoch)
+}
@@ -0,0 +1,109 @@
+extern crate e2d2;
+#[macro_use]
+extern crate lazy_static;
+use e2d2::headers::*;
+use e2d2::interface::dpdk::*;
+use e2d2::interface::*;
+use std::sync::{Mutex, MutexGuard, Once, ONCE_INIT};
+
+static INIT: Once = ONCE_INIT;
+
+lazy_static! {
+    // Tests share core 0's mempool cache, which is not thread safe.
+    static ref CORE: Mutex<()> = Mutex::new(());
+}
+
+fn setup() -> MutexGuard<'static, ()> {
+    let guard = CORE.lock().unwrap_or_else(|e| e.into_inner());
+    INIT.call_once(|| init_system_wl("chained_packets", 0, &[]));
+    init_thread(0, 0);
+    guard
+}
+
+/// Build a packet with one segment per entry in `segments`.
+fn chained(segments: &[&[u8]]) -> Packet<NullHeader, EmptyMetadata> {
+    let mut pkts: Vec<_> = segments
+        .iter()
+        .map(|data| {
+            let mut pkt = new_packet().unwrap();
+            pkt.add_to_payload_tail(data.len()).unwrap();
+            pkt.write_bytes(0, data).unwrap();
+            pkt
+        })
+        .collect();
+    let mut head = pkts.remove(0);
+    for pkt in pkts {
+        head.append_segment(pkt).unwrap();
+    }
+    head
+}
+
+fn contents<T: EndOffset, M: Sized + Send>(pkt: &Packet<T, M>) -> Vec<u8> {
+    let mut buf = vec![0; pkt.pkt_len()];
+    assert_eq!(pkt.read_bytes(0, &mut buf), buf.len());
+    buf
+}
+
+fn segment_lens<T: EndOffset, M: Sized + Send>(pkt: &Packet<T, M>) -> Vec<usize> {
+    pkt.segments().map(|s| s.len()).collect()
+}
+
+#[test]
+fn trim_crosses_segments() {
+    let _core = setup();
+    let mut pkt = chained(&[b"hello", b"world", b"!!"]);
+    assert_eq!(pkt.trim_payload_size(4), 4);
+    assert_eq!(contents(&pkt), b"hellowor");
+    assert_eq!(segment_lens(&pkt), vec![5, 3]);
+    pkt.remove_from_payload_tail(4).unwrap();
+    assert_eq!(contents(&pkt), b"hell");
+    assert!(!pkt.is_segmented());
+    assert!(pkt.remove_from_payload_tail(5).is_err());
+    pkt.free_packet();
+}
+
+#[test]
+fn grow_last_segment() {
+    let _core = setup();
+    let mut pkt = chained(&[b"hello", b"world"]);
+    assert_eq!(pkt.increase_payload_size(3), 3);
+    pkt.write_bytes(10, b"abc").unwrap();
+    assert_eq!(contents(&pkt), b"helloworldabc");
+    assert_eq!(segment_lens(&pkt), vec![5, 8]);
+    pkt.free_packet();
+}
+
+#[test]
+fn parse_pulls_up_header() {
+    let _core = setup();
+    let mut frame = [0u8; 17];
+    frame[12] = 0x08;
+    frame[14..].copy_from_slice(b"xyz");
+    let pkt = chained(&[&frame[..6], &frame[6..10], &frame[10..]]);
+    let pkt = pkt.parse_header::<MacHeader>();
+    assert_eq!(pkt.get_header().etype(), 0x0800);
+    assert_eq!(segment_lens(&pkt), vec![14, 3]);
+    assert_eq!(contents(&pkt), &frame[..]);
+    pkt.free_packet();
+}
+
+#[test]
+fn short_header_is_rejected() {
+    let _core = setup();
+    let mut pkt = chained(&[b"hello", b"world"]);
+    assert!(pkt.make_header_contiguous(11).is_err());
+    pkt.make_header_contiguous(10).unwrap();
+    assert_eq!(segment_lens(&pkt), vec![10]);
+    pkt.free_packet();
+}
+
+#[test]
+fn copy_chained_payload() {
+    let _core = setup();
+    let other = chained(&[b"hello", b"world"]);
+    let mut pkt = chained(&[b"x"]);
+    assert_eq!(pkt.copy_payload(&other), 10);
+    assert_eq!(contents(&pkt), b"helloworld");
+    pkt.free_packet();
+    other.free_packet();
+}
@@ -0,0 +1,133 @@
+extern crate e2d2;
+mod common;
+use common::flow;
+use e2d2::state::*;
+use std::collections::HashMap;
+
+#[test]
+fn state_is_restored_in_order() {
+    let table = FlowTable::with_capacity(1024);
+    let mut nat = HashMap::new();
+    let mut sketch = CountMinSketch::new(1024, 4);
+    for i in 0..100 {
+        table.insert(flow(i), i as u64);
+        nat.insert(flow(i), flow(i).reverse_flow());
+        sketch.add(&flow(i), 3);
+    }
+    let mut out = Vec::new();
+    write_checkpoint(&[&table, &nat, &sketch], &mut out).unwrap();
+
+    let mut restored_table = FlowTable::<u64>::with_capacity(1024);
+    let mut restored_nat = HashMap::new();
+    let mut restored_sketch = CountMinSketch::new(1024, 4);
+    read_checkpoint(
+        &mut [&mut restored_table, &mut restored_nat, &mut restored_sketch],
+        &mut &out[..],
+    ).unwrap();
+    assert_eq!(restored_table.len(), 100);
+    assert_eq!(restored_table.get(&flow(42)), Some(42));
+    assert_eq!(restored_nat, nat);
+    assert_eq!(restored_sketch.estimate(&flow(42)), sketch.estimate(&flow(42)));
+
+    // State must be restored in the same order, and into instances with the same dimensions.
+    assert!(read_checkpoint(&mut [&mut restored_nat, &mut restored_table], &mut &out[..]).is_err());
+    let mut small_sketch = CountMinSketch::new(512, 4);
+    assert!(read_checkpoint(
+        &mut [&mut restored_table, &mut restored_nat, &mut small_sketch],
+        &mut &out[..],
+    ).is_err());
+}
+
+#[test]
+fn reordered_buffer_keeps_out_of_order_data() {
+    let mut buffer = ReorderedBuffer::new(4096).unwrap();
+    buffer.seq(100, b"hello");
+    buffer.add_data(110, b"world");
+    buffer.add_data(130, b"!!");
+    let mut out = Vec::new();
+    buffer.checkpoint(&mut out).unwrap();
+
+    let mut restored = ReorderedBuffer::new(4096).unwrap();
+    restored.restore(&mut &out[..]).unwrap();
+    restored.add_data(105, b"-----");
+    let mut data = [0; 64];
+    let read = restored.read_data(&mut data);
+    assert_eq!(&data[..read], b"hello-----world");
+    restored.add_data(115, b"0123456789abcde");
+    let read = restored.read_data(&mut data);
+    assert_eq!(&data[..read], b"0123456789abcde!!");
+}
+
+#[test]
+fn merge_store_restores_into_new_instance() {
+    let mut store: MergeStoreCP<Sum<u64>> = MergeStoreCP::new();
+    {
+        let mut dp = store.dp_store();
+        dp.merge(flow(1), &Sum(5));
+        dp.publish();
+    }
+    store.sync();
+    let mut out = Vec::new();
+    store.checkpoint(&mut out).unwrap();
+
+    let mut restored: MergeStoreCP<Sum<u64>> = MergeStoreCP::new();
+    restored.restore(&mut &out[..]).unwrap();
+    // Restoring again replaces the restored values rather than adding to them.
+    restored.restore(&mut &out[..]).unwrap();
+    let mut dp = restored.dp_store();
+    dp.merge(flow(1), &Sum(2));
+    dp.publish();
+    restored.sync();
+    assert_eq!(restored.get(&flow(1)), Sum(7));
+}
+
+#[test]
+fn mergeable_store_adds_updates_to_restored_values() {
+    let mut store: MergeableStoreCP<u64> = MergeableStoreCP::new();
+    {
+        let mut dp = store.dp_store_with_cache_and_size(1, 16);
+        dp.update(flow(1), 5);
+    }
+    store.sync();
+    let mut out = Vec::new();
+    store.checkpoint(&mut out).unwrap();
+
+    let mut restored: MergeableStoreCP<u64> = MergeableStoreCP::new();
+    let mut dp = restored.dp_store_with_cache_and_size(1, 16);
+    restored.restore(&mut &out[..]).unwrap();
+    restored.restore(&mut &out[..]).unwrap();
+    restored.sync();
+    assert_eq!(restored.get(&flow(1)), 5);
+    dp.update(flow(1), 2);
+    dp.update(flow(2), 3);
+    restored.sync();
+    assert_eq!(restored.get(&flow(1)), 7);
+    assert_eq!(restored.get(&flow(2)), 3);
+    assert_eq!(restored.len(), 2);
+}
+
+#[test]
+fn sketch_restores_once() {
+    let mut sketch = SketchCP::new(CountMinSketch::new(1024, 4));
+    {
+        let mut dp = sketch.dp_sketch();
+        dp.update(|s| {
+            s.add(&flow(1), 5);
+        });
+        dp.publish();
+    }
+    sketch.sync();
+    let mut out = Vec::new();
+    sketch.checkpoint(&mut out).unwrap();
+
+    let mut restored = SketchCP::new(CountMinSketch::new(1024, 4));
+    restored.restore(&mut &out[..]).unwrap();
+    restored.restore(&mut &out[..]).unwrap();
+    let mut dp = restored.dp_sketch();
+    dp.update(|s| {
+        s.add(&flow(1), 2);
+    });
+    dp.publish();
+    restored.sync();
+    assert_eq!(restored.get().estimate(&flow(1)), 7);
+}
@@ -0,0 +1,34 @@
+extern crate e2d2;
+use e2d2::utils::*;
+
+// Example IPv4 header with the checksum field zeroed.
+const IP_HEADER: [u8; 20] = [
+    0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00,
+    0xc7,
+];
+
+#[test]
+fn ipv4_header_checksum() {
+    assert_eq!(checksum_finish(checksum_add(&IP_HEADER, 0)), 0xb861);
+}
+
+#[test]
+fn checksum_verifies_to_zero() {
+    let mut header = IP_HEADER;
+    header[10] = 0xb8;
+    header[11] = 0x61;
+    assert_eq!(checksum_finish(checksum_add(&header, 0)), 0);
+}
+
+#[test]
+fn checksum_in_pieces() {
+    let whole = checksum_finish(checksum_add(&IP_HEADER, 0));
+    let split = checksum_finish(checksum_add(&IP_HEADER[12..], checksum_add(&IP_HEADER[..12], 0)));
+    assert_eq!(whole, split);
+}
+
+#[test]
+fn checksum_odd_length() {
+    // A trailing byte is padded with zero.
+    assert_eq!(checksum_add(&[0x12, 0x34, 0x56], 0), 0x1234 + 0x5600);
+}
@@ -0,0 +1,12 @@
+use e2d2::utils::Flow;
+
+/// A TCP flow to port 80, distinguished by its source address.
+pub fn flow(i: u32) -> Flow {
+    Flow {
+        src_ip: i,
+        dst_ip: 0x0a000001,
+        src_port: 1024,
+        dst_port: 80,
+        proto: 6,
+    }
+}
@@ -0,0 +1,81 @@
+extern crate e2d2;
+mod common;
+use common::flow;
+use e2d2::state::*;
+use std::cell::Cell;
+use std::time::Duration;
+
+thread_local!(static NOW: Cell<u64> = Cell::new(0));
+
+/// Clock for stores under test, advanced with `advance`. Each test runs on its own thread.
+fn clock() -> u64 {
+    NOW.with(|now| now.get())
+}
+
+fn advance(ns: u64) {
+    NOW.with(|now| now.set(now.get() + ns));
+}
+
+#[test]
+fn max_flows_evicts_least_recently_updated() {
+    let policy = ExpiryPolicy::with_max_flows(64).with_clock(clock);
+    let mut store = DpMergeableStore::<usize>::with_expiry(1, 128, policy);
+    for i in 0..100 {
+        advance(1);
+        store.update(flow(i), 1);
+    }
+    store.expire();
+    // Evicting down to 7/8 of the cap whenever it is exceeded leaves the most recently updated flows.
+    let mut kept: Vec<_> = store.iter().map(|(f, _)| f.src_ip).collect();
+    kept.sort();
+    assert!(kept.len() > 56 && kept.len() <= 64);
+    assert_eq!(kept, (100 - kept.len() as u32..100).collect::<Vec<_>>());
+}
+
+#[test]
+fn ttl_expires_idle_flows() {
+    let policy = ExpiryPolicy::with_ttl(Duration::from_millis(20)).with_clock(clock);
+    let mut store = DpMergeableStore::<usize>::with_expiry(1, 16, policy);
+    advance(1);
+    store.update(flow(1), 1);
+    advance(10_000_000);
+    store.update(flow(2), 1);
+    store.expire();
+    assert_eq!(store.len(), 2);
+    advance(15_000_000);
+    store.expire();
+    assert_eq!(store.len(), 1);
+    assert!(store.iter().any(|(f, _)| *f == flow(2)));
+}
+
+#[test]
+fn cp_store_expires_on_sync() {
+    let mut cp = MergeableStoreCP::<usize>::new_with_expiry(ExpiryPolicy::with_max_flows(8).with_clock(clock));
+    let mut dp = cp.dp_store_with_cache_and_size(1, 64);
+    for i in 0..32 {
+        advance(1);
+        dp.update(flow(i), 1);
+    }
+    cp.sync();
+    // Over the cap, flows are evicted down to 7/8 of it, oldest first.
+    let mut kept: Vec<_> = cp.iter().map(|(f, _)| f.src_ip).collect();
+    kept.sort();
+    assert_eq!(kept, (25..32).collect::<Vec<_>>());
+    // Removals are propagated, so the next sync does not bring the flows back.
+    cp.sync();
+    assert_eq!(cp.len(), 7);
+}
+
+#[test]
+fn cp_channel_store_expires_idle_flows() {
+    let policy = ExpiryPolicy::with_ttl(Duration::from_millis(20)).with_clock(clock);
+    let (mut dp, mut cp) = new_cp_mergeable_store_with_expiry::<usize>(1, 16, policy);
+    advance(1);
+    dp.update(flow(1), 1);
+    cp.recv();
+    advance(30_000_000);
+    dp.update(flow(2), 1);
+    cp.recv();
+    assert_eq!(cp.len(), 1);
+    assert_eq!(cp.get(&flow(2)), 1);
+}
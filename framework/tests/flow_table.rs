@@ -0,0 +1,50 @@
+extern crate e2d2;
+mod common;
+use common::flow;
+use e2d2::state::FlowTable;
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn insert_get_remove() {
+    let table = FlowTable::with_capacity(1024);
+    assert!(table.insert(flow(1), 10u32));
+    assert_eq!(table.get_or_insert(flow(1), 20), Some(10));
+    assert_eq!(table.update(flow(1), |v| v.unwrap_or(0) + 1), Some(11));
+    assert_eq!(table.get(&flow(1)), Some(11));
+    assert_eq!(table.get(&flow(2)), None);
+    assert_eq!(table.remove(&flow(1)), Some(11));
+    assert_eq!(table.get(&flow(1)), None);
+    assert!(table.is_empty());
+}
+
+#[test]
+fn inserts_fail_when_full() {
+    let table = FlowTable::with_capacity(16);
+    let inserted = (0..1000).filter(|i| table.insert(flow(*i), *i)).count();
+    assert_eq!(inserted, table.len());
+    assert!(inserted <= table.capacity());
+    assert!((0..1000).filter(|i| table.get(&flow(*i)) == Some(*i)).count() == inserted);
+}
+
+#[test]
+fn shared_between_threads() {
+    let table = Arc::new(FlowTable::with_capacity(1 << 16));
+    let threads: Vec<_> = (0..4u32)
+        .map(|t| {
+            let table = table.clone();
+            thread::spawn(move || {
+                for i in 0..10_000 {
+                    assert!(table.insert(flow(i * 4 + t), i));
+                    table.update(flow(i % 100), |v| v.unwrap_or(0) + 1);
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    for i in 100..40_000u32 {
+        assert_eq!(table.get(&flow(i)), Some(i / 4));
+    }
+}
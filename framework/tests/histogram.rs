@@ -0,0 +1,42 @@
+extern crate e2d2;
+use e2d2::utils::*;
+
+#[test]
+fn small_values_are_exact() {
+    let histogram = LatencyHistogram::new();
+    for value in 0..100 {
+        histogram.record(value);
+    }
+    assert_eq!(histogram.count(), 100);
+    assert_eq!(histogram.min(), 0);
+    assert_eq!(histogram.max(), 99);
+    assert_eq!(histogram.percentile(50.0), 49);
+    assert_eq!(histogram.percentile(100.0), 99);
+}
+
+#[test]
+fn large_values_within_precision() {
+    let histogram = LatencyHistogram::new();
+    let values = [1_000u64, 25_000, 1_000_000, 3_000_000_000, u64::max_value()];
+    for value in &values {
+        histogram.reset();
+        histogram.record(*value);
+        let reported = histogram.percentile(50.0);
+        assert!(reported >= *value);
+        assert!((reported - *value) as f64 <= *value as f64 / 64.0);
+        assert!(histogram.min() <= *value);
+    }
+}
+
+#[test]
+fn percentiles_and_merge() {
+    let first = LatencyHistogram::new();
+    let second = LatencyHistogram::new();
+    first.record_n(100, 90);
+    second.record_n(10_000, 10);
+    first.merge(&second);
+    assert_eq!(first.count(), 100);
+    assert_eq!(first.percentile(90.0), 100);
+    assert!(first.percentile(99.0) >= 10_000);
+    assert!((first.mean() - 1090.0).abs() < 1e-6);
+}
@@ -0,0 +1,111 @@
+extern crate e2d2;
+extern crate libc;
+use e2d2::common::EmptyMetadata;
+use e2d2::headers::NullHeader;
+use e2d2::interface::dpdk::*;
+use e2d2::interface::*;
+use e2d2::operators::*;
+use e2d2::scheduler::*;
+use std::ffi::CString;
+use std::mem;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const ETYPE: [u8; 2] = [0x88, 0xb5];
+
+/// A veth pair, removed when dropped.
+struct Veth(&'static str);
+
+impl Veth {
+    fn create(name: &'static str, peer: &'static str) -> Option<Veth> {
+        let ip = |args: &[&str]| Command::new("ip").args(args).status().map(|s| s.success()).unwrap_or(false);
+        if !ip(&["link", "add", name, "mtu", "65000", "type", "veth", "peer", "name", peer, "mtu", "65000"]) {
+            return None;
+        }
+        let veth = Veth(name);
+        if ip(&["link", "set", name, "up"]) && ip(&["link", "set", peer, "up"]) {
+            Some(veth)
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for Veth {
+    fn drop(&mut self) {
+        let _ = Command::new("ip").args(&["link", "del", self.0]).status();
+    }
+}
+
+fn frame(len: usize) -> Vec<u8> {
+    let mut frame = vec![0xff; 6];
+    frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x01]);
+    frame.extend_from_slice(&ETYPE);
+    frame.resize(len, 0xab);
+    frame
+}
+
+fn inject(ifname: &str, frames: &[Vec<u8>]) {
+    unsafe {
+        let fd = libc::socket(libc::AF_PACKET, libc::SOCK_RAW, 0);
+        assert!(fd >= 0);
+        let mut addr: libc::sockaddr_ll = mem::zeroed();
+        addr.sll_family = libc::AF_PACKET as u16;
+        addr.sll_ifindex = libc::if_nametoindex(CString::new(ifname).unwrap().as_ptr()) as i32;
+        addr.sll_halen = 6;
+        for frame in frames {
+            let sent = libc::sendto(
+                fd,
+                frame.as_ptr() as *const libc::c_void,
+                frame.len(),
+                0,
+                &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            );
+            assert_eq!(sent, frame.len() as isize);
+        }
+        libc::close(fd);
+    }
+}
+
+#[test]
+fn af_packet_over_veth() {
+    let _veth = match Veth::create("nbtest0", "nbtest1") {
+        Some(veth) => veth,
+        None => {
+            println!("Skipping, could not create a veth pair (requires root)");
+            return;
+        }
+    };
+    init_system_wl("kernel_port", 0, &[]);
+    init_thread(0, 0);
+    let port = PmdPort::new("af_packet:nbtest1", 0).unwrap();
+    let queue = PmdPort::new_queue_pair(&port, 0, 0).unwrap();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let record = received.clone();
+    let mut pipeline = ReceiveBatch::new(queue.clone())
+        .map(Box::new(move |pkt: &Packet<NullHeader, EmptyMetadata>| {
+            let mut etype = [0; 2];
+            pkt.read_bytes(12, &mut etype);
+            if etype == ETYPE {
+                record.lock().unwrap().push((pkt.pkt_len(), pkt.segments().count()));
+            }
+        }))
+        .send(queue);
+
+    // The oversized frame does not fit in a ring frame. Everything received is sent back out of nbtest1, and must not
+    // be received again.
+    inject("nbtest0", &[frame(100), frame(3000), frame(20000)]);
+    for _ in 0..100 {
+        pipeline.execute();
+        thread::sleep(Duration::from_millis(10));
+    }
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[0], (100, 1));
+    assert_eq!(received[1].0, 3000);
+    assert!(received[1].1 > 1);
+    assert_eq!(port.kernel_drops(), Some((1, 0)));
+}
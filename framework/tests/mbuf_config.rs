@@ -0,0 +1,24 @@
+extern crate e2d2;
+use e2d2::config::*;
+
+#[test]
+fn mbuf_layout_defaults() {
+    let config = read_configuration_from_str("name = \"test\"", "test").unwrap();
+    assert_eq!(config.metadata_slots, 16);
+    assert_eq!(config.mbuf_data_room, DEFAULT_MBUF_DATA_ROOM);
+    assert_eq!(config.mbuf_headroom, DEFAULT_MBUF_HEADROOM);
+}
+
+#[test]
+fn jumbo_data_room() {
+    let config = read_configuration_from_str("mbuf_data_room = 9018\nmetadata_slots = 32", "test").unwrap();
+    assert_eq!(config.mbuf_data_room, 9018);
+    assert_eq!(config.metadata_slots, 32);
+}
+
+#[test]
+fn bad_mbuf_layout_is_rejected() {
+    assert!(read_configuration_from_str("metadata_slots = 2", "test").is_err());
+    assert!(read_configuration_from_str("mbuf_data_room = 512", "test").is_err());
+    assert!(read_configuration_from_str("mbuf_data_room = 65000\nmbuf_headroom = 1024", "test").is_err());
+}
@@ -0,0 +1,56 @@
+extern crate e2d2;
+#[macro_use]
+extern crate lazy_static;
+use e2d2::interface::dpdk::*;
+use e2d2::interface::*;
+use std::sync::{Mutex, MutexGuard, Once, ONCE_INIT};
+
+static INIT: Once = ONCE_INIT;
+
+lazy_static! {
+    // Tests share core 0's mempool cache and the global tracking table.
+    static ref CORE: Mutex<()> = Mutex::new(());
+}
+
+fn setup() -> MutexGuard<'static, ()> {
+    let guard = CORE.lock().unwrap_or_else(|e| e.into_inner());
+    INIT.call_once(|| init_system_wl("mbuf_tracking", 0, &[]));
+    init_thread(0, 0);
+    set_mbuf_tracking(false);
+    set_mbuf_tracking(true);
+    guard
+}
+
+#[test]
+fn attributes_to_innermost_owner() {
+    let _core = setup();
+    let outer = mbuf_owner("outer");
+    let a = new_packet().unwrap();
+    let b = {
+        let _inner = mbuf_owner("inner");
+        new_packet_array(2)
+    };
+    let c = new_packet().unwrap();
+    drop(outer);
+    assert_eq!(outstanding_mbufs(), vec![("inner", 2), ("outer", 2)]);
+    a.free_packet();
+    c.free_packet();
+    for pkt in b {
+        pkt.free_packet();
+    }
+    assert!(outstanding_mbufs().is_empty());
+    set_mbuf_tracking(false);
+}
+
+#[test]
+fn references_keep_mbufs_outstanding() {
+    let _core = setup();
+    let _owner = mbuf_owner("shared");
+    let pkt = new_packet().unwrap();
+    let other = pkt.reference();
+    pkt.free_packet();
+    assert_eq!(outstanding_mbufs(), vec![("shared", 1)]);
+    other.free_packet();
+    assert!(outstanding_mbufs().is_empty());
+    set_mbuf_tracking(false);
+}
@@ -0,0 +1,33 @@
+extern crate e2d2;
+use e2d2::config::*;
+
+const CONFIG: &'static str = r#"
+name = "test"
+
+[[mempools]]
+name = "rx0"
+size = 4095
+socket = 1
+
+[[ports]]
+name = "0000:01:00.0"
+rx_cores = [1, 2]
+tx_cores = [1, 2]
+mempool = "rx0"
+"#;
+
+#[test]
+fn read_named_mempools() {
+    let config = read_configuration_from_str(CONFIG, "test").unwrap();
+    assert_eq!(config.mempools.len(), 1);
+    assert_eq!(config.mempools[0].size, 4095);
+    assert_eq!(config.mempools[0].cache_size, DEFAULT_CACHE_SIZE);
+    assert_eq!(config.mempools[0].socket, Some(1));
+    assert_eq!(config.ports[0].rx_mempool(1), Some("rx0"));
+}
+
+#[test]
+fn undefined_mempool_is_rejected() {
+    let config = CONFIG.replace("mempool = \"rx0\"", "rx_mempools = [\"rx0\", \"rx1\"]");
+    assert!(read_configuration_from_str(&config, "test").is_err());
+}
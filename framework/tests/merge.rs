@@ -0,0 +1,111 @@
+extern crate e2d2;
+use e2d2::state::*;
+
+#[test]
+fn max_min_ignore_empty() {
+    let mut max = Max::default();
+    max.merge(&Max::new(3));
+    max.merge(&Max::default());
+    max.merge(&Max::new(1));
+    assert_eq!(max, Max(Some(3)));
+    let mut min = Min::default();
+    min.merge(&Min::new(3));
+    min.merge(&Min::default());
+    min.merge(&Min::new(5));
+    assert_eq!(min, Min(Some(3)));
+}
+
+#[test]
+fn hyperloglog_estimates_merged_cardinality() {
+    let mut a = HyperLogLog::default();
+    let mut b = HyperLogLog::default();
+    for i in 0..60_000u32 {
+        a.insert(&i);
+    }
+    for i in 40_000..100_000u32 {
+        b.insert(&i);
+    }
+    a.merge(&b);
+    let estimate = a.estimate();
+    assert!((estimate - 100_000.0).abs() < 5_000.0, "estimate {}", estimate);
+    let mut small = HyperLogLog::default();
+    for i in 0..100u32 {
+        small.insert(&i);
+        small.insert(&i);
+    }
+    assert!((small.estimate() - 100.0).abs() < 5.0);
+}
+
+#[test]
+fn top_k_keeps_largest() {
+    let mut a = TopK::new(2);
+    let mut b = TopK::new(2);
+    a.add("a", 10);
+    a.add("b", 1);
+    b.add("b", 20);
+    b.add("c", 5);
+    a.merge(&b);
+    assert_eq!(a.top(), vec![("b", 21), ("a", 10)]);
+}
+
+#[test]
+fn merge_store_merges_shards() {
+    let mut cp: MergeStoreCP<SetUnion<u32>, u32> = MergeStoreCP::new();
+    let mut dp0 = cp.dp_store_with_interval_and_size(4, 16);
+    let mut dp1 = cp.dp_store_with_interval_and_size(4, 16);
+    dp0.update_with(1, |s| {
+        s.insert(10);
+    });
+    dp1.update_with(1, |s| {
+        s.insert(11);
+    });
+    dp1.update_with(2, |s| {
+        s.insert(12);
+    });
+    cp.sync();
+    assert!(cp.is_empty());
+    dp0.publish();
+    dp1.publish();
+    cp.sync();
+    assert_eq!(cp.len(), 2);
+    assert_eq!(cp.get(&1).len(), 2);
+    assert_eq!(cp.get(&2).len(), 1);
+}
+
+#[test]
+fn merge_store_keeps_value_configuration() {
+    let mut cp: MergeStoreCP<HyperLogLog, u32> = MergeStoreCP::with_template(HyperLogLog::new(10));
+    let mut dp0 = cp.dp_store_with_interval_and_size(4, 16);
+    let mut dp1 = cp.dp_store_with_interval_and_size(4, 16);
+    dp0.update_with(1, |h| h.insert(&10u32));
+    let mut other = HyperLogLog::new(10);
+    other.insert(&11u32);
+    dp1.merge(1, &other);
+    dp0.publish();
+    dp1.publish();
+    cp.sync();
+    assert_eq!(cp.get(&1).precision(), 10);
+    assert!((cp.get(&1).estimate() - 2.0).abs() < 0.5);
+    assert_eq!(cp.get(&2).precision(), 10);
+
+    let mut top: MergeStoreCP<TopK<&str>, u32> = MergeStoreCP::with_template(TopK::new(1));
+    let mut dp = top.dp_store_with_interval_and_size(4, 16);
+    dp.update_with(1, |t| {
+        t.add("a", 2);
+        t.add("b", 1);
+    });
+    dp.publish();
+    top.sync();
+    assert_eq!(top.get(&1).top(), vec![("a", 2)]);
+}
+
+#[test]
+fn top_k_clear_keeps_k() {
+    let mut top = TopK::new(1);
+    top.add("a", 1);
+    top.clear();
+    assert!(top.top().is_empty());
+    top.add("a", 1);
+    top.add("b", 2);
+    assert_eq!(top.top(), vec![("b", 2)]);
+}
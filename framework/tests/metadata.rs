@@ -0,0 +1,31 @@
+extern crate e2d2;
+use e2d2::interface::*;
+
+#[test]
+fn reregistering_returns_same_field() {
+    register_metadata_field::<u32>("test.same").unwrap();
+    register_metadata_field::<u32>("test.same").unwrap();
+    let fields = metadata_fields();
+    assert_eq!(fields.iter().filter(|f| f.name == "test.same").count(), 1);
+}
+
+#[test]
+fn type_mismatch_is_rejected() {
+    register_metadata_field::<u64>("test.mismatch").unwrap();
+    assert!(register_metadata_field::<u16>("test.mismatch").is_err());
+}
+
+#[test]
+fn fields_do_not_overlap() {
+    register_metadata_field::<u8>("test.a").unwrap();
+    register_metadata_field::<u64>("test.b").unwrap();
+    register_metadata_field::<(u16, u32)>("test.c").unwrap();
+    let fields = metadata_fields();
+    for f in &fields {
+        for g in &fields {
+            if f.name != g.name {
+                assert!(f.offset + f.size <= g.offset || g.offset + g.size <= f.offset);
+            }
+        }
+    }
+}
@@ -0,0 +1,240 @@
+extern crate e2d2;
+#[macro_use]
+extern crate lazy_static;
+use e2d2::allocators::CacheAligned;
+use e2d2::common::EmptyMetadata;
+use e2d2::headers::*;
+use e2d2::interface::dpdk::*;
+use e2d2::interface::*;
+use e2d2::operators::*;
+use e2d2::queues::*;
+use e2d2::scheduler::*;
+use std::sync::{Arc, Mutex, MutexGuard, Once, ONCE_INIT};
+
+static INIT: Once = ONCE_INIT;
+
+lazy_static! {
+    // Tests share core 0's mempool cache, which is not thread safe.
+    static ref CORE: Mutex<()> = Mutex::new(());
+}
+
+fn setup() -> MutexGuard<'static, ()> {
+    let guard = CORE.lock().unwrap_or_else(|e| e.into_inner());
+    INIT.call_once(|| init_system_wl("operators", 0, &[]));
+    init_thread(0, 0);
+    guard
+}
+
+fn packet(data: &[u8]) -> Packet<NullHeader, EmptyMetadata> {
+    let mut pkt = new_packet().unwrap();
+    pkt.add_to_payload_tail(data.len()).unwrap();
+    pkt.write_bytes(0, data).unwrap();
+    pkt
+}
+
+/// A batch that receives packets holding each of `data`.
+fn source(data: &[&[u8]]) -> ReceiveBatch<MpscConsumer> {
+    let (producer, consumer) = new_mpsc_queue_pair();
+    for d in data {
+        assert!(producer.enqueue_one(packet(d)));
+    }
+    consumer
+}
+
+type Seen = Arc<Mutex<Vec<Vec<u8>>>>;
+
+/// Record the contents of every packet in `batch` in `seen`, then free them.
+fn record<V: Batch + 'static>(batch: V, seen: &Seen) -> SendBatch<CacheAligned<VirtualQueue>, MapBatch<V::Header, V>> {
+    let seen = seen.clone();
+    let sink = VirtualPort::new(1).unwrap().new_virtual_queue(0).unwrap();
+    batch
+        .map(Box::new(move |pkt: &Packet<V::Header, V::Metadata>| {
+            let mut data = vec![0; pkt.pkt_len()];
+            pkt.read_bytes(0, &mut data);
+            seen.lock().unwrap().push(data);
+        }))
+        .send(sink)
+}
+
+#[test]
+fn replicas_share_packets() {
+    let _core = setup();
+    let mut sched = StandaloneScheduler::new();
+    let mut replicate = source(&[b"first", b"second"]).replicate(2, false, &mut sched);
+    let seen: Vec<Seen> = (0..2).map(|_| Arc::new(Mutex::new(Vec::new()))).collect();
+    let mut replicas: Vec<_> = (0..2)
+        .map(|i| record(replicate.get_replica(i).unwrap(), &seen[i]))
+        .collect();
+    assert!(replicate.get_replica(0).is_none());
+    sched.execute_one();
+    for replica in &mut replicas {
+        replica.execute();
+    }
+    for seen in &seen {
+        assert_eq!(*seen.lock().unwrap(), vec![b"first".to_vec(), b"second".to_vec()]);
+    }
+}
+
+#[test]
+fn copied_replicas_are_independent() {
+    let _core = setup();
+    let mut sched = StandaloneScheduler::new();
+    let mut replicate = source(&[b"packet"]).replicate(2, true, &mut sched);
+    let original = Arc::new(Mutex::new(Vec::new()));
+    let modified = Arc::new(Mutex::new(Vec::new()));
+    let mut first = record(replicate.get_replica(0).unwrap(), &original);
+    let second = replicate
+        .get_replica(1)
+        .unwrap()
+        .transform(Box::new(|pkt: &mut Packet<NullHeader, EmptyMetadata>| pkt.write_bytes(0, b"P").unwrap()));
+    let mut second = record(second, &modified);
+    sched.execute_one();
+    second.execute();
+    first.execute();
+    assert_eq!(*modified.lock().unwrap(), vec![b"Packet".to_vec()]);
+    assert_eq!(*original.lock().unwrap(), vec![b"packet".to_vec()]);
+}
+
+#[test]
+fn partition_splits_by_predicate() {
+    let _core = setup();
+    let (matched, unmatched) = source(&[b"a1", b"b1", b"a2", b"b2"])
+        .partition(Box::new(|pkt: &Packet<NullHeader, EmptyMetadata>| pkt.get_payload()[0] == b'a'));
+    let a = Arc::new(Mutex::new(Vec::new()));
+    let b = Arc::new(Mutex::new(Vec::new()));
+    let mut matched = record(matched, &a);
+    let mut unmatched = record(unmatched, &b);
+    // Whichever side runs first pulls the parent's packets for both.
+    unmatched.execute();
+    matched.execute();
+    assert_eq!(*a.lock().unwrap(), vec![b"a1".to_vec(), b"a2".to_vec()]);
+    assert_eq!(*b.lock().unwrap(), vec![b"b1".to_vec(), b"b2".to_vec()]);
+}
+
+#[test]
+fn steer_to_target_modulo_targets() {
+    let _core = setup();
+    let (steering, consumers) = new_steering(2);
+    let mut steer = source(&[b"\x00a", b"\x01b", b"\x02c", b"\x03d"])
+        .steer(steering.clone(), Box::new(|pkt: &Packet<NullHeader, EmptyMetadata>| pkt.get_payload()[0] as usize));
+    steer.execute();
+    let seen: Vec<Seen> = (0..2).map(|_| Arc::new(Mutex::new(Vec::new()))).collect();
+    for (consumer, seen) in consumers.into_iter().zip(&seen) {
+        record(consumer, seen).execute();
+    }
+    assert_eq!(*seen[0].lock().unwrap(), vec![b"\x00a".to_vec(), b"\x02c".to_vec()]);
+    assert_eq!(*seen[1].lock().unwrap(), vec![b"\x01b".to_vec(), b"\x03d".to_vec()]);
+    assert_eq!(steering.dropped(0) + steering.dropped(1), 0);
+}
+
+#[test]
+#[should_panic(expected = "at least one target")]
+fn steering_needs_a_target() {
+    new_steering(0);
+}
+
+/// A TCP packet with sequence number 1000 and PSH and FIN set, carrying `payload`.
+fn tcp_packet(payload: &[u8]) -> Packet<TcpHeader, EmptyMetadata> {
+    let mut mac = MacHeader::new();
+    mac.set_etype(0x0800);
+    let mut ip = IpHeader::new();
+    ip.set_version(4);
+    ip.set_ihl(5);
+    ip.set_ttl(64);
+    ip.set_protocol(6);
+    ip.set_length(40 + payload.len() as u16);
+    let mut tcp = TcpHeader::new();
+    tcp.set_data_offset(5);
+    tcp.set_seq_num(1000);
+    tcp.set_psh_flag();
+    tcp.set_fin_flag();
+    let mut pkt = new_packet()
+        .unwrap()
+        .push_header(&mac)
+        .unwrap()
+        .push_header(&ip)
+        .unwrap()
+        .push_header(&tcp)
+        .unwrap();
+    pkt.add_to_payload_tail(payload.len()).unwrap();
+    pkt.get_mut_payload().copy_from_slice(payload);
+    pkt
+}
+
+#[test]
+fn gso_splits_large_segments() {
+    let _core = setup();
+    let (producer, consumer) = new_mpsc_queue_pair();
+    assert!(producer.enqueue_one(tcp_packet(b"0123456789")));
+    assert!(producer.enqueue_one(tcp_packet(b"ab")));
+    let segments = Arc::new(Mutex::new(Vec::new()));
+    let record = segments.clone();
+    let mut gso = consumer
+        .parse::<MacHeader>()
+        .parse::<IpHeader>()
+        .parse::<TcpHeader>()
+        .gso(4)
+        .map(Box::new(move |pkt: &Packet<TcpHeader, EmptyMetadata>| {
+            let tcp = pkt.get_header();
+            // The IP total length follows the Ethernet header and the IP version and DSCP.
+            let mut ip_len = [0; 2];
+            pkt.read_bytes(16, &mut ip_len);
+            assert_eq!(((ip_len[0] as usize) << 8) | ip_len[1] as usize, 40 + pkt.get_payload().len());
+            record
+                .lock()
+                .unwrap()
+                .push((tcp.seq_num(), tcp.psh_flag(), tcp.fin_flag(), pkt.get_payload().to_vec()));
+        }))
+        .send(VirtualPort::new(1).unwrap().new_virtual_queue(0).unwrap());
+    gso.execute();
+    assert_eq!(
+        *segments.lock().unwrap(),
+        vec![
+            (1000, false, false, b"0123".to_vec()),
+            (1004, false, false, b"4567".to_vec()),
+            (1008, true, true, b"89".to_vec()),
+            (1000, true, true, b"ab".to_vec()),
+        ]
+    );
+}
+
+#[test]
+fn exception_punts_selected_packets() {
+    let _core = setup();
+    let (exception, punted) = new_exception_path();
+    let kept = Arc::new(Mutex::new(Vec::new()));
+    let kernel = Arc::new(Mutex::new(Vec::new()));
+    let mut pipeline = record(
+        source(&[b"arp", b"data1", b"arp", b"data2"]).exception(
+            exception.clone(),
+            Box::new(|pkt: &Packet<NullHeader, EmptyMetadata>| pkt.get_payload() == b"arp"),
+        ),
+        &kept,
+    );
+    pipeline.execute();
+    record(punted, &kernel).execute();
+    assert_eq!(*kept.lock().unwrap(), vec![b"data1".to_vec(), b"data2".to_vec()]);
+    assert_eq!(*kernel.lock().unwrap(), vec![b"arp".to_vec(), b"arp".to_vec()]);
+    assert_eq!(exception.dropped(), 0);
+}
+
+#[test]
+fn exception_drops_when_full() {
+    let _core = setup();
+    // A queue of two slots holds a single packet.
+    let (exception, punted) = new_exception_path_with_size(2);
+    let kept = Arc::new(Mutex::new(Vec::new()));
+    let kernel = Arc::new(Mutex::new(Vec::new()));
+    let mut pipeline = record(
+        source(&[b"first", b"second"]).exception(
+            exception.clone(),
+            Box::new(|_: &Packet<NullHeader, EmptyMetadata>| true),
+        ),
+        &kept,
+    );
+    pipeline.execute();
+    record(punted, &kernel).execute();
+    assert!(kept.lock().unwrap().is_empty());
+    assert_eq!(*kernel.lock().unwrap(), vec![b"first".to_vec()]);
+    assert_eq!(exception.dropped(), 1);
+}
@@ -0,0 +1,33 @@
+extern crate e2d2;
+use e2d2::config::*;
+
+const CONFIG: &'static str = r#"
+name = "test"
+
+[[ports]]
+name = "0000:01:00.0"
+cores = [1, 2]
+
+[ports.rss]
+hash = ["ipv4", "tcp"]
+symmetric = true
+reta = [1, 0]
+"#;
+
+#[test]
+fn read_rss_configuration() {
+    let config = read_configuration_from_str(CONFIG, "test").unwrap();
+    let rss = &config.ports[0].rss;
+    assert_eq!(rss.hash, Some(vec![RssHash::Ipv4, RssHash::Tcp]));
+    assert!(rss.symmetric);
+    assert_eq!(rss.reta, vec![1, 0]);
+    assert!(!rss.is_default());
+}
+
+#[test]
+fn bad_rss_configuration_is_rejected() {
+    let config = CONFIG.replace("symmetric = true", "symmetric = \"yes\"");
+    assert!(read_configuration_from_str(&config, "test").is_err());
+    let config = CONFIG.replace("\"tcp\"", "\"icmp\"");
+    assert!(read_configuration_from_str(&config, "test").is_err());
+}
@@ -0,0 +1,53 @@
+extern crate e2d2;
+use e2d2::shared_state::directory::*;
+use e2d2::utils::Flow;
+use std::process;
+
+#[test]
+fn directory_entries_are_readable_by_name() {
+    let name = format!("/nb-test-{}", process::id());
+    let mut directory = Directory::new(&name);
+    let mut vec = directory.register_vec::<u32>("vec", 16).unwrap();
+    let mut map = directory.register_map::<Flow, u64>("flows", 16).unwrap();
+    let counters = directory.register_counters("counters", &["rx", "tx"]).unwrap();
+    assert!(directory.register_vec::<u32>("bad/name", 16).is_none());
+
+    let flow = Flow {
+        src_ip: 1,
+        dst_ip: 2,
+        src_port: 3,
+        dst_port: 4,
+        proto: 6,
+    };
+    directory.begin_snapshot();
+    vec.push(7);
+    vec.push(8);
+    map.insert(flow, 42);
+    counters.add(counters.index("tx").unwrap(), 5);
+    directory.end_snapshot();
+
+    let reader = DirectoryReader::open(&name).unwrap();
+    let entries = reader.entries();
+    assert_eq!(
+        entries.iter().map(|e| (e.name.as_str(), e.kind)).collect::<Vec<_>>(),
+        vec![
+            ("vec", EntryKind::Vec),
+            ("flows", EntryKind::HashMap),
+            ("counters", EntryKind::Counters),
+        ]
+    );
+    let readers: Vec<_> = entries.iter().map(|e| reader.open_entry(e).unwrap()).collect();
+    let (_, (values, flows, counts)) = reader
+        .read_snapshot(|| {
+            (
+                readers[0].values::<u32>(),
+                readers[1].values::<u64>(),
+                readers[2].counters(),
+            )
+        })
+        .unwrap();
+    assert_eq!(values, vec![7, 8]);
+    assert_eq!(flows, vec![42]);
+    assert_eq!(readers[1].records()[0].0.len(), 13);
+    assert_eq!(counts, vec![(String::from("rx"), 0), (String::from("tx"), 5)]);
+}
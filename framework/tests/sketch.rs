@@ -0,0 +1,97 @@
+extern crate e2d2;
+mod common;
+use common::flow;
+use e2d2::state::*;
+
+#[test]
+fn count_min_never_underestimates() {
+    let mut a = CountMinSketch::new(1024, 4);
+    let mut b = CountMinSketch::new(1024, 4);
+    for i in 0..2000 {
+        a.add(&flow(i), 1);
+        b.add(&flow(i), i as u64 % 3);
+    }
+    a.merge(&b);
+    for i in 0..2000 {
+        assert!(a.estimate(&flow(i)) >= 1 + i as u64 % 3);
+    }
+    assert!(a.estimate(&flow(7)) <= 2 + 8 * a.total() / 1024);
+}
+
+#[test]
+fn bloom_and_cuckoo_have_no_false_negatives() {
+    let mut bloom = BloomFilter::with_rate(1000, 0.01);
+    let mut cuckoo = CuckooFilter::with_capacity(1000);
+    for i in 0..1000u32 {
+        bloom.insert(&i);
+        assert!(cuckoo.insert(&i));
+    }
+    assert!((0..1000u32).all(|i| bloom.contains(&i) && cuckoo.contains(&i)));
+    let false_positives = (1000..11000u32).filter(|i| bloom.contains(i)).count();
+    assert!(false_positives < 300, "{} false positives", false_positives);
+    assert!(cuckoo.remove(&5u32));
+    assert!(!cuckoo.contains(&5u32));
+    assert_eq!(cuckoo.len(), 999);
+}
+
+#[test]
+fn cuckoo_keeps_duplicate_fingerprints() {
+    // Keys with the same hash share a fingerprint and buckets, as does a key inserted twice.
+    let mut cuckoo = CuckooFilter::new(16);
+    assert!(cuckoo.insert(&7u32));
+    assert!(cuckoo.insert(&7u32));
+    assert_eq!(cuckoo.len(), 2);
+    assert!(cuckoo.remove(&7u32));
+    assert!(cuckoo.contains(&7u32));
+    assert!(cuckoo.remove(&7u32));
+    assert!(!cuckoo.contains(&7u32));
+    assert!(cuckoo.is_empty());
+}
+
+#[test]
+fn cuckoo_reinserts_victim_on_remove() {
+    // A single bucket, so the fifth key is displaced into the victim slot and the filter is full.
+    let mut cuckoo = CuckooFilter::new(1);
+    for i in 0..5u32 {
+        assert!(cuckoo.insert(&i));
+    }
+    assert!(!cuckoo.insert(&5u32));
+    assert_eq!(cuckoo.len(), 5);
+    assert!(cuckoo.remove(&0u32));
+    assert_eq!(cuckoo.len(), 4);
+    assert!((1..5u32).all(|i| cuckoo.contains(&i)));
+    // The victim found a slot, so there is room again.
+    assert!(cuckoo.insert(&5u32));
+    assert_eq!(cuckoo.len(), 5);
+}
+
+#[test]
+fn heavy_hitters_merge_across_cores() {
+    let mut cp = SketchCP::new(HeavyHitters::<u32>::new(4, 1024, 4));
+    let mut dp0 = cp.dp_sketch();
+    let mut dp1 = cp.dp_sketch();
+    for i in 0..1000u32 {
+        dp0.update(|s| s.add(&(i % 100), 1));
+        dp1.update(|s| s.add(&(i % 100), 1));
+    }
+    for _ in 0..500 {
+        dp0.update(|s| s.add(&1000, 1));
+        dp1.update(|s| s.add(&1000, 1));
+    }
+    dp0.publish();
+    dp1.publish();
+    cp.sync();
+    let top = cp.get().top();
+    assert_eq!(top[0].0, 1000);
+    assert!(top[0].1 >= 1000);
+    assert_eq!(cp.get().total(), 3000);
+}
+
+#[test]
+fn hyperloglog_counts_flows() {
+    let mut hll = HyperLogLog::default();
+    for i in 0..10_000 {
+        hll.insert_key(&flow(i));
+    }
+    assert!((hll.estimate() - 10_000.0).abs() < 500.0);
+}
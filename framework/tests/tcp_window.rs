@@ -484,3 +484,38 @@ fn test_overlapping_write() {
         panic!("Could not write data");
     }
 }
+
+fn read_all(r0: &mut ReorderedBuffer) -> String {
+    let mut read_buf: Vec<_> = (0..r0.buffer_size()).map(|_| 0).collect();
+    let read = r0.read_data(&mut read_buf[..]);
+    String::from(str::from_utf8(&read_buf[..read]).unwrap())
+}
+
+/// Test that data received after a gap is not made available before the gap is filled, including when the gap is
+/// only partially filled.
+#[test]
+fn test_partially_filled_gap() {
+    println!("Running test_partially_filled_gap");
+    let mut r0 = ReorderedBuffer::new(4096).unwrap();
+    r0.seq(100, "hello".as_bytes());
+    r0.add_data(110, "world".as_bytes());
+    r0.add_data(105, "--".as_bytes());
+    assert_eq!(read_all(&mut r0), "hello--", "Read data past a gap");
+    r0.add_data(107, "...".as_bytes());
+    assert_eq!(read_all(&mut r0), "...world");
+}
+
+/// Test filling gaps after the data before them has been read, with several out-of-order segments outstanding.
+#[test]
+fn test_fill_gaps_after_read() {
+    println!("Running test_fill_gaps_after_read");
+    let mut r0 = ReorderedBuffer::new(4096).unwrap();
+    r0.seq(100, "hello".as_bytes());
+    r0.add_data(110, "world".as_bytes());
+    r0.add_data(120, "!!".as_bytes());
+    assert_eq!(read_all(&mut r0), "hello");
+    r0.add_data(105, "-----".as_bytes());
+    assert_eq!(read_all(&mut r0), "-----world", "Read data past a gap");
+    r0.add_data(115, "01234".as_bytes());
+    assert_eq!(read_all(&mut r0), "01234!!");
+}
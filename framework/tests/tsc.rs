@@ -0,0 +1,20 @@
+extern crate e2d2;
+use e2d2::utils::*;
+
+#[test]
+fn tsc_conversion_round_trips() {
+    let hz = tsc_hz();
+    assert!(hz > 0);
+    assert!((tsc_to_ns(hz) as i64 - 1_000_000_000).abs() < 1000);
+    let cycles = ns_to_tsc(5_000_000);
+    assert!((tsc_to_ns(cycles) as i64 - 5_000_000).abs() < 10);
+}
+
+#[test]
+fn tsc_time_is_close_to_wall_clock() {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let wall_ns = now.as_secs() * 1_000_000_000 + now.subsec_nanos() as u64;
+    let tsc_ns = tsc_ns_to_unix_ns(tsc_now_ns());
+    assert!((tsc_ns as i64 - wall_ns as i64).abs() < 100_000_000);
+}
@@ -0,0 +1,184 @@
+#include <stdio.h>
+#include <string.h>
+
+#include <rte_config.h>
+#include <rte_ethdev.h>
+#include <rte_flow.h>
+#include <rte_ip.h>
+#include <rte_tcp.h>
+#include <rte_udp.h>
+
+#include "flow.h"
+
+/**
+ * Install hardware flow rules (rte_flow) on PMD ports. Rules are described by a flat nb_flow_match structure so that
+ * callers do not need to construct rte_flow patterns themselves.
+ **/
+
+static void flow_error_message(struct rte_flow_error* error, char* err, int errlen) {
+    if (err == NULL || errlen <= 0) {
+        return;
+    }
+    snprintf(err, errlen, "%s (type %d)", error->message ? error->message : "unknown error", error->type);
+}
+
+struct rte_flow* create_flow_rule(int port, const struct nb_flow_match* match, const struct nb_flow_actions* actions,
+                                  char* err, int errlen) {
+    struct rte_flow_attr attr = {.ingress = 1};
+    struct rte_flow_item pattern[4];
+    struct rte_flow_action flow_actions[5];
+    struct rte_flow_item_eth eth_spec, eth_mask;
+    struct rte_flow_item_ipv4 ipv4_spec, ipv4_mask;
+    struct rte_flow_item_tcp tcp_spec, tcp_mask;
+    struct rte_flow_item_udp udp_spec, udp_mask;
+    struct rte_flow_action_queue queue;
+    struct rte_flow_action_mark mark;
+    struct rte_flow_error error;
+    int item = 0, action = 0;
+    int match_l3 = match->fields & (NB_FLOW_IPV4_SRC | NB_FLOW_IPV4_DST | NB_FLOW_IP_PROTO);
+    int match_l4 = match->fields & (NB_FLOW_SRC_PORT | NB_FLOW_DST_PORT);
+
+    memset(pattern, 0, sizeof(pattern));
+    memset(flow_actions, 0, sizeof(flow_actions));
+    memset(&error, 0, sizeof(error));
+    memset(&eth_spec, 0, sizeof(eth_spec));
+    memset(&eth_mask, 0, sizeof(eth_mask));
+    memset(&ipv4_spec, 0, sizeof(ipv4_spec));
+    memset(&ipv4_mask, 0, sizeof(ipv4_mask));
+    memset(&tcp_spec, 0, sizeof(tcp_spec));
+    memset(&tcp_mask, 0, sizeof(tcp_mask));
+    memset(&udp_spec, 0, sizeof(udp_spec));
+    memset(&udp_mask, 0, sizeof(udp_mask));
+
+    if (match_l4 && !(match->fields & NB_FLOW_IP_PROTO && (match->ip_proto == IPPROTO_TCP ||
+                                                            match->ip_proto == IPPROTO_UDP))) {
+        if (err != NULL && errlen > 0) {
+            snprintf(err, errlen, "Matching on ports requires matching on TCP or UDP");
+        }
+        return NULL;
+    }
+
+    /* L2 */
+    if (match->fields & NB_FLOW_ETH_SRC) {
+        memcpy(&eth_spec.src, match->eth_src, ETHER_ADDR_LEN);
+        memset(&eth_mask.src, 0xff, ETHER_ADDR_LEN);
+    }
+    if (match->fields & NB_FLOW_ETH_DST) {
+        memcpy(&eth_spec.dst, match->eth_dst, ETHER_ADDR_LEN);
+        memset(&eth_mask.dst, 0xff, ETHER_ADDR_LEN);
+    }
+    if (match->fields & NB_FLOW_ETHER_TYPE) {
+        eth_spec.type = rte_cpu_to_be_16(match->ether_type);
+        eth_mask.type = 0xffff;
+    }
+    pattern[item].type = RTE_FLOW_ITEM_TYPE_ETH;
+    if (match->fields & (NB_FLOW_ETH_SRC | NB_FLOW_ETH_DST | NB_FLOW_ETHER_TYPE)) {
+        pattern[item].spec = &eth_spec;
+        pattern[item].mask = &eth_mask;
+    }
+    item++;
+
+    /* L3 */
+    if (match_l3 || match_l4) {
+        if (match->fields & NB_FLOW_IPV4_SRC) {
+            ipv4_spec.hdr.src_addr = rte_cpu_to_be_32(match->ipv4_src);
+            ipv4_mask.hdr.src_addr = rte_cpu_to_be_32(match->ipv4_src_mask);
+        }
+        if (match->fields & NB_FLOW_IPV4_DST) {
+            ipv4_spec.hdr.dst_addr = rte_cpu_to_be_32(match->ipv4_dst);
+            ipv4_mask.hdr.dst_addr = rte_cpu_to_be_32(match->ipv4_dst_mask);
+        }
+        if (match->fields & NB_FLOW_IP_PROTO) {
+            ipv4_spec.hdr.next_proto_id = match->ip_proto;
+            ipv4_mask.hdr.next_proto_id = 0xff;
+        }
+        pattern[item].type = RTE_FLOW_ITEM_TYPE_IPV4;
+        pattern[item].spec = &ipv4_spec;
+        pattern[item].mask = &ipv4_mask;
+        item++;
+    }
+
+    /* L4 */
+    if (match_l4) {
+        uint16_t src_port = 0, src_mask = 0, dst_port = 0, dst_mask = 0;
+        if (match->fields & NB_FLOW_SRC_PORT) {
+            src_port = rte_cpu_to_be_16(match->src_port);
+            src_mask = 0xffff;
+        }
+        if (match->fields & NB_FLOW_DST_PORT) {
+            dst_port = rte_cpu_to_be_16(match->dst_port);
+            dst_mask = 0xffff;
+        }
+        if (match->ip_proto == IPPROTO_TCP) {
+            tcp_spec.hdr.src_port = src_port;
+            tcp_spec.hdr.dst_port = dst_port;
+            tcp_mask.hdr.src_port = src_mask;
+            tcp_mask.hdr.dst_port = dst_mask;
+            pattern[item].type    = RTE_FLOW_ITEM_TYPE_TCP;
+            pattern[item].spec    = &tcp_spec;
+            pattern[item].mask    = &tcp_mask;
+        } else {
+            udp_spec.hdr.src_port = src_port;
+            udp_spec.hdr.dst_port = dst_port;
+            udp_mask.hdr.src_port = src_mask;
+            udp_mask.hdr.dst_port = dst_mask;
+            pattern[item].type    = RTE_FLOW_ITEM_TYPE_UDP;
+            pattern[item].spec    = &udp_spec;
+            pattern[item].mask    = &udp_mask;
+        }
+        item++;
+    }
+    pattern[item].type = RTE_FLOW_ITEM_TYPE_END;
+
+    /* Actions */
+    if (actions->drop) {
+        flow_actions[action++].type = RTE_FLOW_ACTION_TYPE_DROP;
+    } else if (actions->queue >= 0) {
+        queue.index                 = (uint16_t)actions->queue;
+        flow_actions[action].type   = RTE_FLOW_ACTION_TYPE_QUEUE;
+        flow_actions[action++].conf = &queue;
+    }
+    if (actions->mark) {
+        mark.id                     = actions->mark_id;
+        flow_actions[action].type   = RTE_FLOW_ACTION_TYPE_MARK;
+        flow_actions[action++].conf = &mark;
+    }
+    if (actions->count) {
+        flow_actions[action++].type = RTE_FLOW_ACTION_TYPE_COUNT;
+    }
+    if (action == 0) {
+        flow_actions[action++].type = RTE_FLOW_ACTION_TYPE_PASSTHRU;
+    }
+    flow_actions[action].type = RTE_FLOW_ACTION_TYPE_END;
+
+    if (rte_flow_validate(port, &attr, pattern, flow_actions, &error) != 0) {
+        flow_error_message(&error, err, errlen);
+        return NULL;
+    }
+    struct rte_flow* flow = rte_flow_create(port, &attr, pattern, flow_actions, &error);
+    if (flow == NULL) {
+        flow_error_message(&error, err, errlen);
+    }
+    return flow;
+}
+
+int destroy_flow_rule(int port, struct rte_flow* flow) {
+    struct rte_flow_error error;
+    return rte_flow_destroy(port, flow, &error);
+}
+
+/* Read hit and byte counters for a rule created with the count action. */
+int query_flow_rule_count(int port, struct rte_flow* flow, uint64_t* hits, uint64_t* bytes) {
+    struct rte_flow_query_count count;
+    struct rte_flow_error error;
+    int ret;
+
+    memset(&count, 0, sizeof(count));
+    ret = rte_flow_query(port, flow, RTE_FLOW_ACTION_TYPE_COUNT, &count, &error);
+    if (ret != 0) {
+        return ret;
+    }
+    *hits  = count.hits_set ? count.hits : 0;
+    *bytes = count.bytes_set ? count.bytes : 0;
+    return 0;
+}
@@ -0,0 +1,43 @@
+#ifndef __FLOW_H__
+#define __FLOW_H__
+#include <stdint.h>
+
+/* Fields set in nb_flow_match, must match the Rust definitions in native/zcsi/flow.rs. */
+#define NB_FLOW_ETH_SRC 0x1
+#define NB_FLOW_ETH_DST 0x2
+#define NB_FLOW_ETHER_TYPE 0x4
+#define NB_FLOW_IPV4_SRC 0x8
+#define NB_FLOW_IPV4_DST 0x10
+#define NB_FLOW_IP_PROTO 0x20
+#define NB_FLOW_SRC_PORT 0x40
+#define NB_FLOW_DST_PORT 0x80
+
+/* All multi-byte fields are in host byte order. */
+struct nb_flow_match {
+    uint32_t fields;
+    uint8_t eth_src[6];
+    uint8_t eth_dst[6];
+    uint16_t ether_type;
+    uint32_t ipv4_src;
+    uint32_t ipv4_src_mask;
+    uint32_t ipv4_dst;
+    uint32_t ipv4_dst_mask;
+    uint8_t ip_proto;
+    uint16_t src_port;
+    uint16_t dst_port;
+};
+
+struct nb_flow_actions {
+    int32_t queue; /* Negative for no queue action */
+    int32_t drop;
+    int32_t mark;
+    uint32_t mark_id;
+    int32_t count;
+};
+
+struct rte_flow;
+struct rte_flow* create_flow_rule(int port, const struct nb_flow_match* match, const struct nb_flow_actions* actions,
+                                  char* err, int errlen);
+int destroy_flow_rule(int port, struct rte_flow* flow);
+int query_flow_rule_count(int port, struct rte_flow* flow, uint64_t* hits, uint64_t* bytes);
+#endif
@@ -10,15 +10,32 @@
 RTE_DECLARE_PER_LCORE(int, _mempool_core);
 
 typedef struct rte_mbuf* restrict* restrict mbuf_array_t;
+
+/* Occupancy of a mempool, mirrored in Rust (native/zcsi/zcsi.rs). */
+#define MEMPOOL_STATS_NAMESIZE 32
+struct mempool_stats {
+    char name[MEMPOOL_STATS_NAMESIZE];
+    uint32_t size;
+    uint32_t available;
+    uint32_t in_use;
+    int32_t socket;
+};
+
 /* Called by system initialization */
 int init_mempool_core(int core);
-int init_mempool(int master_core, unsigned int mempool_size, unsigned int mcache_size, unsigned short slots);
+int init_mempool(int master_core, unsigned int mempool_size, unsigned int mcache_size, unsigned short slots,
+                 unsigned int data_size, unsigned int headroom);
 int init_secondary_mempool(const char* mempool_name);
 int find_secondary_mempool();
+uint16_t mempool_metadata_slots();
 struct rte_mbuf* mbuf_alloc();
 void mbuf_free(struct rte_mbuf* buf);
 int mbuf_alloc_bulk(mbuf_array_t array, uint16_t len, int cnt);
 int mbuf_free_bulk(mbuf_array_t array, int cnt);
 struct rte_mempool* get_pframe_pool(int coreid, int sid);
 struct rte_mempool* get_mempool_for_core(int coreid);
+struct rte_mempool* create_named_mempool(const char* name, unsigned int size, unsigned int cache_size, int socket);
+struct rte_mempool* find_named_mempool(const char* name);
+void set_alloc_mempool(struct rte_mempool* pool);
+int mempool_stats(struct mempool_stats* stats, int max);
 #endif
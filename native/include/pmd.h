@@ -4,8 +4,14 @@ int num_pmd_ports();
 int get_pmd_ports(struct rte_eth_dev_info* info, int len);
 void enumerate_pmd_ports();
 int init_pmd_port(int port, int rxqs, int txqs, int rxq_core[], int txq_core[], int nrxd, int ntxd,
-                  int loopback, int tso, int csumoffload);
+                  int loopback, int tso, int csumoffload, int timestamp, struct rte_mempool* rxq_pool[]);
 int free_pmd_port(int port);
 int recv_pkts(int port, int qid, mbuf_array_t pkts, int len);
 int send_pkts(int port, int qid, mbuf_array_t pkts, int len);
+int update_rss_hash(int port, int rss_types, int symmetric);
+int rss_reta_size(int port);
+int update_rss_reta(int port, const uint16_t* reta, int len);
+int set_vlan_strip(int port, int on);
+int supported_ptypes(int port);
+int port_socket_id(int port);
 #endif
@@ -171,7 +171,8 @@ int init_secondary(const char* name, int nlen, int core, char* vdevs[], int vdev
 }
 
 int init_system_whitelisted(const char* name, int nlen, int core, char* whitelist[], int wlcount,
-                            unsigned int mempool_size, unsigned int mcache_size, int slots) {
+                            unsigned int mempool_size, unsigned int mcache_size, int slots, unsigned int data_size,
+                            unsigned int headroom) {
     int ret = 0;
     if (name == NULL || nlen >= MAX_NAME_LEN) {
         return -EINVAL;
@@ -184,13 +185,14 @@ int init_system_whitelisted(const char* name, int nlen, int core, char* whitelis
     if ((ret = init_eal(clean_name, 0, core, mempool_size, whitelist, wlcount, NULL, 0)) < 0) {
         return ret;
     }
-    return init_mempool(core, mempool_size, mcache_size, slots);
+    return init_mempool(core, mempool_size, mcache_size, slots, data_size, headroom);
 }
 
 /* Call this from the main thread on ZCSI to initialize things. This initializes
  * the master thread. */
 int init_system(char* name, int nlen, int core, int slots) {
-    return init_system_whitelisted(name, nlen, core, NULL, 0, NUM_PFRAMES, CACHE_SIZE, slots);
+    return init_system_whitelisted(name, nlen, core, NULL, 0, NUM_PFRAMES, CACHE_SIZE, slots, RTE_MBUF_DEFAULT_DATAROOM,
+                                   RTE_PKTMBUF_HEADROOM);
 }
 
 /* Declared within eal_thread.c, but not exposed */
@@ -0,0 +1,346 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <net/if.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/ioctl.h>
+#include <sys/mman.h>
+#include <sys/socket.h>
+#include <sys/uio.h>
+#include <unistd.h>
+
+#include <arpa/inet.h>
+#include <linux/if_ether.h>
+#include <linux/if_packet.h>
+#include <linux/if_tun.h>
+
+#include <rte_config.h>
+#include <rte_log.h>
+#include <rte_mbuf.h>
+#include <rte_memcpy.h>
+
+#include "mempool.h"
+
+/**
+ * Ports backed by Linux network interfaces, either through an AF_PACKET socket (using PACKET_MMAP rings) or through a
+ * TAP device. Packets are copied between mbufs and the kernel, so these are meant for development and control traffic
+ * rather than the data path. Packets larger than an mbuf are received into chained mbufs. Packets that cannot be
+ * received or sent whole (because they do not fit in an AF_PACKET ring frame or no mbufs are available) are dropped
+ * and counted, see kernel_port_drops.
+ **/
+
+/* Large enough for jumbo frames. */
+#define KP_FRAME_SIZE (1 << 14)
+#define KP_BLOCK_SIZE (1 << 16)
+#define KP_FRAMES_PER_BLOCK (KP_BLOCK_SIZE / KP_FRAME_SIZE)
+/* Offset of packet data in TX frames. */
+#define KP_TX_DATA_OFFSET (TPACKET2_HDRLEN - sizeof(struct sockaddr_ll))
+#define KP_MAX_SEGS 64
+/* Largest packet read from or written to a TAP device. */
+#define KP_MAX_PKT_SIZE (1 << 16)
+
+struct kernel_port {
+    int fd;
+    int tap;
+    /* PACKET_MMAP state, the RX ring is followed by the TX ring. */
+    uint8_t *map;
+    size_t map_len;
+    unsigned int frames;
+    unsigned int rx_frame;
+    unsigned int tx_frame;
+    /* Bounce buffer for TAP devices, used to read packets and write packets with too many segments. */
+    uint8_t *buf;
+    uint64_t rx_dropped;
+    uint64_t tx_dropped;
+};
+
+static inline struct tpacket2_hdr *rx_frame(struct kernel_port *port) {
+    return (struct tpacket2_hdr *)(port->map + (size_t)port->rx_frame * KP_FRAME_SIZE);
+}
+
+/* Address information the kernel stores after the header of each RX frame. */
+static inline struct sockaddr_ll *rx_frame_addr(struct tpacket2_hdr *hdr) {
+    return (struct sockaddr_ll *)((uint8_t *)hdr + TPACKET_ALIGN(sizeof(struct tpacket2_hdr)));
+}
+
+static inline struct tpacket2_hdr *tx_frame(struct kernel_port *port) {
+    return (struct tpacket2_hdr *)(port->map + (size_t)(port->frames + port->tx_frame) * KP_FRAME_SIZE);
+}
+
+static int open_af_packet(struct kernel_port *port, const char *ifname, unsigned int frames) {
+    struct tpacket_req req;
+    struct sockaddr_ll sll;
+    int version = TPACKET_V2;
+#ifdef PACKET_IGNORE_OUTGOING
+    int ignore = 1;
+#endif
+    int ifindex = if_nametoindex(ifname);
+
+    if (ifindex == 0) {
+        return -ENODEV;
+    }
+    port->fd = socket(AF_PACKET, SOCK_RAW, htons(ETH_P_ALL));
+    if (port->fd < 0) {
+        return -errno;
+    }
+    if (setsockopt(port->fd, SOL_PACKET, PACKET_VERSION, &version, sizeof(version)) < 0) {
+        return -errno;
+    }
+#ifdef PACKET_IGNORE_OUTGOING
+    /* Packets we send are otherwise looped back to us, af_packet_recv also skips them for older kernels. */
+    setsockopt(port->fd, SOL_PACKET, PACKET_IGNORE_OUTGOING, &ignore, sizeof(ignore));
+#endif
+
+    memset(&req, 0, sizeof(req));
+    req.tp_block_size = KP_BLOCK_SIZE;
+    req.tp_block_nr   = (frames + KP_FRAMES_PER_BLOCK - 1) / KP_FRAMES_PER_BLOCK;
+    req.tp_frame_size = KP_FRAME_SIZE;
+    req.tp_frame_nr   = req.tp_block_nr * KP_FRAMES_PER_BLOCK;
+    if (setsockopt(port->fd, SOL_PACKET, PACKET_RX_RING, &req, sizeof(req)) < 0 ||
+        setsockopt(port->fd, SOL_PACKET, PACKET_TX_RING, &req, sizeof(req)) < 0) {
+        return -errno;
+    }
+    port->frames  = req.tp_frame_nr;
+    port->map_len = 2 * (size_t)req.tp_block_size * req.tp_block_nr;
+    port->map     = mmap(NULL, port->map_len, PROT_READ | PROT_WRITE, MAP_SHARED, port->fd, 0);
+    if (port->map == MAP_FAILED) {
+        port->map = NULL;
+        return -errno;
+    }
+
+    memset(&sll, 0, sizeof(sll));
+    sll.sll_family   = AF_PACKET;
+    sll.sll_protocol = htons(ETH_P_ALL);
+    sll.sll_ifindex  = ifindex;
+    if (bind(port->fd, (struct sockaddr *)&sll, sizeof(sll)) < 0) {
+        return -errno;
+    }
+    return 0;
+}
+
+static int open_tap(struct kernel_port *port, const char *ifname) {
+    struct ifreq ifr;
+    int sock;
+
+    port->buf = malloc(KP_MAX_PKT_SIZE);
+    if (port->buf == NULL) {
+        return -ENOMEM;
+    }
+    port->fd = open("/dev/net/tun", O_RDWR | O_NONBLOCK);
+    if (port->fd < 0) {
+        return -errno;
+    }
+    memset(&ifr, 0, sizeof(ifr));
+    ifr.ifr_flags = IFF_TAP | IFF_NO_PI;
+    snprintf(ifr.ifr_name, IFNAMSIZ, "%s", ifname);
+    if (ioctl(port->fd, TUNSETIFF, &ifr) < 0) {
+        return -errno;
+    }
+
+    /* Bring the interface up so the kernel stack uses it. */
+    sock = socket(AF_INET, SOCK_DGRAM, 0);
+    if (sock >= 0) {
+        if (ioctl(sock, SIOCGIFFLAGS, &ifr) == 0) {
+            ifr.ifr_flags |= IFF_UP;
+            if (ioctl(sock, SIOCSIFFLAGS, &ifr) < 0) {
+                RTE_LOG(WARNING, PMD, "Could not bring up %s\n", ifname);
+            }
+        }
+        close(sock);
+    }
+    return 0;
+}
+
+void kernel_port_close(struct kernel_port *port) {
+    if (port->map != NULL) {
+        munmap(port->map, port->map_len);
+    }
+    if (port->fd >= 0) {
+        close(port->fd);
+    }
+    free(port->buf);
+    free(port);
+}
+
+/* Open a port on interface ifname, creating a TAP device if tap is set. AF_PACKET rings have room for at least frames
+ * packets in each direction. */
+struct kernel_port *kernel_port_open(const char *ifname, int tap, unsigned int frames) {
+    int ret;
+    struct kernel_port *port = calloc(1, sizeof(struct kernel_port));
+    if (port == NULL) {
+        return NULL;
+    }
+    port->fd  = -1;
+    port->tap = tap;
+    ret       = tap ? open_tap(port, ifname) : open_af_packet(port, ifname, frames);
+    if (ret != 0) {
+        RTE_LOG(WARNING, PMD, "Could not open %s: %s\n", ifname, strerror(-ret));
+        kernel_port_close(port);
+        return NULL;
+    }
+    return port;
+}
+
+/* Number of packets dropped on receipt and transmission since the port was opened. */
+void kernel_port_drops(struct kernel_port *port, uint64_t *rx, uint64_t *tx) {
+    *rx = __atomic_load_n(&port->rx_dropped, __ATOMIC_RELAXED);
+    *tx = __atomic_load_n(&port->tx_dropped, __ATOMIC_RELAXED);
+}
+
+static inline void count_drop(uint64_t *counter) {
+    __atomic_fetch_add(counter, 1, __ATOMIC_RELAXED);
+}
+
+/* Copy len bytes into newly allocated mbufs, chaining as many as needed. Returns NULL if we run out of mbufs. */
+static struct rte_mbuf *copy_to_mbufs(const uint8_t *data, uint32_t len) {
+    struct rte_mbuf *head = mbuf_alloc();
+    uint32_t off          = 0;
+    if (head == NULL) {
+        return NULL;
+    }
+    for (;;) {
+        struct rte_mbuf *seg;
+        uint16_t copy = RTE_MIN(len - off, (uint32_t)rte_pktmbuf_tailroom(rte_pktmbuf_lastseg(head)));
+        rte_memcpy(rte_pktmbuf_append(head, copy), data + off, copy);
+        off += copy;
+        if (off == len) {
+            return head;
+        }
+        seg = mbuf_alloc();
+        if (seg == NULL || rte_pktmbuf_chain(head, seg) != 0) {
+            if (seg != NULL) {
+                rte_pktmbuf_free(seg);
+            }
+            rte_pktmbuf_free(head);
+            return NULL;
+        }
+    }
+}
+
+static int af_packet_recv(struct kernel_port *port, mbuf_array_t pkts, int len) {
+    int n = 0;
+    while (n < len) {
+        struct tpacket2_hdr *hdr = rx_frame(port);
+        struct rte_mbuf *mbuf    = NULL;
+        if (!(hdr->tp_status & TP_STATUS_USER)) {
+            break;
+        }
+        if (rx_frame_addr(hdr)->sll_pkttype == PACKET_OUTGOING) {
+            /* Our own transmissions, looped back by the kernel. */
+        } else if (hdr->tp_snaplen < hdr->tp_len) {
+            /* Larger than a ring frame, only part of the packet is available. */
+            count_drop(&port->rx_dropped);
+        } else {
+            mbuf = copy_to_mbufs((uint8_t *)hdr + hdr->tp_mac, hdr->tp_snaplen);
+            if (mbuf == NULL) {
+                /* Leave the frame for the next call. */
+                break;
+            }
+            /* The kernel strips VLAN tags, report them the same way NICs that strip tags do. */
+            if (hdr->tp_status & TP_STATUS_VLAN_VALID) {
+                mbuf->vlan_tci = hdr->tp_vlan_tci;
+                mbuf->ol_flags |= PKT_RX_VLAN_PKT | PKT_RX_VLAN_STRIPPED;
+            }
+            pkts[n++] = mbuf;
+        }
+        rte_smp_mb();
+        hdr->tp_status = TP_STATUS_KERNEL;
+        port->rx_frame = (port->rx_frame + 1) % port->frames;
+    }
+    return n;
+}
+
+static int af_packet_send(struct kernel_port *port, mbuf_array_t pkts, int len) {
+    int n;
+    for (n = 0; n < len; n++) {
+        struct tpacket2_hdr *hdr = tx_frame(port);
+        uint8_t *data            = (uint8_t *)hdr + KP_TX_DATA_OFFSET;
+        uint32_t pkt_len         = rte_pktmbuf_pkt_len(pkts[n]);
+        if (hdr->tp_status != TP_STATUS_AVAILABLE && hdr->tp_status != TP_STATUS_WRONG_FORMAT) {
+            break;
+        }
+        /* Packets that do not fit in a frame are dropped. */
+        if (pkt_len > KP_FRAME_SIZE - KP_TX_DATA_OFFSET) {
+            count_drop(&port->tx_dropped);
+        } else {
+            const void *src = rte_pktmbuf_read(pkts[n], 0, pkt_len, data);
+            if (src != data) {
+                rte_memcpy(data, src, pkt_len);
+            }
+            hdr->tp_len = pkt_len;
+            rte_smp_mb();
+            hdr->tp_status = TP_STATUS_SEND_REQUEST;
+            port->tx_frame = (port->tx_frame + 1) % port->frames;
+        }
+        rte_pktmbuf_free(pkts[n]);
+    }
+    if (n > 0) {
+        sendto(port->fd, NULL, 0, MSG_DONTWAIT, NULL, 0);
+    }
+    return n;
+}
+
+static int tap_recv(struct kernel_port *port, mbuf_array_t pkts, int len) {
+    int n = 0;
+    while (n < len) {
+        struct rte_mbuf *mbuf;
+        ssize_t ret = read(port->fd, port->buf, KP_MAX_PKT_SIZE);
+        if (ret <= 0) {
+            break;
+        }
+        mbuf = copy_to_mbufs(port->buf, (uint32_t)ret);
+        if (mbuf == NULL) {
+            /* The packet has already been taken from the device. */
+            count_drop(&port->rx_dropped);
+            break;
+        }
+        pkts[n++] = mbuf;
+    }
+    return n;
+}
+
+static int tap_send(struct kernel_port *port, mbuf_array_t pkts, int len) {
+    int n;
+    for (n = 0; n < len; n++) {
+        ssize_t ret;
+        if (pkts[n]->nb_segs <= KP_MAX_SEGS) {
+            struct iovec iov[KP_MAX_SEGS];
+            struct rte_mbuf *seg;
+            int segs = 0;
+            for (seg = pkts[n]; seg != NULL; seg = seg->next, segs++) {
+                iov[segs].iov_base = rte_pktmbuf_mtod(seg, void *);
+                iov[segs].iov_len  = seg->data_len;
+            }
+            ret = writev(port->fd, iov, segs);
+        } else {
+            /* Too many segments for one writev, copy the packet out first. */
+            uint32_t pkt_len = RTE_MIN(rte_pktmbuf_pkt_len(pkts[n]), KP_MAX_PKT_SIZE);
+            const void *src  = rte_pktmbuf_read(pkts[n], 0, pkt_len, port->buf);
+            ret              = write(port->fd, src, pkt_len);
+        }
+        if (ret < 0) {
+            if (errno == EAGAIN) {
+                break;
+            }
+            /* Other errors drop the packet. */
+            count_drop(&port->tx_dropped);
+        }
+        rte_pktmbuf_free(pkts[n]);
+    }
+    return n;
+}
+
+/* Receive up to len packets, copying them into newly allocated mbufs. */
+int kernel_port_recv(struct kernel_port *port, mbuf_array_t pkts, int len) {
+    return port->tap ? tap_recv(port, pkts, len) : af_packet_recv(port, pkts, len);
+}
+
+/* Send up to len packets, returning the number of mbufs consumed (and freed). *dropped is set to the number of
+ * consumed packets that were dropped rather than sent. */
+int kernel_port_send(struct kernel_port *port, mbuf_array_t pkts, int len, int *dropped) {
+    uint64_t before = port->tx_dropped;
+    int sent        = port->tap ? tap_send(port, pkts, len) : af_packet_send(port, pkts, len);
+    *dropped        = (int)(port->tx_dropped - before);
+    return sent;
+}
@@ -23,6 +23,12 @@ _Static_assert(METADATA_SLOT_SIZE % RTE_MBUF_PRIV_ALIGN == 0,
 
 RTE_DEFINE_PER_LCORE(int, _mempool_core) = 0;
 
+/* Size of the data buffer in each mbuf (including headroom), and headroom left in front of data in mbufs we
+ * allocate. PMDs always leave RTE_PKTMBUF_HEADROOM in front of received packets. */
+static uint16_t mbuf_data_room = RTE_MBUF_DEFAULT_BUF_SIZE;
+static uint16_t mbuf_headroom  = RTE_PKTMBUF_HEADROOM;
+static unsigned short pool_metadata_slots;
+
 #if PER_CORE
 /* Creating one pool per core. */
 static struct rte_mempool *pframe_pool[RTE_MAX_LCORE];
@@ -50,6 +56,17 @@ static inline struct rte_mempool *current_pframe_pool() {
     return pframe_pool[MEMPOOL_ID];
 }
 
+/* Mempool that packets allocated by this thread come from instead of the default pool, if set. */
+static __thread struct rte_mempool *alloc_pool;
+
+static inline struct rte_mempool *current_alloc_pool() {
+    return alloc_pool != NULL ? alloc_pool : current_pframe_pool();
+}
+
+void set_alloc_mempool(struct rte_mempool *pool) {
+    alloc_pool = pool;
+}
+
 static inline struct rte_mbuf *current_template() {
     return &mbuf_template[MEMPOOL_ID];
 }
@@ -65,8 +82,7 @@ int init_mempool_core(int core) {
     sprintf(name, "pframe%d", core);
     sid               = rte_lcore_to_socket_id(core);
     pframe_pool[core] = rte_pktmbuf_pool_create(name, core_mempool_size, core_mempool_cache_size,
-                                                core_metadata_slots * METADATA_SLOT_SIZE,
-                                                RTE_MBUF_DEFAULT_BUF_SIZE, sid);
+                                                core_metadata_slots * METADATA_SLOT_SIZE, mbuf_data_room, sid);
     if (pframe_pool[core] == NULL) {
         return -ENOMEM;
     }
@@ -98,11 +114,20 @@ static int init_mempool_socket(int sid, unsigned int mempool_size, unsigned int
     char name[256];
     sprintf(name, "pframe%d", sid);
     pframe_pool[sid] = rte_pktmbuf_pool_create(name, mempool_size, mcache_size, metadata_slots * METADATA_SLOT_SIZE,
-                                               RTE_MBUF_DEFAULT_BUF_SIZE, sid);
+                                               mbuf_data_room, sid);
     return pframe_pool[sid] != NULL;
 }
 
-int init_mempool(int master_core, unsigned int mempool_size, unsigned int mcache_size, unsigned short metadata_slots) {
+/* data_size is the number of bytes of packet data each mbuf can hold, headroom is reserved in front of it. */
+int init_mempool(int master_core, unsigned int mempool_size, unsigned int mcache_size, unsigned short metadata_slots,
+                 unsigned int data_size, unsigned int headroom) {
+    unsigned int room = RTE_MAX(headroom, (unsigned int)RTE_PKTMBUF_HEADROOM) + data_size;
+    if (room > UINT16_MAX || (metadata_slots * METADATA_SLOT_SIZE) > UINT16_MAX) {
+        return -EINVAL;
+    }
+    mbuf_data_room      = (uint16_t)room;
+    mbuf_headroom       = (uint16_t)headroom;
+    pool_metadata_slots = metadata_slots;
 #if (!PER_CORE)
     int initialized[RTE_MAX_NUMA_NODES];
     for (int i = 0; i < RTE_MAX_NUMA_NODES; i++) {
@@ -140,6 +165,44 @@ fail:
 #endif
 }
 
+/* Create an additional mempool, with the same mbuf layout as the default pools, that can be assigned to RX queues. A
+ * negative socket lets DPDK pick. */
+struct rte_mempool *create_named_mempool(const char *name, unsigned int size, unsigned int cache_size, int socket) {
+    return rte_pktmbuf_pool_create(name, size, cache_size, pool_metadata_slots * METADATA_SLOT_SIZE, mbuf_data_room,
+                                   socket < 0 ? SOCKET_ID_ANY : socket);
+}
+
+struct rte_mempool *find_named_mempool(const char *name) {
+    return rte_mempool_lookup(name);
+}
+
+struct mempool_stats_walk {
+    struct mempool_stats *stats;
+    int max;
+    int count;
+};
+
+static void mempool_stats_helper(struct rte_mempool *mp, void *ptr) {
+    struct mempool_stats_walk *walk = ptr;
+    struct mempool_stats *stats;
+    if (walk->count < walk->max) {
+        stats = &walk->stats[walk->count];
+        snprintf(stats->name, sizeof(stats->name), "%s", mp->name);
+        stats->size      = mp->size;
+        stats->available = rte_mempool_avail_count(mp);
+        stats->in_use    = rte_mempool_in_use_count(mp);
+        stats->socket    = mp->socket_id;
+    }
+    walk->count++;
+}
+
+/* Fill in occupancy of up to max mempools, returns the total number of mempools (which may be larger than max). */
+int mempool_stats(struct mempool_stats *stats, int max) {
+    struct mempool_stats_walk walk = {.stats = stats, .max = max, .count = 0};
+    rte_mempool_walk(&mempool_stats_helper, (void *)&walk);
+    return walk.count;
+}
+
 static void set_mempool(struct rte_mempool *mempool) {
 #if (!PER_CORE)
     int initialized[RTE_MAX_NUMA_NODES];
@@ -215,7 +278,16 @@ int init_secondary_mempool(const char *mempool_name) {
 }
 
 struct rte_mbuf *mbuf_alloc() {
-    return rte_pktmbuf_alloc(current_pframe_pool());
+    struct rte_mbuf *mbuf = rte_pktmbuf_alloc(current_alloc_pool());
+    if (mbuf != NULL) {
+        mbuf->data_off = RTE_MIN(mbuf_headroom, mbuf->buf_len);
+    }
+    return mbuf;
+}
+
+/* Number of metadata slots in mbufs from the calling thread's mempool. */
+uint16_t mempool_metadata_slots() {
+    return rte_pktmbuf_priv_size(current_pframe_pool()) / METADATA_SLOT_SIZE;
 }
 
 void mbuf_free(struct rte_mbuf *buf) {
@@ -245,7 +317,7 @@ int mbuf_alloc_bulk(mbuf_array_t array, uint16_t len, int cnt) {
      */
     rxdesc_fields = _mm_setr_epi32(0, len, len, 0);
 
-    ret = rte_mempool_get_bulk(current_pframe_pool(), (void **)array, cnt);
+    ret = rte_mempool_get_bulk(current_alloc_pool(), (void **)array, cnt);
     if (ret != 0) {
         return ret;
     }
@@ -268,6 +340,9 @@ int mbuf_alloc_bulk(mbuf_array_t array, uint16_t len, int cnt) {
 
         _mm_store_si128((__m128i *)&mbuf1->buf_len, template);
         _mm_store_si128((__m128i *)&mbuf1->packet_type, rxdesc_fields);
+
+        mbuf0->data_off = mbuf_headroom;
+        mbuf1->data_off = mbuf_headroom;
     }
 
     if (cnt & 1)
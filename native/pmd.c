@@ -7,6 +7,19 @@
 #define HW_RXCSUM 0
 #define HW_TXCSUM 0
 #define MIN(a, b) ((a) < (b) ? (a) : (b))
+
+/* Header fields that can be used for RSS, these must match RssHash in the framework. */
+#define NB_RSS_IPV4 0x1
+#define NB_RSS_IPV6 0x2
+#define NB_RSS_TCP 0x4
+#define NB_RSS_UDP 0x8
+#define NB_RSS_SCTP 0x10
+#define MAX_RSS_KEY_LEN 64
+
+/* Key the driver configured for each port, saved before a symmetric key first replaces it so it can be restored. */
+static uint8_t default_rss_key[RTE_MAX_ETHPORTS][MAX_RSS_KEY_LEN];
+static uint8_t default_rss_key_len[RTE_MAX_ETHPORTS];
+
 static const struct rte_eth_conf default_eth_conf = {
     .link_speeds = ETH_LINK_SPEED_AUTONEG, /* auto negotiate speed */
     /*.link_duplex = ETH_LINK_AUTONEG_DUPLEX,	[> auto negotiation duplex <]*/
@@ -111,8 +124,16 @@ void enumerate_pmd_ports() {
     }
 }
 
+/* Mempool for an RX queue: the one given in rxq_pool (if any), otherwise the default pool for the polling core. */
+static struct rte_mempool* rxq_mempool(struct rte_mempool* rxq_pool[], int rxq_core[], int i) {
+    if (rxq_pool != NULL && rxq_pool[i] != NULL) {
+        return rxq_pool[i];
+    }
+    return get_pframe_pool(rxq_core[i], rte_lcore_to_socket_id(rxq_core[i]));
+}
+
 int init_pmd_port(int port, int rxqs, int txqs, int rxq_core[], int txq_core[], int nrxd, int ntxd,
-                  int loopback, int tso, int csumoffload) {
+                  int loopback, int tso, int csumoffload, int timestamp, struct rte_mempool* rxq_pool[]) {
     struct rte_eth_dev_info dev_info = {};
     struct rte_eth_conf eth_conf;
     struct rte_eth_rxconf eth_rxconf;
@@ -129,12 +150,50 @@ int init_pmd_port(int port, int rxqs, int txqs, int rxq_core[], int txq_core[],
 
     eth_conf           = default_eth_conf;
     eth_conf.lpbk_mode = !(!loopback);
+    /* Verify checksums on RX, so received packets carry checksum flags. On TX csumoffload only allows packets to
+     * request checksum offload, it is not applied otherwise. */
+    eth_conf.rxmode.hw_ip_checksum = !(!csumoffload);
 
     /* Use defaut rx/tx configuration as provided by PMD drivers,
      * with minor tweaks */
     rte_eth_dev_info_get(port, &dev_info);
 
+    /* Accept frames as large as our mbufs can hold when they are configured larger than a standard Ethernet frame. */
+    if (rxqs > 0) {
+        struct rte_mempool* pool = rxq_mempool(rxq_pool, rxq_core, 0);
+        uint32_t max_len         = rte_pktmbuf_data_room_size(pool) - RTE_PKTMBUF_HEADROOM;
+        if (max_len > ETHER_MAX_LEN) {
+            eth_conf.rxmode.jumbo_frame    = 1;
+            eth_conf.rxmode.max_rx_pkt_len = MIN(max_len, dev_info.max_rx_pktlen);
+        }
+    }
+
+    /* Hardware timestamps can only be requested through the offloads field, which replaces the rxmode bitfields. */
+    if (timestamp) {
+#ifdef DEV_RX_OFFLOAD_TIMESTAMP
+        if (!(dev_info.rx_offload_capa & DEV_RX_OFFLOAD_TIMESTAMP)) {
+            printf("Port %d cannot timestamp received packets\n", port);
+            return -ENOTSUP;
+        }
+        eth_conf.rxmode.ignore_offload_bitfield = 1;
+        eth_conf.rxmode.offloads = DEV_RX_OFFLOAD_TIMESTAMP | DEV_RX_OFFLOAD_CRC_STRIP;
+        if (eth_conf.rxmode.hw_ip_checksum) {
+            eth_conf.rxmode.offloads |= DEV_RX_OFFLOAD_CHECKSUM;
+        }
+        if (eth_conf.rxmode.jumbo_frame) {
+            eth_conf.rxmode.offloads |= DEV_RX_OFFLOAD_JUMBO_FRAME;
+        }
+#else
+        printf("RX timestamps need DPDK 17.11 or later\n");
+        return -ENOTSUP;
+#endif
+    }
+
     eth_rxconf = dev_info.default_rxconf;
+#ifdef DEV_RX_OFFLOAD_TIMESTAMP
+    /* Queues must enable at least the offloads enabled for the port. */
+    eth_rxconf.offloads = eth_conf.rxmode.offloads;
+#endif
     if (strcmp(dev_info.driver_name, "rte_em_pmd") != 0 &&
         strcmp(dev_info.driver_name, "net_e1000_em") != 0) {
         /* Drop packets when no descriptors are available
@@ -159,7 +218,7 @@ int init_pmd_port(int port, int rxqs, int txqs, int rxq_core[], int txq_core[],
 
     for (i = 0; i < rxqs; i++) {
         int sid = rte_lcore_to_socket_id(rxq_core[i]);
-        ret = rte_eth_rx_queue_setup(port, i, nrxd, sid, &eth_rxconf, get_pframe_pool(rxq_core[i], sid));
+        ret = rte_eth_rx_queue_setup(port, i, nrxd, sid, &eth_rxconf, rxq_mempool(rxq_pool, rxq_core, i));
         if (ret != 0) {
             printf("Failed to initialize rxq\n");
             return ret; /* Clean things up? */
@@ -184,7 +243,13 @@ int init_pmd_port(int port, int rxqs, int txqs, int rxq_core[], int txq_core[],
     return 0;
 }
 
+/* NUMA node the port is attached to, -1 if unknown. */
+int port_socket_id(int port) {
+    return rte_eth_dev_socket_id(port);
+}
+
 void free_pmd_port(int port) {
+    default_rss_key_len[port] = 0;
     rte_eth_dev_stop(port);
     rte_eth_dev_close(port);
 }
@@ -255,5 +320,135 @@ int attach_pmd_device(const char* devname) {
     return (int)port;
 }
 
-/* FIXME: Add function to modify RSS hash function using
- * rte_eth_dev_rss_hash_update */
+/* Toeplitz key with a repeating 16 bit pattern, this results in the same hash (and hence the same queue) for both
+ * directions of a flow. See Woo and Park, "Scalable TCP Session Monitoring with Symmetric Receive-side Scaling". */
+static uint8_t symmetric_rss_key[MAX_RSS_KEY_LEN] = {
+    0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a,
+    0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a,
+    0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a,
+    0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a, 0x6d, 0x5a,
+};
+
+static int save_default_rss_key(int port, const struct rte_eth_dev_info* dev_info) {
+    struct rte_eth_rss_conf rss_conf = {};
+    int ret;
+
+    if (default_rss_key_len[port] != 0) {
+        return 0;
+    }
+    if (dev_info->hash_key_size == 0 || dev_info->hash_key_size > MAX_RSS_KEY_LEN) {
+        return -ENOTSUP;
+    }
+    rss_conf.rss_key     = default_rss_key[port];
+    rss_conf.rss_key_len = dev_info->hash_key_size;
+    ret                  = rte_eth_dev_rss_hash_conf_get(port, &rss_conf);
+    if (ret == 0) {
+        default_rss_key_len[port] = dev_info->hash_key_size;
+    }
+    return ret;
+}
+
+static uint64_t rss_hf_from_types(int rss_types) {
+    uint64_t rss_hf = 0;
+    if (rss_types == 0) {
+        return default_eth_conf.rx_adv_conf.rss_conf.rss_hf;
+    }
+    if (rss_types & NB_RSS_IPV4) {
+        rss_hf |= ETH_RSS_IPV4 | ETH_RSS_FRAG_IPV4 | ETH_RSS_NONFRAG_IPV4_OTHER;
+    }
+    if (rss_types & NB_RSS_IPV6) {
+        rss_hf |= ETH_RSS_IPV6 | ETH_RSS_FRAG_IPV6 | ETH_RSS_NONFRAG_IPV6_OTHER | ETH_RSS_IPV6_EX;
+    }
+    if (rss_types & NB_RSS_TCP) {
+        rss_hf |= ETH_RSS_TCP;
+    }
+    if (rss_types & NB_RSS_UDP) {
+        rss_hf |= ETH_RSS_UDP;
+    }
+    if (rss_types & NB_RSS_SCTP) {
+        rss_hf |= ETH_RSS_SCTP;
+    }
+    return rss_hf;
+}
+
+/* Change the set of header fields hashed by RSS, and switch to a symmetric key or back to the driver's key. rss_types
+ * is a combination of NB_RSS_* flags, 0 selects the default set of fields. */
+int update_rss_hash(int port, int rss_types, int symmetric) {
+    struct rte_eth_dev_info dev_info = {};
+    struct rte_eth_rss_conf rss_conf = {};
+
+    if (port >= RTE_MAX_ETHPORTS || rte_eth_devices[port].state != RTE_ETH_DEV_ATTACHED) {
+        return -ENODEV;
+    }
+    rte_eth_dev_info_get(port, &dev_info);
+    /* Only ask for hashes the NIC can compute, otherwise the update is rejected. None at all would turn RSS off. */
+    rss_conf.rss_hf = rss_hf_from_types(rss_types) & dev_info.flow_type_rss_offloads;
+    if (rss_conf.rss_hf == 0) {
+        return -ENOTSUP;
+    }
+    if (symmetric) {
+        int ret = save_default_rss_key(port, &dev_info);
+        if (ret != 0) {
+            return ret;
+        }
+        /* The key was saved, so the NIC's key size is known to fit. */
+        rss_conf.rss_key     = symmetric_rss_key;
+        rss_conf.rss_key_len = dev_info.hash_key_size;
+    } else if (default_rss_key_len[port] != 0) {
+        rss_conf.rss_key     = default_rss_key[port];
+        rss_conf.rss_key_len = default_rss_key_len[port];
+    }
+    return rte_eth_dev_rss_hash_update(port, &rss_conf);
+}
+
+/* Size of the RSS redirection table (RETA), or a negative value if the port has none. */
+int rss_reta_size(int port) {
+    struct rte_eth_dev_info info;
+    if (get_rte_eth_dev_info(port, &info) != 0) {
+        return -ENODEV;
+    } else if (info.reta_size == 0) {
+        return -ENOTSUP;
+    } else {
+        return info.reta_size;
+    }
+}
+
+/* Update the RSS redirection table: hash bucket i is steered to queue reta[i % len]. */
+int update_rss_reta(int port, const uint16_t* reta, int len) {
+    struct rte_eth_rss_reta_entry64 reta_conf[ETH_RSS_RETA_SIZE_512 / RTE_RETA_GROUP_SIZE];
+    int reta_size = rss_reta_size(port);
+
+    if (reta_size < 0) {
+        return reta_size;
+    }
+    if (len <= 0 || reta_size > ETH_RSS_RETA_SIZE_512) {
+        return -EINVAL;
+    }
+    memset(reta_conf, 0, sizeof(reta_conf));
+    for (int i = 0; i < reta_size; i++) {
+        int group = i / RTE_RETA_GROUP_SIZE;
+        int shift = i % RTE_RETA_GROUP_SIZE;
+        reta_conf[group].mask |= (1ULL << shift);
+        reta_conf[group].reta[shift] = reta[i % len];
+    }
+    return rte_eth_dev_rss_reta_update(port, reta_conf, reta_size);
+}
+
+/* Enable or disable stripping of VLAN tags by the NIC, stripped tags are reported in the mbuf. */
+int set_vlan_strip(int port, int on) {
+    int offload = rte_eth_dev_get_vlan_offload(port);
+    if (offload < 0) {
+        return offload;
+    }
+    if (on) {
+        offload |= ETH_VLAN_STRIP_OFFLOAD;
+    } else {
+        offload &= ~ETH_VLAN_STRIP_OFFLOAD;
+    }
+    return rte_eth_dev_set_vlan_offload(port, offload);
+}
+
+/* Number of L3 and L4 packet types the port can classify received packets into. */
+int supported_ptypes(int port) {
+    return rte_eth_dev_get_supported_ptypes(port, RTE_PTYPE_L3_MASK | RTE_PTYPE_L4_MASK, NULL, 0);
+}
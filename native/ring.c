@@ -118,3 +118,29 @@ static int init_ovs_ring(int iface, struct rte_mempool *mempool) {
 int init_ovs_eth_ring(int iface, int core) {
     return init_ovs_ring(iface, get_mempool_for_core(core));
 }
+
+/* Named rings used to pass mbufs between NetBricks processes (or pipelines in one process). Mbufs are passed by
+ * reference, so all users must share the mempools they were allocated from. Rings are multi-producer and
+ * multi-consumer, so any number of pipelines (in any process) can attach to either side. */
+struct rte_ring *nb_ring(const char *name, unsigned int count, int socket, int create) {
+    struct rte_ring *ring = rte_ring_lookup(name);
+    if (ring == NULL && create) {
+        ring = rte_ring_create(name, count, socket < 0 ? SOCKET_ID_ANY : socket, 0);
+        if (ring == NULL) {
+            /* Another process might have created it since we looked. */
+            ring = rte_ring_lookup(name);
+        }
+    }
+    if (ring == NULL) {
+        RTE_LOG(WARNING, PMD, "Could not %s ring %s\n", create ? "create" : "find", name);
+    }
+    return ring;
+}
+
+int ring_enqueue_pkts(struct rte_ring *ring, mbuf_array_t pkts, int len) {
+    return rte_ring_enqueue_burst(ring, (void **)pkts, len, NULL);
+}
+
+int ring_dequeue_pkts(struct rte_ring *ring, mbuf_array_t pkts, int len) {
+    return rte_ring_dequeue_burst(ring, (void **)pkts, len, NULL);
+}
@@ -1,6 +1,9 @@
 #include <rte_config.h>
+#include <rte_cycles.h>
 #include <rte_hash_crc.h>
 #include <rte_ip.h>
+#include <rte_mbuf.h>
+#include <rte_tcp.h>
 
 // Make rte_hash_crc available to Rust. This adds some cost, will look into producing a pure Rust
 // version.
@@ -11,3 +14,23 @@ uint32_t crc_hash_native(const void* data, uint32_t len, uint32_t initial) {
 uint16_t ipv4_cksum(const void* iphdr) {
     return rte_ipv4_cksum((const struct ipv4_hdr*)iphdr);
 }
+
+/* Mark a TCP/IPv4 packet for TSO: the NIC splits it into segments of at most mss bytes of payload. Per DPDK's
+ * requirements, the IP checksum is zeroed and the TCP checksum is seeded with the pseudo-header checksum. */
+void mbuf_set_tso(struct rte_mbuf* m, uint16_t l2_len, uint16_t l3_len, uint16_t l4_len, uint16_t mss) {
+    struct ipv4_hdr* ip = rte_pktmbuf_mtod_offset(m, struct ipv4_hdr*, l2_len);
+    struct tcp_hdr* tcp = rte_pktmbuf_mtod_offset(m, struct tcp_hdr*, l2_len + l3_len);
+
+    m->l2_len    = l2_len;
+    m->l3_len    = l3_len;
+    m->l4_len    = l4_len;
+    m->tso_segsz = mss;
+    m->ol_flags |= PKT_TX_TCP_SEG | PKT_TX_IPV4 | PKT_TX_IP_CKSUM;
+    ip->hdr_checksum = 0;
+    tcp->cksum       = rte_ipv4_phdr_cksum(ip, m->ol_flags);
+}
+
+/* TSC frequency as calibrated by the EAL, 0 before the EAL is initialized. */
+uint64_t tsc_frequency(void) {
+    return rte_get_tsc_hz();
+}
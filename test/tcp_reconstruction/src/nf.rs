@@ -1,6 +1,5 @@
 use e2d2::headers::*;
 use e2d2::operators::*;
-use e2d2::scheduler::*;
 use e2d2::state::*;
 use e2d2::utils::Flow;
 use fnv::FnvHasher;
@@ -12,26 +11,17 @@ type FnvHash = BuildHasherDefault<FnvHasher>;
 const BUFFER_SIZE: usize = 2048;
 const PRINT_SIZE: usize = 256;
 
-pub fn reconstruction<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
-    parent: T,
-    sched: &mut S,
-) -> CompositionBatch {
+pub fn reconstruction<T: 'static + Batch<Header = NullHeader>>(parent: T) -> CompositionBatch {
     let mut cache = HashMap::<Flow, ReorderedBuffer, FnvHash>::with_hasher(Default::default());
     let mut read_buf: Vec<u8> = (0..PRINT_SIZE).map(|_| 0).collect();
-    let mut groups = parent
+    let (tcp, other) = parent
         .parse::<MacHeader>()
         .transform(box move |p| {
             p.get_mut_header().swap_addresses();
         })
         .parse::<IpHeader>()
-        .group_by(
-            2,
-            box move |p| if p.get_header().protocol() == 6 { 0 } else { 1 },
-            sched,
-        );
-    let pipe = groups
-        .get_group(0)
-        .unwrap()
+        .partition(box move |p| p.get_header().protocol() == 6);
+    let pipe = tcp
         .metadata(box move |p| {
             let flow = p.get_header().flow().unwrap();
             flow
@@ -97,5 +87,5 @@ pub fn reconstruction<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Si
             }
         })
         .compose();
-    merge(vec![pipe, groups.get_group(1).unwrap().compose()]).compose()
+    merge(vec![pipe, other.compose()]).compose()
 }
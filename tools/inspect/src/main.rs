@@ -0,0 +1,241 @@
+extern crate e2d2;
+extern crate getopts;
+use e2d2::shared_state::directory::*;
+use e2d2::utils::Flow;
+use getopts::Options;
+use std::env;
+use std::mem::size_of;
+use std::net::Ipv4Addr;
+use std::process;
+use std::ptr;
+use std::thread;
+use std::time::Duration;
+
+/// A key or value copied out of shared memory.
+enum Field {
+    None,
+    Int(u64),
+    Text(String),
+}
+
+/// Contents of a directory entry, read from a consistent snapshot.
+struct Dump {
+    info: EntryInfo,
+    records: Vec<(Field, Field)>,
+}
+
+fn format_flow(flow: &Flow) -> String {
+    // Copy fields out of the packed struct before formatting them.
+    let (src_ip, src_port, dst_ip, dst_port, proto) =
+        (flow.src_ip, flow.src_port, flow.dst_ip, flow.dst_port, flow.proto);
+    format!(
+        "{}:{} -> {}:{} ({})",
+        Ipv4Addr::from(src_ip),
+        src_port,
+        Ipv4Addr::from(dst_ip),
+        dst_port,
+        proto
+    )
+}
+
+/// Decode raw bytes without knowing their type: flows are recognized by their size, small values are read as
+/// integers, everything else is shown as hex.
+fn decode(bytes: &[u8]) -> Field {
+    unsafe {
+        match bytes.len() {
+            0 => Field::None,
+            1 => Field::Int(bytes[0] as u64),
+            2 => Field::Int(ptr::read_unaligned(bytes.as_ptr() as *const u16) as u64),
+            4 => Field::Int(ptr::read_unaligned(bytes.as_ptr() as *const u32) as u64),
+            8 => Field::Int(ptr::read_unaligned(bytes.as_ptr() as *const u64)),
+            len if len == size_of::<Flow>() => {
+                Field::Text(format_flow(&ptr::read_unaligned(bytes.as_ptr() as *const Flow)))
+            }
+            _ => Field::Text(bytes.iter().map(|b| format!("{:02x}", b)).collect()),
+        }
+    }
+}
+
+fn read_entries(reader: &DirectoryReader, filter: &[String]) -> Option<(usize, Vec<Dump>)> {
+    let entries: Vec<_> = reader
+        .entries()
+        .into_iter()
+        .filter(|e| e.kind != EntryKind::Name && (filter.is_empty() || filter.contains(&e.name)))
+        .filter_map(|info| match reader.open_entry(&info) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                eprintln!("Could not open {}: {}", info.name, e);
+                None
+            }
+        })
+        .collect();
+    // Copy records out of shared memory while the snapshot is held, so the output is consistent.
+    reader
+        .read_snapshot(|| {
+            entries
+                .iter()
+                .map(|entry| match entry.info().kind {
+                    EntryKind::Counters => entry
+                        .counters()
+                        .into_iter()
+                        .map(|(name, value)| (Field::Text(name), Field::Int(value)))
+                        .collect(),
+                    _ => entry
+                        .records()
+                        .iter()
+                        .map(|&(k, v)| (decode(k), decode(v)))
+                        .collect(),
+                })
+                .collect::<Vec<Vec<_>>>()
+        })
+        .map(|(version, records)| {
+            (
+                version,
+                entries
+                    .iter()
+                    .zip(records.into_iter())
+                    .map(|(entry, records)| Dump {
+                        info: entry.info().clone(),
+                        records: records,
+                    })
+                    .collect(),
+            )
+        })
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_field(field: &Field) -> String {
+    match *field {
+        Field::None => String::from("null"),
+        Field::Int(i) => i.to_string(),
+        Field::Text(ref s) => json_string(s),
+    }
+}
+
+fn text_field(field: &Field) -> String {
+    match *field {
+        Field::None => String::new(),
+        Field::Int(i) => i.to_string(),
+        Field::Text(ref s) => s.clone(),
+    }
+}
+
+fn print_json(version: usize, dumps: &[Dump]) {
+    let entries: Vec<_> = dumps
+        .iter()
+        .map(|dump| {
+            let records: Vec<_> = dump.records
+                .iter()
+                .map(|&(ref k, ref v)| match dump.info.kind {
+                    EntryKind::Vec => json_field(v),
+                    _ => format!("[{}, {}]", json_field(k), json_field(v)),
+                })
+                .collect();
+            format!(
+                "{{\"name\": {}, \"kind\": {}, \"records\": [{}]}}",
+                json_string(&dump.info.name),
+                json_string(&format!("{:?}", dump.info.kind)),
+                records.join(", ")
+            )
+        })
+        .collect();
+    println!("{{\"version\": {}, \"entries\": [{}]}}", version, entries.join(", "));
+}
+
+fn print_text(version: usize, dumps: &[Dump]) {
+    println!("version {}", version);
+    for dump in dumps {
+        println!("{} ({:?}, {} records)", dump.info.name, dump.info.kind, dump.records.len());
+        for (idx, &(ref k, ref v)) in dump.records.iter().enumerate() {
+            match dump.info.kind {
+                EntryKind::Vec => println!("\t[{}] {}", idx, text_field(v)),
+                _ => println!("\t{}: {}", text_field(k), text_field(v)),
+            }
+        }
+    }
+}
+
+fn print_list(reader: &DirectoryReader, json: bool) {
+    let entries = reader.entries();
+    if json {
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|e| {
+                format!(
+                    "{{\"name\": {}, \"kind\": {}, \"size\": {}}}",
+                    json_string(&e.name),
+                    json_string(&format!("{:?}", e.kind)),
+                    e.segment_size
+                )
+            })
+            .collect();
+        println!("[{}]", entries.join(", "));
+    } else {
+        for e in &entries {
+            println!("{}\t{:?}\t{} bytes", e.name, e.kind, e.segment_size);
+        }
+    }
+}
+
+fn main() {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help menu");
+    opts.optflag("j", "json", "print JSON instead of text");
+    opts.optflag("l", "list", "only list directory entries");
+    opts.optopt("w", "watch", "print entries every SECS seconds", "SECS");
+    let args: Vec<String> = env::args().collect();
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => panic!(f.to_string()),
+    };
+    if matches.opt_present("h") || matches.free.is_empty() {
+        let brief = format!("Usage: {} [options] DIRECTORY [ENTRY...]", args[0]);
+        print!("{}", opts.usage(&brief));
+        process::exit(if matches.opt_present("h") { 0 } else { 1 });
+    }
+    let json = matches.opt_present("j");
+    let watch: Option<u64> = matches
+        .opt_str("w")
+        .map(|w| w.parse().expect("Could not parse watch interval"));
+
+    let reader = match DirectoryReader::open(&matches.free[0]) {
+        Ok(reader) => reader,
+        Err(e) => {
+            println!("Could not open directory {}: {}", matches.free[0], e);
+            process::exit(1);
+        }
+    };
+    if matches.opt_present("l") {
+        print_list(&reader, json);
+        return;
+    }
+
+    let filter = &matches.free[1..];
+    loop {
+        match read_entries(&reader, filter) {
+            Some((version, dumps)) => if json {
+                print_json(version, &dumps)
+            } else {
+                print_text(version, &dumps)
+            },
+            None => println!("Could not read a consistent snapshot"),
+        }
+        match watch {
+            Some(secs) => thread::sleep(Duration::from_secs(secs)),
+            None => break,
+        }
+    }
+}